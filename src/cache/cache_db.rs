@@ -1,4 +1,4 @@
-use crate::db::db;
+use crate::db::{engine::engine, index, meta, stats, Rename};
 use crate::cache;
 
 /// Deletes a database and its associated entries from both the file database and the cache.
@@ -13,18 +13,39 @@ use crate::cache;
 /// - If the deletion is successful, it returns a JSON-formatted string with a success message.
 /// - If an error occurs during the deletion, it returns an error message.
 pub fn delete_db(name: &str) -> Result<String, String> {
-    if let Err(err) = db::delete(name) {
-        return Err(err);
+    if !engine().db_exists(name) {
+        return Err("{\"code\": 400, \"message\": \"DataBase not exist\"}".to_string());
     }
 
-    let c_keys: Vec<String> = cache::keys();
-
-    c_keys.iter().for_each(|key| {
-        if key.find(name).is_some() {
-            let k = cache::from_cache_string(key.to_owned());
-            cache::delete(&k.db, &k.table, &k.key).unwrap();
-        }
-    });
+    if let Err(err) = engine().delete_db(name) {
+        return Err(("{\"code\": 500, \"message\": \"".to_owned() + &err + "\"}").to_string());
+    }
+    meta::forget_db(name);
+    index::forget_db(name);
+    stats::forget_db(name);
+    cache::evict_db(name);
 
     return Ok("{\"code\": 200, \"message\": \"DB was delete\"}".to_string());
-}
\ No newline at end of file
+}
+/// Renames a database, carrying over its indexes, deadlines, statistics and cached rows.
+///
+/// The caller has to keep other writers out while the database is renamed.
+///
+/// # Arguments
+///
+/// * `name` - Current database name.
+/// * `to` - New database name.
+///
+/// # Returns
+///
+/// Returns Ok(()) if the database was renamed; Err with an error message otherwise.
+pub fn rename_db(name: &str, to: &str) -> Result<(), String> {
+    engine().rename_db(name, to)?;
+
+    // The metadata moves along with the database
+    meta::forget_db(name);
+    meta::forget_db(to);
+    cache::on_rename(&Rename::db(name, to));
+
+    Ok(())
+}
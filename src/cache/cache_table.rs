@@ -93,9 +93,10 @@ impl Cache {
 
     /// Deletes a cache entry based on the provided key, reducing the current data size.
     pub fn delete(&mut self, key: &str) {
-        self.current_data_size -= self.time_data.get(key).unwrap().data_length;
+        if let Some(time_h) = self.time_data.remove(key) {
+            self.current_data_size = self.current_data_size.saturating_sub(time_h.data_length);
+        }
         self.data.remove(key);
-        self.time_data.remove(key);
     }
 }
 
@@ -114,7 +115,7 @@ fn expand_cache(cache: &mut Cache) {
     }
 
     if lowest_time != std::u128::MAX {
-        cache.current_data_size -= lowest_data_length;
+        cache.current_data_size = cache.current_data_size.saturating_sub(lowest_data_length);
         cache.data.remove(&lowest_key);
         cache.time_data.remove(&lowest_key);
     }
@@ -0,0 +1,47 @@
+//! Per-key locks for read-modify-write operations.
+//!
+//! A locked key blocks other writers of the same row while the rest of the
+//! table stays available.
+
+use std::{collections::HashSet, sync::{Condvar, Mutex, MutexGuard}};
+use lazy_static::lazy_static;
+
+lazy_static! {
+    /// Locked row keys and the condition waiting writers sleep on.
+    static ref LOCKED: (Mutex<HashSet<String>>, Condvar) = (Mutex::new(HashSet::new()), Condvar::new());
+}
+
+/// Holds the lock of one row until it is dropped.
+pub struct KeyGuard {
+    /// Locked cache key.
+    key: String
+}
+
+impl Drop for KeyGuard {
+    fn drop(&mut self) {
+        let (lock, cvar) = &*LOCKED;
+        lock.lock().unwrap().remove(&self.key);
+        cvar.notify_all();
+    }
+}
+
+/// Locks a row, waiting while another writer holds it.
+///
+/// # Arguments
+///
+/// * `key` - Cache key of the row.
+///
+/// # Returns
+///
+/// Returns a guard which releases the lock when dropped.
+pub fn lock(key: &str) -> KeyGuard {
+    let (lock, cvar) = &*LOCKED;
+    let mut locked: MutexGuard<'_, HashSet<String>> = lock.lock().unwrap();
+
+    while locked.contains(key) {
+        locked = cvar.wait(locked).unwrap();
+    }
+    locked.insert(key.to_string());
+
+    KeyGuard { key: key.to_string() }
+}
@@ -1,12 +1,14 @@
 //! Caching module
 pub mod cache_table;
 pub mod cache_db;
+pub mod key_lock;
 
 use std::{sync::{Mutex, MutexGuard}, time::{SystemTime, UNIX_EPOCH}};
 use lazy_static::lazy_static;
 
 use crate::config::CONFIG;
-use crate::db::{row, table};
+use crate::db::{self, checksum, engine::engine, index, meta::{self, DbMeta}, names, schema::{self, Schema}, stats, ttl, Rename};
+use crate::types;
 use crate::protos::row::Row;
 use crate::cache::cache_table::Cache;
 
@@ -24,6 +26,40 @@ struct CacheKey {
     key: String
 }
 
+/// Conditions under which `put` writes a row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PutMode {
+    /// Write only if the key does not exist (NX).
+    Insert,
+
+    /// Write only if the key already exists (XX).
+    Update,
+
+    /// Write in any case.
+    Upsert
+}
+
+impl PutMode {
+    /// Parses a mode name: `nx`, `xx` or `upsert`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "nx" | "insert" => Some(Self::Insert),
+            "xx" | "update" => Some(Self::Update),
+            "upsert" => Some(Self::Upsert),
+            _ => None
+        }
+    }
+
+    /// Returns the name of the mode as given in a `mode` header.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Insert => "nx",
+            Self::Update => "xx",
+            Self::Upsert => "upsert"
+        }
+    }
+}
+
 lazy_static! {
     /// Global cache instance
     pub  static ref CACHE: Mutex<Cache> = Mutex::new(Cache::new(CONFIG.cache_size.try_into().unwrap()));
@@ -43,28 +79,161 @@ lazy_static! {
 ///
 /// Returns true if the addition is successful; false if the key already exists in the cache.
 pub fn add(db: &str, table: &str, key: &str, value: &str, _type: &str) -> bool {
-    let mut cache: MutexGuard<'_, Cache> = CACHE.lock().unwrap();
-    let cache_key: String = to_cache_string(db, table, key).to_string();
+    let mut row: Row = Row::new();
+    if types::set_row_value(&mut row, value, _type).is_err() {
+        return false;
+    }
+
+    add_row(db, table, key, &row)
+}
+
+/// Adds a prepared row to the cache table and the file database.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+/// * `table` - Table name.
+/// * `key` - Key for the new row.
+/// * `row` - Row to be associated with the key.
+///
+/// # Returns
+///
+/// Returns true if the addition is successful; false if the key already exists in the cache.
+pub fn add_row(db: &str, table: &str, key: &str, row: &Row) -> bool {
+    let cache_key: String = to_cache_string(db, table, key);
+    let _guard: key_lock::KeyGuard = key_lock::lock(&cache_key);
+
+    let previous: Option<Row> = match stored(db, table, key) {
+        Ok(Some(row)) if !ttl::is_expired(&row) => return false,
+        Ok(previous) => previous,
+        Err(err) => {
+            println!("[ ERROR ] Cache: Can't add new key - {}. Reason: {}", key, err);
+            return false;
+        }
+    };
 
-    if cache.data.contains_key(&cache_key) {
+    if let Err(err) = store(db, table, key, row, previous.as_ref()) {
+        println!("[ ERROR ] Cache: Can't add new key - {}. Reason: {}", key, err);
         return false;
     }
-    
-    let mut row: Row = Row::new();
-    row.set_value(value.to_string());
-    row.set_type(_type.to_string());
 
-    cache.data.entry(cache_key.clone()).or_insert(row.clone());
+    true
+}
 
-    //update time when updated
+/// Writes a row according to the mode, replacing the stored and the cached copy.
+///
+/// The row is replaced as a whole: the engine writes the new row in one record and
+/// switches the key over to it, so readers see either the old or the new row.
+/// The key lock is held for the whole call, which makes the existence check and
+/// the write atomic with respect to other writers of the row.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+/// * `table` - Table name.
+/// * `key` - Row key.
+/// * `row` - New row.
+/// * `mode` - Write condition.
+///
+/// # Returns
+///
+/// Returns Ok(true) if the row was written, Ok(false) if the mode condition is not met;
+/// Err with an error message if the write failed.
+pub fn put(db: &str, table: &str, key: &str, row: &Row, mode: PutMode) -> Result<bool, String> {
+    let cache_key: String = to_cache_string(db, table, key);
+    let _guard: key_lock::KeyGuard = key_lock::lock(&cache_key);
+
+    let previous: Option<Row> = stored(db, table, key)?;
+    let exists: bool = previous.as_ref().is_some_and(|row| !ttl::is_expired(row));
+    match mode {
+        PutMode::Insert if exists => return Ok(false),
+        PutMode::Update if !exists => return Ok(false),
+        _ => {}
+    }
+
+    store(db, table, key, row, previous.as_ref())?;
+
+    Ok(true)
+}
+
+/// Changes a row under its key lock.
+///
+/// The current row is read, passed to `change` and the returned row is stored.
+/// Other writers of the same key wait until the new row is stored.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+/// * `table` - Table name.
+/// * `key` - Row key.
+/// * `change` - Builds the new row from the current one.
+///
+/// # Returns
+///
+/// Returns the stored row; Err with a message if the row does not exist, `change` fails or the write fails.
+pub fn update<F>(db: &str, table: &str, key: &str, change: F) -> Result<Row, String>
+where
+    F: FnOnce(Row) -> Result<Row, String>
+{
+    upsert(db, table, key, |current| change(current.ok_or("key does not exist")?))
+}
+
+/// Changes or creates a row under its key lock.
+///
+/// Like [`update`], but `change` is also called for a missing row, with None.
+///
+/// # Returns
+///
+/// Returns the stored row; Err with a message if the row can not be read, `change` fails or the write fails.
+pub fn upsert<F>(db: &str, table: &str, key: &str, change: F) -> Result<Row, String>
+where
+    F: FnOnce(Option<Row>) -> Result<Row, String>
+{
+    let _guard: key_lock::KeyGuard = key_lock::lock(&to_cache_string(db, table, key));
+
+    let previous: Option<Row> = stored(db, table, key)?;
+    let current: Option<Row> = previous.clone().filter(|row| !ttl::is_expired(row));
+    let row: Row = change(current)?;
+    store(db, table, key, &row, previous.as_ref())?;
+
+    Ok(row)
+}
+
+/// Reads the stored row of a key, from the cache if it holds a copy.
+///
+/// Expired rows are returned too, the caller decides if they count. A row
+/// which can not be read, such as a corrupted one, is an error rather than
+/// a missing row.
+fn stored(db: &str, table: &str, key: &str) -> Result<Option<Row>, String> {
+    if let Some(cached) = CACHE.lock().unwrap().data.get(&to_cache_string(db, table, key)) {
+        return Ok(Some(cached.clone()));
+    }
+
+    if !engine().table_exists(db, table) {
+        return Ok(None);
+    }
+
+    engine().read_row(db, table, key)
+}
+
+/// Writes a row to the storage, the indexes and the cache.
+///
+/// The caller has to hold the key lock of the row, `previous` is the stored row it replaces.
+/// The cache is only locked once the row is written and flushed, so writes of other
+/// keys share a group commit and reads do not wait for the flush.
+fn store(db: &str, table: &str, key: &str, row: &Row, previous: Option<&Row>) -> Result<(), String> {
+    engine().write_row(db, table, key, row)?;
+    db::on_put(db, table, key, row, previous);
+
+    let mut cache: MutexGuard<'_, Cache> = CACHE.lock().unwrap();
+    let cache_key: String = to_cache_string(db, table, key);
+    cache.insert(cache_key.clone(), row.clone());
     cache.safe_time_insert(&cache_key, TimeCache {
         last_accessed: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_micros(),
-        data_length: value.len()
+        data_length: types::row_size(row)
     });
-    
-    row::add_row(db, table, key, &mut row);
 
-    return true;
+    Ok(())
 }
 
 /// Retrieves a row from the cache table or the file database if not present in the cache.
@@ -77,25 +246,54 @@ pub fn add(db: &str, table: &str, key: &str, value: &str, _type: &str) -> bool {
 ///
 /// # Returns
 ///
-/// Returns a Result containing the retrieved row or an error message if the row is not found.
+/// Returns a Result containing the retrieved row, or an error message if the row is not found or its stored bytes are damaged.
 pub fn get(db: &str, table: &str, key: &str) -> Result<Row, String> {
     let mut cache: MutexGuard<'_, Cache> = CACHE.lock().unwrap();
+    lookup(&mut cache, db, table, key)
+}
+
+/// Retrieves several rows at once, the cache is locked only once.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+/// * `names` - Table name and key of every row.
+///
+/// # Returns
+///
+/// Returns the result of [`get`] for every row, in the order of `names`.
+pub fn get_many(db: &str, names: &[(String, String)]) -> Vec<Result<Row, String>> {
+    let mut cache: MutexGuard<'_, Cache> = CACHE.lock().unwrap();
+
+    names.iter()
+        .map(|(table, key)| lookup(&mut cache, db, table, key))
+        .collect()
+}
+
+/// Looks a row up in the locked cache, reading it from the storage on a miss.
+fn lookup(cache: &mut Cache, db: &str, table: &str, key: &str) -> Result<Row, String> {
     let data: Option<&Row> = cache.get(&to_cache_string(db, table, key));
 
     if let Some(r) = data {
+        if ttl::is_expired(r) {
+            return Err("0".to_string());
+        }
+
         return Ok(r.clone());
     }
 
-    let row: Result<Row, String> = row::read_row(db, table, key);
+    let row: Result<Option<Row>, String> = engine().read_row(db, table, key);
 
     match row {
-        Ok(r) => {
+        Ok(Some(r)) if ttl::is_expired(&r) => Err("0".to_string()),
+        Ok(Some(r)) => {
             let cache_key: String = to_cache_string(db, table, key);
             cache.update_last_accessed(&cache_key);
 
-            return Ok(r);
+            Ok(r)
         },
-        Err(_) => return Err("0".to_string())
+        Err(err) if checksum::is_corrupted(&err) => Err(err),
+        _ => Err("0".to_string())
     }
 }
 
@@ -111,20 +309,89 @@ pub fn get(db: &str, table: &str, key: &str) -> Result<Row, String> {
 ///
 /// Returns a Result indicating the status of the deletion operation.
 pub fn delete(db: &str, table: &str, key: &str) -> Result<String, String> {
-    let mut cache: MutexGuard<'_, Cache> = CACHE.lock().unwrap();
     let cache_key: String = to_cache_string(db, table, key);
-    
-    cache.data.remove(&cache_key);
-    cache.time_data.remove(&cache_key);
+    let _guard: key_lock::KeyGuard = key_lock::lock(&cache_key);
+    let previous: Option<Row> = stored(db, table, key).unwrap_or(None);
+    uncache(&cache_key);
 
-    let status: bool = row::delete_row(db, table, key);
+    let status: bool = engine().delete_row(db, table, key).unwrap_or(false);
     if status {
+        db::on_delete(db, table, key, previous.as_ref());
         return Ok(format!("Row with key {} was deleted", key.to_string()));
     } else {
         return Err(format!("Cant delete row with key {}", key.to_string()));
     }
 }
 
+/// Drops the cached copy of a row, the storage is changed after it without holding the cache lock.
+fn uncache(cache_key: &str) {
+    let mut cache: MutexGuard<'_, Cache> = CACHE.lock().unwrap();
+    cache.data.remove(cache_key);
+    cache.time_data.remove(cache_key);
+}
+
+/// Deletes a row if it is expired, used by the expiry reaper.
+///
+/// The row is checked again under its key lock, so a row which was rewritten
+/// in the meantime is kept.
+///
+/// # Returns
+///
+/// Returns true if the row was deleted.
+pub fn delete_expired(db: &str, table: &str, key: &str) -> bool {
+    let cache_key: String = to_cache_string(db, table, key);
+    let _guard: key_lock::KeyGuard = key_lock::lock(&cache_key);
+
+    let row: Row = match engine().read_row(db, table, key) {
+        Ok(Some(row)) if ttl::is_expired(&row) => row,
+        _ => return false
+    };
+
+    uncache(&cache_key);
+
+    let deleted: bool = engine().delete_row(db, table, key).unwrap_or(false);
+    if deleted {
+        db::on_delete(db, table, key, Some(&row));
+    }
+
+    deleted
+}
+
+/// Counts the rows of a database, or of one of its tables, held in the cache.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+/// * `table` - Table name, None to count the rows of all tables.
+pub fn cached_rows(db: &str, table: Option<&str>) -> usize {
+    let cache: MutexGuard<'_, Cache> = CACHE.lock().unwrap();
+
+    cache.data
+        .keys()
+        .map(|key| from_cache_string(key.to_string()))
+        .filter(|data| data.db == db && (table.is_none() || table == Some(data.table.as_str())))
+        .count()
+}
+
+/// Drops the cached rows of a deleted database.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+pub fn evict_db(db: &str) {
+    let mut cache: MutexGuard<'_, Cache> = CACHE.lock().unwrap();
+    let keys_to_delete: Vec<String> = cache.data
+        .keys()
+        .filter(|key| from_cache_string(key.to_string()).db == db)
+        .cloned()
+        .collect();
+
+    for key in keys_to_delete {
+        cache.data.remove(&key);
+        cache.time_data.remove(&key);
+    }
+}
+
 /// Retrieves a vector of all keys currently present in the cache.
 ///
 /// # Returns
@@ -152,8 +419,9 @@ pub fn clear() {
 /// # Returns
 ///
 /// Returns a formatted cache key string combining the provided database, table, and key.
+/// The names are encoded as on disk, so they never contain the separator.
 fn to_cache_string(db: &str, table: &str, key: &str) -> String {
-    return format!("{}|rdb|{}|rdb|{}", db, table, key);
+    format!("{}|rdb|{}|rdb|{}", names::encode(db), names::encode(table), key)
 }
 
 /// Converts a cache key string into separate components (database, table, and key).
@@ -172,10 +440,10 @@ fn from_cache_string(cache_string: String) -> CacheKey {
         key: String::new(),
     };
 
-    let cs_split: Vec<&str> = cache_string.split("|rdb|").collect();
+    let cs_split: Vec<&str> = cache_string.splitn(3, "|rdb|").collect();
 
-    key.db = cs_split[0].to_string();
-    key.table = cs_split[1].to_string();
+    key.db = names::decode(cs_split[0]).unwrap_or_default();
+    key.table = names::decode(cs_split[1]).unwrap_or_default();
     key.key = cs_split[2].to_string();
 
     return key;
@@ -192,10 +460,13 @@ fn from_cache_string(cache_string: String) -> CacheKey {
 ///
 /// Returns true if the table deletion is successful; false otherwise.
 pub fn delete_table(db: &str, name: &str) -> bool {
-    let status: bool = table::delete_table(db, name);
+    let status: bool = engine().delete_table(db, name);
     if !status {
         return false;
     }
+    index::forget_table(db, name);
+    schema::forget_table(db, name);
+    stats::forget_table(db, name);
 
     let mut cache: MutexGuard<'_, Cache> = CACHE.lock().unwrap();
     let keys_to_delete: Vec<String> = cache.time_data
@@ -214,6 +485,68 @@ pub fn delete_table(db: &str, name: &str) -> bool {
     return true;
 }
 
+/// Renames a table, carrying over its metadata, indexes, deadlines, statistics and cached rows.
+///
+/// The caller has to keep other writers out while the table is renamed.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+/// * `name` - Current table name.
+/// * `to` - New table name.
+///
+/// # Returns
+///
+/// Returns Ok(()) if the table was renamed; Err with an error message otherwise.
+pub fn rename_table(db: &str, name: &str, to: &str) -> Result<(), String> {
+    engine().rename_table(db, name, to)?;
+
+    let mut db_meta: DbMeta = meta::get_db_meta(db);
+    let paths: Option<Vec<String>> = db_meta.indexes.remove(name);
+    let schema: Option<Schema> = db_meta.schemas.remove(name);
+    if paths.is_some() || schema.is_some() {
+        db_meta.indexes.extend(paths.map(|paths| (to.to_string(), paths)));
+        db_meta.schemas.extend(schema.map(|schema| (to.to_string(), schema)));
+        meta::set_db_meta(db, &db_meta)?;
+    }
+
+    on_rename(&Rename::table(db, name, to));
+
+    Ok(())
+}
+
+/// Moves the cached rows and the state kept by the storage modules of renamed tables.
+pub fn on_rename(rename: &Rename) {
+    db::on_rename(rename);
+
+    let mut cache: MutexGuard<'_, Cache> = CACHE.lock().unwrap();
+    let moved: Vec<String> = cache.data
+        .keys()
+        .chain(cache.time_data.keys())
+        .filter(|key| {
+            let data: CacheKey = from_cache_string(key.to_string());
+            rename.map(&data.db, &data.table).is_some()
+        })
+        .cloned()
+        .collect();
+
+    for old in moved {
+        let data: CacheKey = from_cache_string(old.clone());
+        let (to_db, to_table): (String, String) = match rename.map(&data.db, &data.table) {
+            Some(names) => names,
+            None => continue
+        };
+        let new: String = to_cache_string(&to_db, &to_table, &data.key);
+
+        if let Some(row) = cache.data.remove(&old) {
+            cache.data.insert(new.clone(), row);
+        }
+        if let Some(time_h) = cache.time_data.remove(&old) {
+            cache.time_data.insert(new, time_h);
+        }
+    }
+}
+
 // pub fn insert_proto(event: Row) {
 //     CACHE.lock().unwrap().insert(event.id().to_string(), event.clone());
 //     let d_event: db::Row = db::Row{
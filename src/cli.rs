@@ -0,0 +1,116 @@
+//! Offline command line tools.
+//!
+//! ```text
+//! rdsync export <db> <table> <file> [--format ndjson|csv] [--columns column:field,...] [--key-column name]
+//! rdsync import <db> <table> <file> [--format ndjson|csv] [--columns column:field,...] [--key-column name] [--mode nx|xx|upsert]
+//! ```
+//!
+//! The tools open the storage directly, so they have to run while the server is stopped.
+
+use std::{collections::HashMap, fs::File, io::{BufReader, BufWriter}};
+
+use crate::{cache::PutMode, db::{self, transfer::{self, CsvColumns, Format}}};
+
+/// Usage of the command line tools.
+const USAGE: &str = "usage:
+  rdsync export <db> <table> <file> [--format ndjson|csv] [--columns column:field,...] [--key-column name]
+  rdsync import <db> <table> <file> [--format ndjson|csv] [--columns column:field,...] [--key-column name] [--mode nx|xx|upsert]";
+
+/// Runs a command line tool if one is given in the arguments.
+///
+/// # Arguments
+///
+/// * `args` - Process arguments, including the program name.
+///
+/// # Returns
+///
+/// Returns the exit code of the tool, or None if the server should be started.
+pub fn run(args: &[String]) -> Option<i32> {
+    let command: &str = args.get(1)?.as_str();
+    if command != "export" && command != "import" {
+        eprintln!("unknown command `{}`\n{}", command, USAGE);
+        return Some(2);
+    }
+
+    let (positional, options): (Vec<&str>, HashMap<&str, &str>) = match parse_args(&args[2..]) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            eprintln!("{}\n{}", err, USAGE);
+            return Some(2);
+        }
+    };
+
+    let (db_name, table, path): (&str, &str, &str) = match positional[..] {
+        [db_name, table, path] => (db_name, table, path),
+        _ => {
+            eprintln!("{}", USAGE);
+            return Some(2);
+        }
+    };
+
+    let format: Format = match Format::parse(options.get("format").unwrap_or(&"ndjson")) {
+        Some(format) => format,
+        None => {
+            eprintln!("format must be ndjson or csv");
+            return Some(2);
+        }
+    };
+    let columns: CsvColumns = CsvColumns::parse(options.get("key-column").copied(), options.get("columns").copied());
+
+    if !db::init() {
+        eprintln!("[ ERROR ] CLI: Can not init DataBase");
+        return Some(1);
+    }
+
+    let result: Result<String, String> = if command == "export" {
+        File::create(path)
+            .map_err(|e| format!("can not create {} - {}", path, e))
+            .and_then(|file| transfer::export(db_name, table, format, &columns, &mut BufWriter::new(file)))
+            .and_then(|report| serde_json::to_string(&report).map_err(|e| e.to_string()))
+    } else {
+        let mode: PutMode = match PutMode::parse(options.get("mode").unwrap_or(&"upsert")) {
+            Some(mode) => mode,
+            None => {
+                eprintln!("mode must be nx, xx or upsert");
+                return Some(2);
+            }
+        };
+
+        File::open(path)
+            .map_err(|e| format!("can not open {} - {}", path, e))
+            .and_then(|file| transfer::import(db_name, table, format, &columns, mode, BufReader::new(file)))
+            .and_then(|report| serde_json::to_string(&report).map_err(|e| e.to_string()))
+    };
+
+    match result {
+        Ok(report) => {
+            println!("{}", report);
+            Some(0)
+        },
+        Err(err) => {
+            eprintln!("[ ERROR ] CLI: {}", err);
+            Some(1)
+        }
+    }
+}
+
+/// Splits arguments into positional ones and `--name value` options.
+fn parse_args(args: &[String]) -> Result<(Vec<&str>, HashMap<&str, &str>), String> {
+    let mut positional: Vec<&str> = Vec::new();
+    let mut options: HashMap<&str, &str> = HashMap::new();
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        match arg.strip_prefix("--") {
+            Some(name) => match iter.next() {
+                Some(value) => {
+                    options.insert(name, value.as_str());
+                },
+                None => return Err(format!("option --{} needs a value", name))
+            },
+            None => positional.push(arg.as_str())
+        }
+    }
+
+    Ok((positional, options))
+}
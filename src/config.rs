@@ -20,7 +20,62 @@ pub struct Config {
     pub cache_size: u16,
 
     /// Thread pool size
-    pub workers_count: u16
+    pub workers_count: u16,
+
+    /// Max size of one table segment file (in MB)
+    pub segment_size: u16,
+
+    /// Default durability level of writes
+    pub durability: Durability,
+
+    /// Interval between two group commits (in ms)
+    pub group_commit_interval: u64,
+
+    /// Storage engine (`fs` or `memory`)
+    pub engine: String,
+
+    /// Directory of the snapshot archives, the archive paths of requests are relative to it
+    pub snapshot_path: String,
+
+    /// Directory of the files tables are exported to and imported from, the paths of requests are relative to it
+    pub transfer_path: String,
+
+    /// Time after which an open transaction without requests is rolled back (in seconds)
+    pub tx_idle_timeout: u64
+}
+
+/// Defines when written data is flushed to the disk
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Durability {
+    /// Leave flushing to the operating system
+    None,
+
+    /// Flush every write before it is acknowledged
+    FsyncEveryWrite,
+
+    /// Flush writes in batches every `group_commit_interval`, acknowledging them after the flush
+    GroupCommit
+}
+
+impl Durability {
+    /// Parses a durability level from its configuration name.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "none" => Some(Durability::None),
+            "fsync-every-write" => Some(Durability::FsyncEveryWrite),
+            "group-commit" => Some(Durability::GroupCommit),
+            _ => None
+        }
+    }
+
+    /// Returns the configuration name of the durability level.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Durability::None => "none",
+            Durability::FsyncEveryWrite => "fsync-every-write",
+            Durability::GroupCommit => "group-commit"
+        }
+    }
 }
 
 /// Enum to handle configuration-related errors
@@ -31,6 +86,9 @@ pub enum ConfigError {
 
     /// TOML parsing error
     TomlError(toml::de::Error),
+
+    /// Invalid configuration value
+    ValueError(String),
 }
 
 /// Lazy static instance of the configuration
@@ -50,7 +108,14 @@ impl Default for Config {
             ip: "127.0.0.1".to_string(),
             port: 7045,
             cache_size: 20,
-            workers_count: 128
+            workers_count: 128,
+            segment_size: 64,
+            durability: Durability::None,
+            group_commit_interval: 10,
+            engine: "fs".to_string(),
+            snapshot_path: "snapshots".to_string(),
+            transfer_path: "transfers".to_string(),
+            tx_idle_timeout: 60
         }
     }
 }
@@ -83,5 +148,34 @@ pub fn read_config() -> Result<Config, ConfigError> {
         conf.workers_count = workers_count.try_into().unwrap();
     }
 
+    if let Some(segment_size) = toml_value.get("SEGMENT_SIZE").and_then(|v| v.as_integer()) {
+        conf.segment_size = segment_size.try_into().unwrap();
+    }
+
+    if let Some(durability) = toml_value.get("DURABILITY").and_then(|v| v.as_str()) {
+        conf.durability = Durability::parse(durability)
+            .ok_or(ConfigError::ValueError(format!("unknown durability level `{}`", durability)))?;
+    }
+
+    if let Some(interval) = toml_value.get("GROUP_COMMIT_INTERVAL").and_then(|v| v.as_integer()) {
+        conf.group_commit_interval = interval.try_into().unwrap();
+    }
+
+    if let Some(engine) = toml_value.get("ENGINE").and_then(|v| v.as_str()) {
+        conf.engine = engine.to_string();
+    }
+
+    if let Some(snapshot_path) = toml_value.get("SNAPSHOT_PATH").and_then(|v| v.as_str()) {
+        conf.snapshot_path = snapshot_path.to_string();
+    }
+
+    if let Some(transfer_path) = toml_value.get("TRANSFER_PATH").and_then(|v| v.as_str()) {
+        conf.transfer_path = transfer_path.to_string();
+    }
+
+    if let Some(timeout) = toml_value.get("TX_IDLE_TIMEOUT").and_then(|v| v.as_integer()) {
+        conf.tx_idle_timeout = timeout.try_into().unwrap();
+    }
+
     Ok(conf)
 }
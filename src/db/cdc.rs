@@ -0,0 +1,340 @@
+//! Change-data-capture subscriptions.
+//!
+//! A client session subscribes to a database, to one table of it or to the
+//! keys of a table starting with a prefix. Every row added, updated or
+//! deleted afterwards is pushed to the session as an event frame carrying
+//! the `rud` of the `subscribe` request. The events of one subscription are
+//! numbered from 1 without gaps, so a client can tell that it missed one.
+//!
+//! Events are built by the write hooks ([`crate::db::on_put`] and
+//! [`crate::db::on_delete`]) while the row is locked, which keeps them in the
+//! order of the writes, and are sent by a separate thread, so a slow
+//! subscriber never holds up a write. The events of a transaction commit are
+//! held back with [`defer`] and published once all of its rows are written,
+//! so the writes a failed commit puts back are never seen. Subscriptions are
+//! dropped when their session disconnects.
+
+use std::{cell::RefCell, collections::HashMap, sync::{mpsc::{self, Receiver, Sender}, Mutex, MutexGuard}, thread};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{db::Rename, http, protos::row::Row, types};
+
+/// Kind of a row change.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Op {
+    /// A new row was written.
+    Add,
+
+    /// An existing row was replaced.
+    Update,
+
+    /// A row was deleted.
+    Delete
+}
+
+/// Rows watched by one subscription.
+#[derive(Debug, Clone)]
+struct Subscription {
+    /// Client session which receives the events.
+    session: String,
+
+    /// Request flag of the `subscribe` request, sent with every event.
+    rud: String,
+
+    /// Database name.
+    db: String,
+
+    /// Table name, None for all tables of the database.
+    table: Option<String>,
+
+    /// Key prefix, empty for all keys.
+    prefix: String,
+
+    /// Number of the last sent event.
+    seq: u64
+}
+
+impl Subscription {
+    /// Checks if a row is watched by the subscription.
+    fn matches(&self, db: &str, table: &str, key: &str) -> bool {
+        self.db == db
+            && self.table.iter().all(|watched| watched == table)
+            && key.starts_with(&self.prefix)
+    }
+}
+
+/// Event frame of one row change.
+#[derive(Serialize, Debug)]
+struct Event<'a> {
+    /// Subscription id.
+    subscription: &'a str,
+
+    /// Number of the event within the subscription.
+    seq: u64,
+
+    /// Kind of the change.
+    op: Op,
+
+    /// Database name.
+    db: &'a str,
+
+    /// Table name.
+    table: &'a str,
+
+    /// Row key.
+    key: &'a str,
+
+    /// New value in its text form, None for a deleted row.
+    value: Option<&'a str>,
+
+    /// Type of the new value, None for a deleted row.
+    #[serde(rename = "type")]
+    _type: Option<&'a str>
+}
+
+/// An event waiting to be sent.
+struct Frame {
+    /// Client session.
+    to: String,
+
+    /// Request flag of the subscription.
+    rud: String,
+
+    /// Event as JSON.
+    data: String
+}
+
+/// A change held back until the writes around it are done.
+struct Change {
+    /// Kind of the change.
+    op: Op,
+
+    /// Database name.
+    db: String,
+
+    /// Table name.
+    table: String,
+
+    /// Row key.
+    key: String,
+
+    /// New row, None for a deleted row.
+    row: Option<Row>
+}
+
+/// Holds back the events of the writes made by the current thread until
+/// [`Deferred::publish`] is called. Dropping it discards them.
+pub struct Deferred(());
+
+impl Deferred {
+    /// Publishes the held back events in the order of their writes.
+    pub fn publish(self) {
+        let changes: Vec<Change> = DEFERRED.with(|deferred| deferred.borrow_mut().take()).unwrap_or_default();
+
+        for change in changes {
+            publish(change.op, &change.db, &change.table, &change.key, change.row.as_ref());
+        }
+    }
+}
+
+impl Drop for Deferred {
+    fn drop(&mut self) {
+        DEFERRED.with(|deferred| deferred.borrow_mut().take());
+    }
+}
+
+thread_local! {
+    /// Changes held back by a [`Deferred`] of the thread, None when nothing is held back.
+    static DEFERRED: RefCell<Option<Vec<Change>>> = const { RefCell::new(None) };
+}
+
+lazy_static! {
+    /// Subscriptions by id.
+    static ref SUBSCRIPTIONS: Mutex<HashMap<String, Subscription>> = Mutex::new(HashMap::new());
+
+    /// Queue of the sending thread, started with the first event.
+    static ref OUTBOX: Mutex<Sender<Frame>> = Mutex::new(start_sender());
+}
+
+/// Subscribes a session to the changes of a database, a table or a key prefix.
+///
+/// # Arguments
+///
+/// * `session` - Client session which receives the events.
+/// * `rud` - Request flag sent with every event.
+/// * `db` - Database name.
+/// * `table` - Table name, None for all tables.
+/// * `prefix` - Key prefix, empty for all keys.
+///
+/// # Returns
+///
+/// Returns the id of the subscription, None if the session is already closed.
+pub fn subscribe(session: &str, rud: &str, db: &str, table: Option<&str>, prefix: &str) -> Option<String> {
+    let id: String = Uuid::new_v4().to_string();
+    let mut subscriptions: MutexGuard<'_, HashMap<String, Subscription>> = SUBSCRIPTIONS.lock().unwrap();
+
+    // Checked under the lock, a session closing after it drops the subscription with the others
+    if !http::is_connected(session) {
+        return None;
+    }
+
+    subscriptions.insert(id.clone(), Subscription {
+        session: session.to_string(),
+        rud: rud.to_string(),
+        db: db.to_string(),
+        table: table.map(|table| table.to_string()),
+        prefix: prefix.to_string(),
+        seq: 0
+    });
+
+    Some(id)
+}
+
+/// Cancels a subscription of a session.
+///
+/// # Returns
+///
+/// Returns false if the session has no subscription with the id.
+pub fn unsubscribe(id: &str, session: &str) -> bool {
+    let mut subscriptions: MutexGuard<'_, HashMap<String, Subscription>> = SUBSCRIPTIONS.lock().unwrap();
+
+    match subscriptions.get(id) {
+        Some(subscription) if subscription.session == session => subscriptions.remove(id).is_some(),
+        _ => false
+    }
+}
+
+/// Drops all subscriptions of a disconnected session.
+pub fn drop_session(session: &str) {
+    let mut subscriptions: MutexGuard<'_, HashMap<String, Subscription>> = SUBSCRIPTIONS.lock().unwrap();
+    let before: usize = subscriptions.len();
+
+    subscriptions.retain(|_, subscription| subscription.session != session);
+    if subscriptions.len() < before {
+        println!("[ INFO ] CDC: dropped {} subscriptions of a closed session", before - subscriptions.len());
+    }
+}
+
+/// Starts holding back the events of the writes made by the current thread.
+///
+/// # Returns
+///
+/// Returns the guard which publishes or discards the events.
+pub fn defer() -> Deferred {
+    DEFERRED.with(|deferred| *deferred.borrow_mut() = Some(Vec::new()));
+    Deferred(())
+}
+
+/// Publishes a written row.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+/// * `table` - Table name.
+/// * `key` - Row key.
+/// * `row` - Written row.
+/// * `created` - The key did not exist before the write.
+pub fn on_put(db: &str, table: &str, key: &str, row: &Row, created: bool) {
+    let op: Op = if created { Op::Add } else { Op::Update };
+    record(op, db, table, key, Some(row));
+}
+
+/// Publishes a deleted row.
+pub fn on_delete(db: &str, table: &str, key: &str) {
+    record(Op::Delete, db, table, key, None);
+}
+
+/// Moves the subscriptions of renamed tables and databases to the new names.
+pub fn on_rename(rename: &Rename) {
+    for subscription in SUBSCRIPTIONS.lock().unwrap().values_mut() {
+        match subscription.table.as_deref() {
+            Some(table) => if let Some((db, table)) = rename.map(&subscription.db, table) {
+                subscription.db = db;
+                subscription.table = Some(table);
+            },
+            None => if rename.table.is_none() && rename.db == subscription.db {
+                subscription.db = rename.to_db.clone();
+            }
+        }
+    }
+}
+
+/// Publishes a change, or holds it back if the thread defers its events.
+fn record(op: Op, db: &str, table: &str, key: &str, row: Option<&Row>) {
+    let held: bool = DEFERRED.with(|deferred| match deferred.borrow_mut().as_mut() {
+        Some(changes) => {
+            changes.push(Change {
+                op,
+                db: db.to_string(),
+                table: table.to_string(),
+                key: key.to_string(),
+                row: row.cloned()
+            });
+            true
+        },
+        None => false
+    });
+
+    if !held {
+        publish(op, db, table, key, row);
+    }
+}
+
+/// Queues the event of a change for every subscription watching the row.
+fn publish(op: Op, db: &str, table: &str, key: &str, row: Option<&Row>) {
+    let mut subscriptions: MutexGuard<'_, HashMap<String, Subscription>> = SUBSCRIPTIONS.lock().unwrap();
+    if subscriptions.is_empty() {
+        return;
+    }
+
+    let value: Option<String> = row.map(types::row_value);
+    let mut frames: Vec<Frame> = Vec::new();
+
+    for (id, subscription) in subscriptions.iter_mut() {
+        if !subscription.matches(db, table, key) {
+            continue;
+        }
+
+        subscription.seq += 1;
+        let event: Event = Event {
+            subscription: id,
+            seq: subscription.seq,
+            op,
+            db,
+            table,
+            key,
+            value: value.as_deref(),
+            _type: row.map(|row| row.type_())
+        };
+
+        frames.push(Frame {
+            to: subscription.session.clone(),
+            rud: subscription.rud.clone(),
+            data: serde_json::to_string(&event).unwrap() + "\njson"
+        });
+    }
+
+    // Queued while the subscriptions are locked, so the events keep their order
+    let outbox: MutexGuard<'_, Sender<Frame>> = OUTBOX.lock().unwrap();
+    for frame in frames {
+        if outbox.send(frame).is_err() {
+            println!("[ ERROR ] CDC: the sending thread is gone, an event was dropped");
+        }
+    }
+}
+
+/// Starts the thread which sends the queued events to the subscribers.
+fn start_sender() -> Sender<Frame> {
+    let (sender, receiver): (Sender<Frame>, Receiver<Frame>) = mpsc::channel();
+
+    thread::spawn(move || {
+        for frame in receiver {
+            http::send(&frame.rud, &frame.data, &frame.to);
+        }
+    });
+
+    sender
+}
@@ -0,0 +1,88 @@
+//! CRC-32 (IEEE) checksums of stored data.
+
+/// Beginning of the error returned for a row whose stored bytes are damaged.
+pub const CORRUPTED: &str = "[ ERROR ] Corrupted row";
+
+/// Lookup table of the reversed 0xEDB88320 polynomial.
+static TABLE: [u32; 256] = build_table();
+
+/// Builds the lookup table at compile time.
+const fn build_table() -> [u32; 256] {
+    let mut table: [u32; 256] = [0; 256];
+    let mut i: usize = 0;
+
+    while i < 256 {
+        let mut crc: u32 = i as u32;
+        let mut bit: u32 = 0;
+
+        while bit < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+            bit += 1;
+        }
+
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+}
+
+/// Incremental CRC-32 of a stream of bytes.
+#[derive(Debug, Clone, Copy)]
+pub struct Crc32 {
+    /// Inverted running checksum.
+    state: u32
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Crc32 {
+    /// Starts a new checksum.
+    pub fn new() -> Self {
+        Self { state: 0xFFFF_FFFF }
+    }
+
+    /// Adds bytes to the checksum.
+    pub fn update(&mut self, data: &[u8]) {
+        for byte in data {
+            self.state = TABLE[((self.state ^ *byte as u32) & 0xFF) as usize] ^ (self.state >> 8);
+        }
+    }
+
+    /// Returns the checksum of all added bytes.
+    pub fn finish(&self) -> u32 {
+        !self.state
+    }
+
+    /// Returns the checksum as an 8 digit hex string.
+    pub fn hex(&self) -> String {
+        format!("{:08x}", self.finish())
+    }
+}
+
+/// Computes the CRC-32 of a byte slice.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc: Crc32 = Crc32::new();
+    crc.update(data);
+    crc.finish()
+}
+
+
+/// Builds the error of a damaged row.
+///
+/// # Arguments
+///
+/// * `key` - Row key.
+/// * `reason` - What is wrong with the stored bytes.
+pub fn corrupted(key: &str, reason: &str) -> String {
+    format!("{} - {}. Reason: {}", CORRUPTED, key, reason)
+}
+
+/// Checks if an error reports a damaged row.
+pub fn is_corrupted(err: &str) -> bool {
+    err.starts_with(CORRUPTED)
+}
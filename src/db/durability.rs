@@ -0,0 +1,134 @@
+//! Flushing of written data according to the durability level.
+//!
+//! The level comes from the database metadata and falls back to the server
+//! configuration. Writers call [`sync`] after every write and the request is
+//! acknowledged only after it returns, so a reply sent through `http::send`
+//! always meets the chosen guarantee.
+
+use std::{collections::HashMap, fs::File, sync::{Condvar, Mutex, MutexGuard, Once}, thread, time};
+use lazy_static::lazy_static;
+
+use crate::{config::{self, Durability}, db::meta};
+
+/// Files waiting for the next group commit.
+struct GroupState {
+    /// Handles to flush in the collected batch.
+    pending: Vec<File>,
+
+    /// Id of the batch which is being collected.
+    batch: u64,
+
+    /// Id of the last flushed batch.
+    synced: u64,
+
+    /// Errors of failed batches by id, with the number of writers which have not seen it yet.
+    failed: HashMap<u64, (String, usize)>
+}
+
+lazy_static! {
+    /// Group commit state and the condition writers wait on.
+    static ref GROUP: (Mutex<GroupState>, Condvar) = (
+        Mutex::new(GroupState { pending: Vec::new(), batch: 1, synced: 0, failed: HashMap::new() }),
+        Condvar::new()
+    );
+}
+
+/// Starts the group commit thread once.
+static FLUSHER: Once = Once::new();
+
+/// Returns the durability level of a database.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+///
+/// # Returns
+///
+/// Returns the database override if it is set, otherwise the server default.
+pub fn level(db: &str) -> Durability {
+    meta::with_db_meta(db, |meta| meta.durability.as_deref().and_then(Durability::parse))
+        .unwrap_or(config::CONFIG.durability)
+}
+
+/// Makes the data written to a file durable according to the level of the database.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+/// * `file` - File the data was written to.
+///
+/// # Returns
+///
+/// Returns Ok(()) once the data meets the durability level; Err with an error message otherwise.
+pub fn sync(db: &str, file: &File) -> Result<(), String> {
+    match level(db) {
+        Durability::None => Ok(()),
+        Durability::FsyncEveryWrite => file.sync_data().map_err(|e| format!("[ ERROR ] Durability: Can not sync - {}", e)),
+        Durability::GroupCommit => group_sync(file)
+    }
+}
+
+/// Adds a file to the collected batch and waits until the batch is flushed.
+fn group_sync(file: &File) -> Result<(), String> {
+    FLUSHER.call_once(start_flusher);
+
+    let handle: File = file.try_clone().map_err(|e| e.to_string())?;
+    let (lock, cvar) = &*GROUP;
+
+    let mut state: MutexGuard<'_, GroupState> = lock.lock().unwrap();
+    state.pending.push(handle);
+    let batch: u64 = state.batch;
+
+    while state.synced < batch {
+        state = cvar.wait(state).unwrap();
+    }
+
+    // The error stays until every writer of the batch got it, later batches do not clear it
+    let (err, waiting): &mut (String, usize) = match state.failed.get_mut(&batch) {
+        Some(failed) => failed,
+        None => return Ok(())
+    };
+    let err: String = err.clone();
+    *waiting -= 1;
+    if *waiting == 0 {
+        state.failed.remove(&batch);
+    }
+
+    Err(err)
+}
+
+/// Spawns the thread which flushes the collected batch every `group_commit_interval`.
+fn start_flusher() {
+    thread::spawn(|| {
+        let interval: time::Duration = time::Duration::from_millis(config::CONFIG.group_commit_interval);
+        let (lock, cvar) = &*GROUP;
+
+        loop {
+            thread::sleep(interval);
+
+            let (files, batch): (Vec<File>, u64) = {
+                let mut state: MutexGuard<'_, GroupState> = lock.lock().unwrap();
+                if state.pending.is_empty() {
+                    continue;
+                }
+
+                let batch: u64 = state.batch;
+                state.batch += 1;
+                (std::mem::take(&mut state.pending), batch)
+            };
+
+            let errors: Vec<String> = files.iter()
+                .filter_map(|f| f.sync_data().err())
+                .map(|e| format!("[ ERROR ] Durability: Can not sync - {}", e))
+                .collect();
+            let error: Option<String> = errors.into_iter().next();
+
+            let mut state: MutexGuard<'_, GroupState> = lock.lock().unwrap();
+            state.synced = batch;
+            if let Some(err) = error {
+                state.failed.insert(batch, (err, files.len()));
+            }
+            cvar.notify_all();
+        }
+    });
+}
@@ -0,0 +1,334 @@
+//! File system storage engine.
+//!
+//! Layout: `{db_path}/{db}/{table}/` directories, rows of a table are kept
+//! in the segment files of its directory. Database and table names are
+//! encoded with [`names::encode`] before they become directory names.
+
+use std::{fs::{self, OpenOptions, ReadDir}, io::{Error, Write}, path::Path, time::{SystemTime, UNIX_EPOCH}};
+use protobuf::Message;
+
+use crate::{config, db::{checksum, names, segment}, protos::row::Row};
+use super::{CapturedRows, KeyRange, SkippedRecord, StorageEngine};
+
+/// Name of the metadata file inside a database directory.
+const META_FILE: &str = "meta.json";
+
+/// Storage engine keeping databases and tables as directories.
+pub struct FsEngine;
+
+impl FsEngine {
+    /// Creates a new file system engine.
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for FsEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Builds the directory of a database.
+pub fn db_dir(db: &str) -> String {
+    format!("{}/{}", config::CONFIG.db_path, names::encode(db))
+}
+
+/// Builds the directory of a table.
+pub fn table_dir(db: &str, table: &str) -> String {
+    format!("{}/{}", db_dir(db), names::encode(table))
+}
+
+/// Initializes a directory at the specified path if it does not exist.
+///
+/// # Arguments
+///
+/// * `path` - Path of the directory to be initialized.
+///
+/// # Returns
+///
+/// Returns true if the directory is successfully created or already exists; false otherwise.
+fn init_dir(path: &str) -> bool {
+    let dir: &Path = Path::new(&path);
+    if !dir.exists() {
+        let result: Result<(), Error> = fs::create_dir_all(path);
+        match result {
+            Ok(_) => {
+                println!("[ INFO ] DB: dir created");
+                return true;
+            },
+            Err(error) => {
+                println!("[ ERROR ] DB: directory not created - {}", error);
+                return false;
+            },
+        }
+    }
+
+    true
+}
+
+/// Test for checking if a directory exists.
+#[test]
+fn is_dir_exist_test() {
+    assert!(!is_dir_exist("table"));
+}
+
+/// Checks if a directory exists at the specified path.
+///
+/// # Arguments
+///
+/// * `path` - Path of the directory to be checked.
+///
+/// # Returns
+///
+/// Returns true if the directory exists; false otherwise.
+fn is_dir_exist(path: &str) -> bool {
+    let db_path: &str = &config::CONFIG.db_path;
+
+    let format_path: String = format!("{}/{}", db_path, path);
+    let dir: &Path = Path::new(&format_path);
+    dir.exists()
+}
+
+/// Lists the names of subdirectories of a directory.
+fn list_dirs(path: &str) -> Result<Vec<String>, String> {
+    let data: ReadDir = fs::read_dir(path).map_err(|e| e.to_string())?;
+
+    let mut list: Vec<String> = data
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().to_str().and_then(names::decode))
+        .collect();
+    list.sort();
+
+    Ok(list)
+}
+
+/// Converts a file time into Unix time in seconds.
+fn unix_secs(time: std::io::Result<SystemTime>) -> Option<u64> {
+    time.ok()?.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs())
+}
+
+/// Renames the directories of a directory whose names are not encoded yet.
+///
+/// Directories made before the names were encoded keep their raw name, a name
+/// like `my db` is moved to `my%20db` so it is found again.
+fn migrate_dirs(path: &str) -> Result<(), String> {
+    let data: ReadDir = fs::read_dir(path).map_err(|e| e.to_string())?;
+
+    for entry in data.filter_map(|entry| entry.ok()).filter(|entry| entry.path().is_dir()) {
+        let raw: String = entry.file_name().to_string_lossy().to_string();
+        let canonical: bool = names::decode(&raw).is_some_and(|name| names::encode(&name) == raw);
+
+        let dir: String = if canonical {
+            format!("{}/{}", path, raw)
+        } else {
+            let target: String = format!("{}/{}", path, names::encode(&raw));
+            fs::rename(entry.path(), &target).map_err(|e| format!("can not rename {} - {}", raw, e))?;
+            println!("[ INFO ] DB: renamed directory `{}` to its encoded name", raw);
+            target
+        };
+
+        if path == config::CONFIG.db_path {
+            migrate_dirs(&dir)?;
+        }
+    }
+
+    Ok(())
+}
+
+impl StorageEngine for FsEngine {
+    fn init(&self) -> bool {
+        if !init_dir(&config::CONFIG.db_path) {
+            return false;
+        }
+
+        if let Err(err) = migrate_dirs(&config::CONFIG.db_path) {
+            println!("[ ERROR ] DB: Can not encode directory names - {}", err);
+            return false;
+        }
+
+        segment::load_all()
+    }
+
+    fn start(&self) {
+        segment::start_compaction();
+    }
+
+    fn is_persistent(&self) -> bool {
+        true
+    }
+
+    fn create_db(&self, db: &str) -> bool {
+        if names::check_name("database", db).is_err() || self.db_exists(db) {
+            return false;
+        }
+
+        init_dir(&db_dir(db))
+    }
+
+    fn delete_db(&self, db: &str) -> Result<(), String> {
+        segment::delete_db(db)
+    }
+
+    fn db_exists(&self, db: &str) -> bool {
+        !db.is_empty() && is_dir_exist(&names::encode(db))
+    }
+
+    fn list_dbs(&self) -> Result<Vec<String>, String> {
+        list_dirs(&config::CONFIG.db_path)
+    }
+
+    fn read_db_meta(&self, db: &str) -> Option<String> {
+        fs::read_to_string(format!("{}/{}", db_dir(db), META_FILE)).ok()
+    }
+
+    fn write_db_meta(&self, db: &str, meta: &str) -> Result<(), String> {
+        names::check_name("database", db)?;
+        if !self.db_exists(db) {
+            self.create_db(db);
+        }
+
+        let path: String = format!("{}/{}", db_dir(db), META_FILE);
+
+        // Written through a temp file, so a crash never leaves half of the file
+        let tmp_path: String = format!("{}.tmp", path);
+        fs::write(&tmp_path, meta).map_err(|e| format!("[ ERROR ] Meta: Can not write {} - {}", tmp_path, e))?;
+        fs::rename(&tmp_path, &path).map_err(|e| format!("[ ERROR ] Meta: Can not write {} - {}", path, e))
+    }
+
+    fn read_table_file(&self, db: &str, table: &str, name: &str) -> Option<String> {
+        fs::read_to_string(format!("{}/{}", table_dir(db, table), name)).ok()
+    }
+
+    fn append_table_file(&self, db: &str, table: &str, name: &str, content: &str) -> Result<(), String> {
+        let path: String = format!("{}/{}", table_dir(db, table), name);
+
+        OpenOptions::new().create(true).append(true).open(&path)
+            .and_then(|mut file| file.write_all(content.as_bytes()))
+            .map_err(|e| format!("[ ERROR ] DB: Can not write {} - {}", path, e))
+    }
+
+    fn write_table_file(&self, db: &str, table: &str, name: &str, content: &str) -> Result<(), String> {
+        let path: String = format!("{}/{}", table_dir(db, table), name);
+
+        // Written through a temp file, so a crash never leaves half of the file
+        let tmp_path: String = format!("{}.tmp", path);
+        fs::write(&tmp_path, content).map_err(|e| format!("[ ERROR ] DB: Can not write {} - {}", tmp_path, e))?;
+        fs::rename(&tmp_path, &path).map_err(|e| format!("[ ERROR ] DB: Can not write {} - {}", path, e))
+    }
+
+    fn create_table(&self, db: &str, table: &str) -> bool {
+        if names::check_name("database", db).is_err() || names::check_name("table", table).is_err() {
+            return false;
+        }
+
+        if !self.db_exists(db) {
+            self.create_db(db);
+        }
+
+        init_dir(&table_dir(db, table))
+    }
+
+    fn delete_table(&self, db: &str, table: &str) -> bool {
+        segment::delete_table(db, table).is_ok()
+    }
+
+    fn table_exists(&self, db: &str, table: &str) -> bool {
+        !db.is_empty() && !table.is_empty() && is_dir_exist(&format!("{}/{}", names::encode(db), names::encode(table)))
+    }
+
+    fn list_tables(&self, db: &str) -> Result<Vec<String>, String> {
+        list_dirs(&db_dir(db))
+    }
+
+    fn rename_table(&self, db: &str, table: &str, to: &str) -> Result<(), String> {
+        names::check_name("table", to)?;
+        if !self.table_exists(db, table) {
+            return Err("no such table".to_string());
+        }
+        if self.table_exists(db, to) {
+            return Err(format!("table {} already exists", to));
+        }
+
+        segment::rename_dir(&table_dir(db, table), &table_dir(db, to))
+    }
+
+    fn rename_db(&self, db: &str, to: &str) -> Result<(), String> {
+        names::check_name("database", to)?;
+        if !self.db_exists(db) {
+            return Err("no such database".to_string());
+        }
+        if self.db_exists(to) {
+            return Err(format!("database {} already exists", to));
+        }
+
+        segment::rename_dir(&db_dir(db), &db_dir(to))
+    }
+
+    fn write_row(&self, db: &str, table: &str, key: &str, row: &Row) -> Result<(), String> {
+        names::check_row(db, table, key)?;
+        if !self.table_exists(db, table) {
+            self.create_table(db, table);
+        }
+
+        let bytes: Vec<u8> = row.write_to_bytes().map_err(|e| e.to_string())?;
+        segment::put(db, table, key, &bytes)
+    }
+
+    fn read_row(&self, db: &str, table: &str, key: &str) -> Result<Option<Row>, String> {
+        let bytes: Vec<u8> = match segment::get(db, table, key)? {
+            Some(b) => b,
+            None => return Ok(None)
+        };
+
+        decode_row(key, &bytes).map(Some)
+    }
+
+    fn capture_table(&self, db: &str, table: &str) -> Result<CapturedRows, String> {
+        let rows: segment::Capture = segment::capture(db, table)?;
+
+        Ok(Box::new(rows.map(|row| row.and_then(|(key, bytes)| {
+            let row: Row = decode_row(&key, &bytes)?;
+            Ok((key, row))
+        }))))
+    }
+
+    fn delete_row(&self, db: &str, table: &str, key: &str) -> Result<bool, String> {
+        segment::delete(db, table, key)
+    }
+
+    fn row_exists(&self, db: &str, table: &str, key: &str) -> bool {
+        self.table_exists(db, table) && segment::contains(db, table, key).unwrap_or(false)
+    }
+
+    fn list_rows(&self, db: &str, table: &str) -> Result<Vec<String>, String> {
+        segment::keys(db, table)
+    }
+
+    fn scan_rows(&self, db: &str, table: &str, range: &KeyRange) -> Result<Vec<String>, String> {
+        segment::scan(db, table, range)
+    }
+
+    fn skipped_records(&self, db: &str, table: &str) -> Result<Vec<SkippedRecord>, String> {
+        segment::skipped(db, table)
+    }
+
+    fn created_at(&self, db: &str, table: Option<&str>) -> Option<u64> {
+        let dir: String = match table {
+            Some(table) => table_dir(db, table),
+            None => db_dir(db)
+        };
+
+        unix_secs(fs::metadata(dir).and_then(|m| m.created()))
+    }
+}
+
+/// Decodes a stored row.
+fn decode_row(key: &str, bytes: &[u8]) -> Result<Row, String> {
+    let mut row: Row = Row::new();
+    match row.merge_from_bytes(bytes) {
+        Ok(_) => Ok(row),
+        Err(e) => Err(checksum::corrupted(key, &format!("can not decode the row - {}", e)))
+    }
+}
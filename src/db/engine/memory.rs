@@ -0,0 +1,246 @@
+//! In-memory storage engine.
+//!
+//! Keeps everything in process memory, which makes it a fit for tests and
+//! ephemeral deployments. Nothing is written to the disk.
+
+use std::{collections::{BTreeMap, HashMap}, sync::{RwLock, RwLockReadGuard, RwLockWriteGuard}};
+
+use crate::{db::{names, ttl}, protos::row::Row};
+use super::{KeyRange, StorageEngine};
+
+/// Data of one database.
+struct MemoryDb {
+    /// Serialized metadata.
+    meta: Option<String>,
+
+    /// Rows sorted by key of every table.
+    tables: HashMap<String, BTreeMap<String, Row>>,
+
+    /// Creation time of the database, Unix time in seconds.
+    created_at: u64,
+
+    /// Creation time of every table, Unix time in seconds.
+    created: HashMap<String, u64>
+}
+
+impl MemoryDb {
+    /// Creates an empty database.
+    fn new() -> Self {
+        Self {
+            meta: None,
+            tables: HashMap::new(),
+            created_at: ttl::now(),
+            created: HashMap::new()
+        }
+    }
+
+    /// Returns the rows of a table, creating the table if needed.
+    fn table(&mut self, table: &str) -> &mut BTreeMap<String, Row> {
+        if !self.tables.contains_key(table) {
+            self.created.insert(table.to_string(), ttl::now());
+        }
+
+        self.tables.entry(table.to_string()).or_default()
+    }
+}
+
+/// Storage engine keeping all data in memory.
+pub struct MemoryEngine {
+    /// Databases by name.
+    dbs: RwLock<HashMap<String, MemoryDb>>
+}
+
+impl MemoryEngine {
+    /// Creates a new empty in-memory engine.
+    pub fn new() -> Self {
+        Self {
+            dbs: RwLock::new(HashMap::new())
+        }
+    }
+
+    /// Locks the databases for reading.
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<String, MemoryDb>> {
+        self.dbs.read().unwrap()
+    }
+
+    /// Locks the databases for writing.
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<String, MemoryDb>> {
+        self.dbs.write().unwrap()
+    }
+}
+
+impl Default for MemoryEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StorageEngine for MemoryEngine {
+    fn init(&self) -> bool {
+        println!("[ INFO ] Engine: using in-memory storage, data is not persisted");
+        true
+    }
+
+    fn is_persistent(&self) -> bool {
+        false
+    }
+
+    fn create_db(&self, db: &str) -> bool {
+        if names::check_name("database", db).is_err() {
+            return false;
+        }
+
+        let mut dbs: RwLockWriteGuard<'_, HashMap<String, MemoryDb>> = self.write();
+        if dbs.contains_key(db) {
+            return false;
+        }
+
+        dbs.insert(db.to_string(), MemoryDb::new());
+        true
+    }
+
+    fn delete_db(&self, db: &str) -> Result<(), String> {
+        match self.write().remove(db) {
+            Some(_) => Ok(()),
+            None => Err("DataBase not exist".to_string())
+        }
+    }
+
+    fn db_exists(&self, db: &str) -> bool {
+        self.read().contains_key(db)
+    }
+
+    fn list_dbs(&self) -> Result<Vec<String>, String> {
+        let mut names: Vec<String> = self.read().keys().cloned().collect();
+        names.sort();
+
+        Ok(names)
+    }
+
+    fn read_db_meta(&self, db: &str) -> Option<String> {
+        self.read().get(db).and_then(|d| d.meta.clone())
+    }
+
+    fn write_db_meta(&self, db: &str, meta: &str) -> Result<(), String> {
+        names::check_name("database", db)?;
+        self.write().entry(db.to_string()).or_insert_with(MemoryDb::new).meta = Some(meta.to_string());
+        Ok(())
+    }
+
+    fn create_table(&self, db: &str, table: &str) -> bool {
+        if names::check_name("database", db).is_err() || names::check_name("table", table).is_err() {
+            return false;
+        }
+
+        self.write().entry(db.to_string()).or_insert_with(MemoryDb::new).table(table);
+        true
+    }
+
+    fn delete_table(&self, db: &str, table: &str) -> bool {
+        match self.write().get_mut(db) {
+            Some(d) => {
+                d.created.remove(table);
+                d.tables.remove(table).is_some()
+            },
+            None => false
+        }
+    }
+
+    fn table_exists(&self, db: &str, table: &str) -> bool {
+        self.read().get(db).map(|d| d.tables.contains_key(table)).unwrap_or(false)
+    }
+
+    fn list_tables(&self, db: &str) -> Result<Vec<String>, String> {
+        match self.read().get(db) {
+            Some(d) => {
+                let mut names: Vec<String> = d.tables.keys().cloned().collect();
+                names.sort();
+                Ok(names)
+            },
+            None => Err("DataBase not exist".to_string())
+        }
+    }
+
+    fn rename_table(&self, db: &str, table: &str, to: &str) -> Result<(), String> {
+        names::check_name("table", to)?;
+        let mut dbs: RwLockWriteGuard<'_, HashMap<String, MemoryDb>> = self.write();
+        let d: &mut MemoryDb = dbs.get_mut(db).ok_or("no such table")?;
+
+        if d.tables.contains_key(to) {
+            return Err(format!("table {} already exists", to));
+        }
+        let rows: BTreeMap<String, Row> = d.tables.remove(table).ok_or("no such table")?;
+
+        d.tables.insert(to.to_string(), rows);
+        if let Some(created) = d.created.remove(table) {
+            d.created.insert(to.to_string(), created);
+        }
+
+        Ok(())
+    }
+
+    fn rename_db(&self, db: &str, to: &str) -> Result<(), String> {
+        names::check_name("database", to)?;
+        let mut dbs: RwLockWriteGuard<'_, HashMap<String, MemoryDb>> = self.write();
+
+        if dbs.contains_key(to) {
+            return Err(format!("database {} already exists", to));
+        }
+        let d: MemoryDb = dbs.remove(db).ok_or("no such database")?;
+        dbs.insert(to.to_string(), d);
+
+        Ok(())
+    }
+
+    fn write_row(&self, db: &str, table: &str, key: &str, row: &Row) -> Result<(), String> {
+        names::check_row(db, table, key)?;
+        self.write()
+            .entry(db.to_string()).or_insert_with(MemoryDb::new)
+            .table(table)
+            .insert(key.to_string(), row.clone());
+
+        Ok(())
+    }
+
+    fn read_row(&self, db: &str, table: &str, key: &str) -> Result<Option<Row>, String> {
+        match self.read().get(db).and_then(|d| d.tables.get(table)) {
+            Some(rows) => Ok(rows.get(key).cloned()),
+            None => Err("[ ERROR ] Row: Table is not exist".to_string())
+        }
+    }
+
+    fn delete_row(&self, db: &str, table: &str, key: &str) -> Result<bool, String> {
+        match self.write().get_mut(db).and_then(|d| d.tables.get_mut(table)) {
+            Some(rows) => Ok(rows.remove(key).is_some()),
+            None => Err("[ ERROR ] Row: Table is not exist".to_string())
+        }
+    }
+
+    fn row_exists(&self, db: &str, table: &str, key: &str) -> bool {
+        self.read().get(db).and_then(|d| d.tables.get(table)).map(|rows| rows.contains_key(key)).unwrap_or(false)
+    }
+
+    fn list_rows(&self, db: &str, table: &str) -> Result<Vec<String>, String> {
+        match self.read().get(db).and_then(|d| d.tables.get(table)) {
+            Some(rows) => Ok(rows.keys().cloned().collect()),
+            None => Err("[ ERROR ] Row: Table is not exist".to_string())
+        }
+    }
+
+    fn scan_rows(&self, db: &str, table: &str, range: &KeyRange) -> Result<Vec<String>, String> {
+        match self.read().get(db).and_then(|d| d.tables.get(table)) {
+            Some(rows) => Ok(range.keys(rows)),
+            None => Err("[ ERROR ] Row: Table is not exist".to_string())
+        }
+    }
+
+    fn created_at(&self, db: &str, table: Option<&str>) -> Option<u64> {
+        let dbs: RwLockReadGuard<'_, HashMap<String, MemoryDb>> = self.read();
+        let d: &MemoryDb = dbs.get(db)?;
+
+        match table {
+            Some(table) => d.created.get(table).copied(),
+            None => Some(d.created_at)
+        }
+    }
+}
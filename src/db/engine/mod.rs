@@ -0,0 +1,256 @@
+//! Pluggable storage engines.
+//!
+//! All storage access goes through the [`StorageEngine`] trait. The engine is
+//! selected by the `ENGINE` configuration value:
+//!
+//! * `fs` - log-structured segment files under the database path (default)
+//! * `memory` - ephemeral in-memory store, nothing survives a restart
+
+/// Module for the file system engine.
+pub mod fs;
+
+/// Module for the in-memory engine.
+pub mod memory;
+
+use std::{collections::BTreeMap, ops::{Bound, RangeBounds}};
+use lazy_static::lazy_static;
+use serde::Serialize;
+
+use crate::{config, protos::row::Row};
+
+/// Storage of databases, tables and rows.
+pub trait StorageEngine: Send + Sync {
+    /// Prepares the storage on server startup.
+    ///
+    /// Returns true if the engine is ready; false otherwise.
+    fn init(&self) -> bool;
+
+    /// Starts background maintenance of the storage.
+    fn start(&self) {}
+
+    /// Checks if the data survives a restart, only persistent engines use the write-ahead log.
+    fn is_persistent(&self) -> bool;
+
+    /// Creates a database, returns false if it already exists or can not be created.
+    fn create_db(&self, db: &str) -> bool;
+
+    /// Deletes a database with all its tables.
+    fn delete_db(&self, db: &str) -> Result<(), String>;
+
+    /// Checks if a database exists.
+    fn db_exists(&self, db: &str) -> bool;
+
+    /// Lists the names of all databases.
+    fn list_dbs(&self) -> Result<Vec<String>, String>;
+
+    /// Reads the serialized metadata of a database.
+    fn read_db_meta(&self, db: &str) -> Option<String>;
+
+    /// Writes the serialized metadata of a database, creating the database if needed.
+    fn write_db_meta(&self, db: &str, meta: &str) -> Result<(), String>;
+
+    /// Reads a side file kept next to the rows of a table, such as a persisted index.
+    ///
+    /// Returns None if the table has no such file. Engines which keep nothing
+    /// across a restart have no side files.
+    fn read_table_file(&self, _db: &str, _table: &str, _name: &str) -> Option<String> {
+        None
+    }
+
+    /// Appends to a side file of an existing table, creating the file if needed.
+    fn append_table_file(&self, _db: &str, _table: &str, _name: &str, _content: &str) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Replaces a side file of an existing table in one step.
+    fn write_table_file(&self, _db: &str, _table: &str, _name: &str, _content: &str) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Creates a table, creating the database if needed.
+    ///
+    /// Returns true if the table exists after the call; false otherwise.
+    fn create_table(&self, db: &str, table: &str) -> bool;
+
+    /// Deletes a table with all its rows, returns false if it can not be deleted.
+    fn delete_table(&self, db: &str, table: &str) -> bool;
+
+    /// Checks if a table exists.
+    fn table_exists(&self, db: &str, table: &str) -> bool;
+
+    /// Lists the names of all tables of a database.
+    fn list_tables(&self, db: &str) -> Result<Vec<String>, String>;
+
+    /// Renames a table in one step, the rows stay as they are.
+    ///
+    /// Returns an error if the table does not exist or the new name is taken.
+    fn rename_table(&self, db: &str, table: &str, to: &str) -> Result<(), String>;
+
+    /// Renames a database in one step, along with its tables and metadata.
+    ///
+    /// Returns an error if the database does not exist or the new name is taken.
+    fn rename_db(&self, db: &str, to: &str) -> Result<(), String>;
+
+    /// Writes a row, creating the database and the table if needed.
+    fn write_row(&self, db: &str, table: &str, key: &str, row: &Row) -> Result<(), String>;
+
+    /// Reads a row, returns Ok(None) if the key is not present.
+    fn read_row(&self, db: &str, table: &str, key: &str) -> Result<Option<Row>, String>;
+
+    /// Deletes a row, returns Ok(false) if the key is not present.
+    fn delete_row(&self, db: &str, table: &str, key: &str) -> Result<bool, String>;
+
+    /// Checks if a row exists.
+    fn row_exists(&self, db: &str, table: &str, key: &str) -> bool;
+
+    /// Lists the keys of all rows of a table in lexicographic order.
+    fn list_rows(&self, db: &str, table: &str) -> Result<Vec<String>, String>;
+
+    /// Lists the keys of a table which fall into the range, in lexicographic order.
+    fn scan_rows(&self, db: &str, table: &str, range: &KeyRange) -> Result<Vec<String>, String>;
+
+    /// Returns when a database, or a table of it, was created as Unix time in seconds.
+    ///
+    /// Returns None if the time is not known.
+    fn created_at(&self, db: &str, table: Option<&str>) -> Option<u64>;
+
+    /// Lists the damaged records of a table which were skipped when it was loaded.
+    ///
+    /// Such records frame no row any more, so the rows they held can not be
+    /// listed or read. Engines which keep no files have none.
+    fn skipped_records(&self, _db: &str, _table: &str) -> Result<Vec<SkippedRecord>, String> {
+        Ok(Vec::new())
+    }
+
+    /// Captures the rows of a table as they are now, to be read while writes go on.
+    ///
+    /// Writes made after the call are not seen by the returned rows. The
+    /// default reads all of them right away.
+    fn capture_table(&self, db: &str, table: &str) -> Result<CapturedRows, String> {
+        Ok(Box::new(self.read_table(db, table)?.into_iter().map(Ok)))
+    }
+
+    /// Reads all rows of a table along with their keys.
+    fn read_table(&self, db: &str, table: &str) -> Result<Vec<(String, Row)>, String> {
+        let keys: Vec<String> = self.list_rows(db, table)?;
+        let mut rows: Vec<(String, Row)> = Vec::with_capacity(keys.len());
+
+        for key in keys {
+            if let Ok(Some(row)) = self.read_row(db, table, &key) {
+                rows.push((key, row));
+            }
+        }
+
+        Ok(rows)
+    }
+}
+
+/// Rows of a table captured by [`StorageEngine::capture_table`], in key order.
+pub type CapturedRows = Box<dyn Iterator<Item = Result<(String, Row), String>> + Send>;
+
+/// A damaged record skipped when a table was loaded.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SkippedRecord {
+    /// Segment id.
+    pub segment: u32,
+
+    /// Offset of the damaged bytes inside the segment.
+    pub offset: u64,
+
+    /// Number of skipped bytes.
+    pub length: u64,
+
+    /// Key named by the damaged record, None if it can not be read.
+    pub key: Option<String>
+}
+
+/// Range of keys to scan in lexicographic order.
+#[derive(Debug, Clone)]
+pub struct KeyRange {
+    /// Lower bound of the keys.
+    pub start: Bound<String>,
+
+    /// Upper bound of the keys.
+    pub end: Bound<String>,
+
+    /// Max number of returned keys.
+    pub limit: usize,
+
+    /// Return the keys from the upper bound down.
+    pub reverse: bool
+}
+
+impl KeyRange {
+    /// Creates a range covering all keys.
+    pub fn all() -> Self {
+        Self {
+            start: Bound::Unbounded,
+            end: Bound::Unbounded,
+            limit: usize::MAX,
+            reverse: false
+        }
+    }
+
+    /// Collects the keys of a sorted map which fall into the range.
+    pub fn keys<V>(&self, map: &BTreeMap<String, V>) -> Vec<String> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+
+        let bounds: (Bound<&str>, Bound<&str>) = (as_str(&self.start), as_str(&self.end));
+        let range = map.range::<str, _>(bounds).map(|(key, _)| key.clone());
+
+        if self.reverse {
+            range.rev().take(self.limit).collect()
+        } else {
+            range.take(self.limit).collect()
+        }
+    }
+
+    /// Checks if a key lies between the bounds of the range.
+    pub fn contains(&self, key: &str) -> bool {
+        RangeBounds::<str>::contains(&(as_str(&self.start), as_str(&self.end)), key)
+    }
+
+    /// Checks if no key can fall into the range.
+    fn is_empty(&self) -> bool {
+        match (&self.start, &self.end) {
+            (Bound::Included(start), Bound::Included(end)) => start > end,
+            (Bound::Included(start), Bound::Excluded(end))
+            | (Bound::Excluded(start), Bound::Included(end))
+            | (Bound::Excluded(start), Bound::Excluded(end)) => start >= end,
+            _ => false
+        }
+    }
+}
+
+/// Borrows the key of a bound.
+fn as_str(bound: &Bound<String>) -> Bound<&str> {
+    match bound {
+        Bound::Included(key) => Bound::Included(key.as_str()),
+        Bound::Excluded(key) => Bound::Excluded(key.as_str()),
+        Bound::Unbounded => Bound::Unbounded
+    }
+}
+
+lazy_static! {
+    /// Global storage engine selected by the configuration.
+    pub static ref ENGINE: Box<dyn StorageEngine> = from_name(&config::CONFIG.engine);
+}
+
+/// Creates a storage engine by its configuration name, falling back to the file system engine.
+pub fn from_name(name: &str) -> Box<dyn StorageEngine> {
+    match name {
+        "memory" => Box::new(memory::MemoryEngine::new()),
+        "fs" => Box::new(fs::FsEngine::new()),
+        _ => {
+            println!("[ WARN ] Engine: unknown engine `{}`, using `fs`", name);
+            Box::new(fs::FsEngine::new())
+        }
+    }
+}
+
+/// Returns the global storage engine.
+pub fn engine() -> &'static dyn StorageEngine {
+    ENGINE.as_ref()
+}
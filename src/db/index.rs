@@ -0,0 +1,321 @@
+//! Secondary indexes on JSON fields.
+//!
+//! An index maps the value found at a JSON path, such as `user.email`, to the
+//! keys of the rows holding it. The indexed paths are kept in the database
+//! metadata. The entries live in memory: an index is built from the table the
+//! first time it is used and is kept up to date by `cache::add` and
+//! `cache::delete` afterwards.
+//!
+//! A table is read without holding the index lock. The writes made while it
+//! is read are collected and applied to the new index before it is installed.
+
+use std::{collections::{BTreeMap, BTreeSet, HashMap}, sync::{atomic::{AtomicU64, Ordering}, Mutex, MutexGuard}};
+use lazy_static::lazy_static;
+use serde_json::{Map, Value};
+
+use crate::{db::{engine::engine, meta::{self, DbMeta}, Rename}, protos::row::Row};
+
+/// Entries of one index.
+#[derive(Default)]
+struct Index {
+    /// Row keys by the serialized value at the path.
+    entries: BTreeMap<String, BTreeSet<String>>,
+
+    /// Serialized value at the path by row key.
+    values: HashMap<String, String>
+}
+
+impl Index {
+    /// Points the row key at a new value, or removes it when the row has none.
+    fn set(&mut self, key: &str, value: Option<String>) {
+        if let Some(old) = self.values.remove(key) {
+            if let Some(keys) = self.entries.get_mut(&old) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    self.entries.remove(&old);
+                }
+            }
+        }
+
+        if let Some(value) = value {
+            self.entries.entry(value.clone()).or_default().insert(key.to_string());
+            self.values.insert(key.to_string(), value);
+        }
+    }
+}
+
+/// An index which is being built.
+struct Build {
+    /// Database name.
+    db: String,
+
+    /// Table name.
+    table: String,
+
+    /// Row keys and their new JSON values written since the build started, None for a deleted row.
+    writes: Vec<(String, Option<Value>)>
+}
+
+lazy_static! {
+    /// Built indexes by database and table name, then by path.
+    static ref INDEXES: Mutex<HashMap<(String, String), HashMap<String, Index>>> = Mutex::new(HashMap::new());
+
+    /// Running builds by id, always locked after `INDEXES`.
+    static ref BUILDS: Mutex<HashMap<u64, Build>> = Mutex::new(HashMap::new());
+}
+
+/// Id of the next build.
+static NEXT_BUILD: AtomicU64 = AtomicU64::new(1);
+
+/// Creates an index on a JSON path of a table and builds it.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+/// * `table` - Table name.
+/// * `path` - Dot separated path of the field, e.g. `user.email`.
+///
+/// # Returns
+///
+/// Returns Ok(true) if the index was created, Ok(false) if it already exists; Err with an error message otherwise.
+pub fn create(db: &str, table: &str, path: &str) -> Result<bool, String> {
+    if path.is_empty() || path.split('.').any(|part| part.is_empty()) {
+        return Err(format!("invalid index path `{}`", path));
+    }
+
+    if !engine().table_exists(db, table) {
+        return Err("no such table".to_string());
+    }
+
+    let mut db_meta: DbMeta = meta::get_db_meta(db);
+    let paths: &mut Vec<String> = db_meta.indexes.entry(table.to_string()).or_default();
+    if paths.iter().any(|p| p == path) {
+        return Ok(false);
+    }
+
+    paths.push(path.to_string());
+    meta::set_db_meta(db, &db_meta)?;
+
+    build(db, table, path);
+
+    println!("[ INFO ] Index: created `{}` on {}/{}", path, db, table);
+
+    Ok(true)
+}
+
+/// Drops an index of a table.
+///
+/// # Returns
+///
+/// Returns Ok(true) if the index was dropped, Ok(false) if there is no such index; Err with an error message otherwise.
+pub fn drop(db: &str, table: &str, path: &str) -> Result<bool, String> {
+    let mut db_meta: DbMeta = meta::get_db_meta(db);
+    let paths: &mut Vec<String> = match db_meta.indexes.get_mut(table) {
+        Some(paths) if paths.iter().any(|p| p == path) => paths,
+        _ => return Ok(false)
+    };
+
+    paths.retain(|p| p != path);
+    if paths.is_empty() {
+        db_meta.indexes.remove(table);
+    }
+    meta::set_db_meta(db, &db_meta)?;
+
+    if let Some(built) = INDEXES.lock().unwrap().get_mut(&(db.to_string(), table.to_string())) {
+        built.remove(path);
+    }
+
+    Ok(true)
+}
+
+/// Lists the indexed paths of a table.
+pub fn paths(db: &str, table: &str) -> Vec<String> {
+    meta::get_db_meta(db).indexes.get(table).cloned().unwrap_or_default()
+}
+
+/// Updates the built indexes of a table after a row was written.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+/// * `table` - Table name.
+/// * `key` - Key of the written row.
+/// * `row` - New content of the row.
+pub fn on_put(db: &str, table: &str, key: &str, row: &Row) {
+    record(db, table, key, Some(row));
+}
+
+/// Updates the built indexes of a table after a row was deleted.
+pub fn on_delete(db: &str, table: &str, key: &str) {
+    record(db, table, key, None);
+}
+
+/// Drops the indexes of a deleted table.
+pub fn forget_table(db: &str, table: &str) {
+    INDEXES.lock().unwrap().remove(&(db.to_string(), table.to_string()));
+
+    let mut db_meta: DbMeta = meta::get_db_meta(db);
+    if db_meta.indexes.remove(table).is_some() {
+        if let Err(err) = meta::set_db_meta(db, &db_meta) {
+            println!("[ ERROR ] Index: Can't drop indexes of {}/{} - {}", db, table, err);
+        }
+    }
+}
+
+/// Moves the built indexes of renamed tables to the new names.
+pub fn on_rename(rename: &Rename) {
+    rename.rekey(&mut INDEXES.lock().unwrap());
+
+    for build in BUILDS.lock().unwrap().values_mut() {
+        if let Some((db, table)) = rename.map(&build.db, &build.table) {
+            build.db = db;
+            build.table = table;
+        }
+    }
+}
+
+/// Drops the built indexes of a deleted database.
+pub fn forget_db(db: &str) {
+    INDEXES.lock().unwrap().retain(|(index_db, _), _| index_db != db);
+}
+
+/// Finds the rows which can match an equality filter with an index.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+/// * `table` - Table name.
+/// * `filter` - JSON filter object.
+///
+/// # Returns
+///
+/// Returns the sorted keys of the candidate rows, or None if no index covers the filter.
+pub fn lookup(db: &str, table: &str, filter: &Map<String, Value>) -> Option<Vec<String>> {
+    let (path, value): (String, &Value) = equality(filter)?;
+    if !paths(db, table).contains(&path) {
+        return None;
+    }
+
+    if let Some(keys) = matching(db, table, &path, value) {
+        return Some(keys);
+    }
+
+    build(db, table, &path);
+    matching(db, table, &path, value)
+}
+
+/// Returns the sorted keys of the rows holding a value, None if the index is not built.
+fn matching(db: &str, table: &str, path: &str, value: &Value) -> Option<Vec<String>> {
+    let indexes: MutexGuard<'_, HashMap<(String, String), HashMap<String, Index>>> = INDEXES.lock().unwrap();
+    let index: &Index = indexes.get(&(db.to_string(), table.to_string()))?.get(path)?;
+
+    let keys: Vec<String> = index.entries.get(&value.to_string())
+        .map(|keys| keys.iter().cloned().collect())
+        .unwrap_or_default();
+
+    Some(keys)
+}
+
+/// Updates the built indexes and the running builds of a table after a write.
+fn record(db: &str, table: &str, key: &str, row: Option<&Row>) {
+    let mut indexes: MutexGuard<'_, HashMap<(String, String), HashMap<String, Index>>> = INDEXES.lock().unwrap();
+    let mut builds: MutexGuard<'_, HashMap<u64, Build>> = BUILDS.lock().unwrap();
+
+    let built: Option<&mut HashMap<String, Index>> = indexes.get_mut(&(db.to_string(), table.to_string()))
+        .filter(|built| !built.is_empty());
+    let mut running: Vec<&mut Build> = builds.values_mut()
+        .filter(|build| build.db == db && build.table == table)
+        .collect();
+    if built.is_none() && running.is_empty() {
+        return;
+    }
+
+    let json: Option<Value> = row.and_then(parse);
+    if let Some(built) = built {
+        for (path, index) in built.iter_mut() {
+            index.set(key, json.as_ref().and_then(|j| value_at(j, path)));
+        }
+    }
+
+    for build in running.iter_mut() {
+        build.writes.push((key.to_string(), json.clone()));
+    }
+}
+
+/// Builds an index from the table and installs it.
+///
+/// The table is read without the index lock. The writes made meanwhile are
+/// applied afterwards, and the index is dropped if its path was removed.
+fn build(db: &str, table: &str, path: &str) {
+    let id: u64 = NEXT_BUILD.fetch_add(1, Ordering::SeqCst);
+    BUILDS.lock().unwrap().insert(id, Build { db: db.to_string(), table: table.to_string(), writes: Vec::new() });
+
+    let mut index: Index = scan(db, table, path);
+
+    let mut indexes: MutexGuard<'_, HashMap<(String, String), HashMap<String, Index>>> = INDEXES.lock().unwrap();
+    let build: Build = BUILDS.lock().unwrap().remove(&id).unwrap();
+
+    for (key, json) in build.writes {
+        index.set(&key, json.and_then(|j| value_at(&j, path)));
+    }
+
+    if paths(&build.db, &build.table).iter().any(|p| p == path) {
+        indexes.entry((build.db, build.table)).or_default().insert(path.to_string(), index);
+    }
+}
+
+/// Reads the whole table into a new index.
+fn scan(db: &str, table: &str, path: &str) -> Index {
+    let mut index: Index = Index::default();
+
+    for key in engine().list_rows(db, table).unwrap_or_default() {
+        if let Ok(Some(row)) = engine().read_row(db, table, &key) {
+            index.set(&key, parse(&row).and_then(|json| value_at(&json, path)));
+        }
+    }
+
+    index
+}
+
+/// Parses the value of a json row.
+fn parse(row: &Row) -> Option<Value> {
+    if row.type_() != "json" {
+        return None;
+    }
+
+    serde_json::from_str(row.value()).ok()
+}
+
+/// Returns the serialized scalar found at a dot separated path.
+fn value_at(json: &Value, path: &str) -> Option<String> {
+    let mut value: &Value = json;
+
+    for part in path.split('.') {
+        value = value.as_object()?.get(part)?;
+    }
+
+    match value {
+        Value::Object(_) | Value::Array(_) => None,
+        scalar => Some(scalar.to_string())
+    }
+}
+
+/// Turns a filter into the path and the value it compares with.
+///
+/// The filter follows the first key of each nested object, the same way `json_filter` does,
+/// so only filters which end with a scalar can use an index.
+fn equality(filter: &Map<String, Value>) -> Option<(String, &Value)> {
+    let mut parts: Vec<&str> = Vec::new();
+    let mut current: &Map<String, Value> = filter;
+
+    loop {
+        let (key, value): (&String, &Value) = current.iter().next()?;
+        parts.push(key);
+
+        match value {
+            Value::Object(next) => current = next,
+            Value::Array(_) => return None,
+            scalar => return Some((parts.join("."), scalar))
+        }
+    }
+}
@@ -1,34 +1,117 @@
 //! JSON Data filter
+use std::ops::Bound;
+
 use serde_json::{Map, Value};
 use serde_json::map::Keys;
 
-use crate::{db::table::get_table_with_keys, http::row_methods::Bunch};
-
+use crate::{db::{engine::{engine, KeyRange}, index, ttl}, http::row_methods::Bunch, protos::row::Row};
+
+/// Number of keys read from the table at once while filtering.
+const SCAN_BATCH: usize = 512;
+
+/// Filters all json rows of a table.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+/// * `name` - Table name.
+/// * `filter_json` - JSON object the rows are matched against.
+///
+/// # Returns
+///
+/// Returns a Result containing the matching rows, or an error message.
 pub fn filter(db: &str, name: &str, filter_json: &str) -> Result<Vec<Bunch>, String> {
-    let filter: Map<String, Value> = serde_json::from_str(filter_json).unwrap();
+    filter_range(db, name, filter_json, &KeyRange::all())
+}
 
-    let data: Result<Vec<Bunch>, String> = get_table_with_keys(db, name);
-    let bunch: Vec<Bunch>;
-    match data {
-        Ok(_bunch) => {bunch = _bunch},
-        Err(e) => {return Err(e)}
+/// Filters the json rows of a table whose keys fall into a range.
+///
+/// The table is walked in key order in batches, `range.limit` caps the number of matches.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+/// * `name` - Table name.
+/// * `filter_json` - JSON object the rows are matched against.
+/// * `range` - Range of keys to look at.
+///
+/// # Returns
+///
+/// Returns a Result containing the matching rows in key order, or an error message.
+pub fn filter_range(db: &str, name: &str, filter_json: &str, range: &KeyRange) -> Result<Vec<Bunch>, String> {
+    let filter: Map<String, Value> = match serde_json::from_str(filter_json) {
+        Ok(f) => f,
+        Err(_) => return Err("{\"code\": 400, \"message\": \"filter must be a json object\"}\njson".to_string())
+    };
+
+    if !engine().table_exists(db, name) {
+        return Err("{\"code\": 400, \"message\": \"no such table\"}\njson".to_string());
     }
 
     let mut matches: Vec<Bunch> = Vec::with_capacity(512);
 
-    for x in bunch {
-        if x._type == "json" {
-            let obj: Map<String, Value> = serde_json::from_str(x.value.as_str().unwrap()).unwrap();
+    // Equality filters on an indexed path only look at the rows the index points to
+    if let Some(keys) = index::lookup(db, name, &filter) {
+        for key in keys.iter().filter(|key| range.contains(key)) {
+            if let Some(bunch) = matching(db, name, key, &filter) {
+                matches.push(bunch);
+
+                if matches.len() >= range.limit {
+                    break;
+                }
+            }
+        }
+
+        return Ok(matches);
+    }
+
+    let mut batch_range: KeyRange = KeyRange { limit: SCAN_BATCH, reverse: false, ..range.clone() };
+
+    loop {
+        let keys: Vec<String> = engine().scan_rows(db, name, &batch_range)?;
 
-            if apply_filter(&obj, &filter) {
-                matches.push(x);
+        for key in keys.iter() {
+            if let Some(bunch) = matching(db, name, key, &filter) {
+                matches.push(bunch);
+
+                if matches.len() >= range.limit {
+                    return Ok(matches);
+                }
             }
         }
+
+        match keys.last() {
+            Some(last) if keys.len() == SCAN_BATCH => batch_range.start = Bound::Excluded(last.to_string()),
+            _ => break
+        }
     }
 
     Ok(matches)
 }
 
+/// Reads a row and checks it against the filter.
+///
+/// # Returns
+///
+/// Returns the row if it is a json row matching the filter; None otherwise.
+fn matching(db: &str, name: &str, key: &str, filter: &Map<String, Value>) -> Option<Bunch> {
+    let row: Row = engine().read_row(db, name, key).ok()??;
+    if row.type_() != "json" || ttl::is_expired(&row) {
+        return None;
+    }
+
+    let obj: Map<String, Value> = serde_json::from_str(row.value()).ok()?;
+    if !apply_filter(&obj, filter) {
+        return None;
+    }
+
+    Some(Bunch {
+        key: key.to_string(),
+        value: row.value().into(),
+        _type: row.type_().to_string(),
+    })
+}
+
 fn apply_filter(json: &Map<String, Value>, filter: &Map<String, Value>) -> bool {
     //TODO: Make more then for one key filter
     fn recursive_apply(json: &Map<String, Value>, filter: &Map<String, Value>) -> bool {
@@ -42,19 +125,34 @@ fn apply_filter(json: &Map<String, Value>, filter: &Map<String, Value>) -> bool
         let key: String = keys_collect[0].clone();
 
         let filter_value: &Value = filter.get(&key).unwrap();
-        let json_value: &Value = json.get(&key).unwrap();
+        let json_value: &Value = match json.get(&key) {
+            Some(value) => value,
+            None => return false
+        };
 
-        let mut new_filter_value: &Map<String, Value>;
+        let new_filter_value: &Map<String, Value>;
         let new_json_value: &Map<String, Value>;
 
         match filter_value {
             Value::Array(value) => {
-                new_filter_value = value.get(0).unwrap().as_object().unwrap();
-                new_json_value = json_value.as_array().unwrap().get(0).unwrap().as_object().unwrap();
+                let first_json: Option<&Map<String, Value>> = json_value.as_array()
+                    .and_then(|a| a.first())
+                    .and_then(|v| v.as_object());
+
+                match (value.first().and_then(|v| v.as_object()), first_json) {
+                    (Some(f), Some(j)) => {
+                        new_filter_value = f;
+                        new_json_value = j;
+                    },
+                    _ => return false
+                }
             },
             Value::Object(value) => {
                 new_filter_value = value;
-                new_json_value = json_value.as_object().unwrap();
+                new_json_value = match json_value.as_object() {
+                    Some(j) => j,
+                    None => return false
+                };
             }
             _ => {
                 if filter_value == json_value {
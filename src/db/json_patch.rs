@@ -0,0 +1,168 @@
+//! Partial updates of JSON documents.
+//!
+//! Supports RFC 7396 merge patches and RFC 6902 patch documents.
+
+use serde_json::{Map, Value};
+
+/// Applies a patch to a document.
+///
+/// An array body is read as an RFC 6902 patch document, anything else as an RFC 7396
+/// merge patch, unless the kind is given explicitly.
+///
+/// # Arguments
+///
+/// * `doc` - Document to change.
+/// * `patch` - Patch body.
+/// * `kind` - `merge`, `json` or None to detect it from the body.
+///
+/// # Returns
+///
+/// Returns the patched document, or an error message if the patch can not be applied.
+pub fn patch(doc: &Value, patch: &Value, kind: Option<&str>) -> Result<Value, String> {
+    match kind {
+        Some("merge") => Ok(merge_patch(doc, patch)),
+        Some("json") => json_patch(doc, patch),
+        None if patch.is_array() => json_patch(doc, patch),
+        None => Ok(merge_patch(doc, patch)),
+        Some(other) => Err(format!("unknown patch type `{}`", other))
+    }
+}
+
+/// Applies an RFC 7396 merge patch.
+pub fn merge_patch(doc: &Value, patch: &Value) -> Value {
+    let patch_obj: &Map<String, Value> = match patch.as_object() {
+        Some(obj) => obj,
+        None => return patch.clone()
+    };
+
+    let mut target: Map<String, Value> = doc.as_object().cloned().unwrap_or_default();
+
+    for (name, value) in patch_obj {
+        if value.is_null() {
+            target.remove(name);
+        } else {
+            let current: Value = target.remove(name).unwrap_or(Value::Null);
+            target.insert(name.clone(), merge_patch(&current, value));
+        }
+    }
+
+    Value::Object(target)
+}
+
+/// Applies an RFC 6902 patch document, all operations or none.
+pub fn json_patch(doc: &Value, patch: &Value) -> Result<Value, String> {
+    let ops: &Vec<Value> = patch.as_array().ok_or("json patch must be an array of operations")?;
+    let mut target: Value = doc.clone();
+
+    for (i, op) in ops.iter().enumerate() {
+        apply_op(&mut target, op).map_err(|e| format!("operation {}: {}", i, e))?;
+    }
+
+    Ok(target)
+}
+
+/// Applies one operation of a patch document.
+fn apply_op(doc: &mut Value, op: &Value) -> Result<(), String> {
+    let name: &str = op.get("op").and_then(|v| v.as_str()).ok_or("missing `op`")?;
+    let path: &str = op.get("path").and_then(|v| v.as_str()).ok_or("missing `path`")?;
+
+    let value = || op.get("value").cloned().ok_or("missing `value`".to_string());
+    let from = || op.get("from").and_then(|v| v.as_str()).ok_or("missing `from`".to_string());
+
+    match name {
+        "add" => add(doc, path, value()?),
+        "remove" => remove(doc, path).map(|_| ()),
+        "replace" => {
+            remove(doc, path)?;
+            add(doc, path, value()?)
+        },
+        "move" => {
+            let from: &str = from()?;
+            if path.starts_with(&format!("{}/", from)) {
+                return Err("can not move a value into itself".to_string());
+            }
+
+            let moved: Value = remove(doc, from)?;
+            add(doc, path, moved)
+        },
+        "copy" => {
+            let copied: Value = doc.pointer(&check_pointer(from()?)?).cloned().ok_or("`from` does not exist")?;
+            add(doc, path, copied)
+        },
+        "test" => match doc.pointer(&check_pointer(path)?) {
+            Some(current) if *current == value()? => Ok(()),
+            _ => Err("test failed".to_string())
+        },
+        other => Err(format!("unknown op `{}`", other))
+    }
+}
+
+/// Checks that a path is a valid JSON pointer.
+fn check_pointer(path: &str) -> Result<String, String> {
+    if !path.is_empty() && !path.starts_with('/') {
+        return Err(format!("invalid path `{}`", path));
+    }
+
+    Ok(path.to_string())
+}
+
+/// Splits a JSON pointer into the pointer of the parent and the unescaped last token.
+fn split_pointer(path: &str) -> Result<(&str, String), String> {
+    check_pointer(path)?;
+    let pos: usize = path.rfind('/').ok_or("the root has no parent")?;
+    let token: String = path[pos + 1..].replace("~1", "/").replace("~0", "~");
+
+    Ok((&path[..pos], token))
+}
+
+/// Adds a value at a path, replacing the whole document for the empty path.
+fn add(doc: &mut Value, path: &str, value: Value) -> Result<(), String> {
+    if path.is_empty() {
+        *doc = value;
+        return Ok(());
+    }
+
+    let (parent, token): (&str, String) = split_pointer(path)?;
+    match doc.pointer_mut(parent) {
+        Some(Value::Object(obj)) => {
+            obj.insert(token, value);
+            Ok(())
+        },
+        Some(Value::Array(arr)) => {
+            let index: usize = if token == "-" { arr.len() } else { array_index(&token, arr.len() + 1)? };
+            arr.insert(index, value);
+            Ok(())
+        },
+        Some(_) => Err(format!("`{}` is not a container", parent)),
+        None => Err(format!("`{}` does not exist", parent))
+    }
+}
+
+/// Removes the value at a path and returns it.
+fn remove(doc: &mut Value, path: &str) -> Result<Value, String> {
+    if path.is_empty() {
+        return Ok(std::mem::take(doc));
+    }
+
+    let (parent, token): (&str, String) = split_pointer(path)?;
+    match doc.pointer_mut(parent) {
+        Some(Value::Object(obj)) => obj.remove(&token).ok_or(format!("`{}` does not exist", path)),
+        Some(Value::Array(arr)) => {
+            let index: usize = array_index(&token, arr.len())?;
+            Ok(arr.remove(index))
+        },
+        _ => Err(format!("`{}` does not exist", path))
+    }
+}
+
+/// Parses an array index token which has to be below `len`.
+fn array_index(token: &str, len: usize) -> Result<usize, String> {
+    if token.len() > 1 && token.starts_with('0') {
+        return Err(format!("invalid array index `{}`", token));
+    }
+
+    match token.parse::<usize>() {
+        Ok(index) if index < len => Ok(index),
+        _ => Err(format!("invalid array index `{}`", token))
+    }
+}
@@ -0,0 +1,90 @@
+//! Database metadata kept by the storage engine.
+
+use std::{collections::{BTreeMap, HashMap}, sync::{Mutex, MutexGuard}};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::db::{engine::engine, schema::Schema};
+
+/// Settings of one database.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DbMeta {
+    /// Durability level overriding the server default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub durability: Option<String>,
+
+    /// Indexed JSON paths by table name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub indexes: BTreeMap<String, Vec<String>>,
+
+    /// JSON Schemas of the json rows by table name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub schemas: BTreeMap<String, Schema>
+}
+
+lazy_static! {
+    /// Already read metadata by database name.
+    static ref DB_META: Mutex<HashMap<String, DbMeta>> = Mutex::new(HashMap::new());
+}
+
+/// Reads the metadata of a database.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+///
+/// # Returns
+///
+/// Returns the stored metadata, or the default one if the database has none.
+pub fn get_db_meta(db: &str) -> DbMeta {
+    with_db_meta(db, |meta| meta.clone())
+}
+
+/// Reads a part of the metadata of a database without copying the rest of it.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+/// * `read` - Picks the needed part from the metadata, called with the metadata locked.
+///
+/// # Returns
+///
+/// Returns the value picked by `read`.
+pub fn with_db_meta<T, F>(db: &str, read: F) -> T
+where
+    F: FnOnce(&DbMeta) -> T
+{
+    let mut cache: MutexGuard<'_, HashMap<String, DbMeta>> = DB_META.lock().unwrap();
+
+    let meta: &DbMeta = cache.entry(db.to_string()).or_insert_with(|| {
+        engine().read_db_meta(db)
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    });
+
+    read(meta)
+}
+
+/// Writes the metadata of a database, creating the database if needed.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+/// * `meta` - New metadata.
+///
+/// # Returns
+///
+/// Returns Ok(()) if the metadata was written; Err with an error message otherwise.
+pub fn set_db_meta(db: &str, meta: &DbMeta) -> Result<(), String> {
+    let content: String = serde_json::to_string(meta).map_err(|e| e.to_string())?;
+    engine().write_db_meta(db, &content)?;
+
+    DB_META.lock().unwrap().insert(db.to_string(), meta.clone());
+
+    Ok(())
+}
+
+/// Drops the cached metadata of a deleted database.
+pub fn forget_db(db: &str) {
+    DB_META.lock().unwrap().remove(db);
+}
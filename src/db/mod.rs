@@ -1,4 +1,4 @@
-//! Database file operations module.
+//! Database storage operations module.
 pub mod table;
 
 /// Module for database row operations.
@@ -10,76 +10,178 @@ pub mod db;
 /// Module for filtering json data in row
 pub mod json_filter;
 
-use std::{fs::{self, ReadDir}, io::Error, path::Path};
+/// Module for patching json data in row
+pub mod json_patch;
 
-use crate::config;
+/// Module for the log-structured row storage.
+pub mod segment;
 
-/// Initializes a directory at the specified path if it does not exist.
-///
-/// # Arguments
-///
-/// * `path` - Path of the directory to be initialized.
-///
-/// # Returns
-///
-/// Returns true if the directory is successfully created or already exists; false otherwise.
-fn init_dir(path: &str) -> bool {
-    let dir: &Path = Path::new(&path);
-    if !dir.exists() {
-        let result: Result<(), Error> = fs::create_dir(path);
-        match result {
-            Ok(_) => {
-                println!("[ INFO ] DB: dir created");
-                return true;
-            },
-            Err(error) => {
-                println!("[ ERROR ] DB: directory not created - {}", error);
-                return false;
-            },
+/// Module for the write-ahead log of mutating requests.
+pub mod wal;
+
+/// Module for the database metadata.
+pub mod meta;
+
+/// Module for flushing writes according to the durability level.
+pub mod durability;
+
+/// Module for the pluggable storage engines.
+pub mod engine;
+
+/// Module for the secondary indexes on json fields.
+pub mod index;
+
+/// Module for the expiry of rows with a time to live.
+pub mod ttl;
+
+/// Module for the checksums of stored data.
+pub mod checksum;
+
+/// Module for the consistent snapshots of databases.
+pub mod snapshot;
+
+/// Module for the export and import of tables.
+pub mod transfer;
+
+/// Module for the file system safe encoding of names.
+pub mod names;
+
+/// Module for the JSON Schemas of tables.
+pub mod schema;
+
+/// Module for the statistics of tables and databases.
+pub mod stats;
+
+/// Module for the integrity scan of stored rows.
+pub mod verify;
+
+/// Module for the change-data-capture subscriptions.
+pub mod cdc;
+
+use std::collections::HashMap;
+
+use engine::{engine, StorageEngine};
+use crate::protos::row::Row;
+
+/// A renamed table or database, maps the old names of the touched tables to the new ones.
+pub struct Rename {
+    /// Current database name.
+    pub db: String,
+
+    /// New database name, the same as `db` for a renamed table.
+    pub to_db: String,
+
+    /// Current and new name of a renamed table, None when the whole database is renamed.
+    pub table: Option<(String, String)>
+}
+
+impl Rename {
+    /// Describes a renamed table.
+    pub fn table(db: &str, table: &str, to: &str) -> Self {
+        Self { db: db.to_string(), to_db: db.to_string(), table: Some((table.to_string(), to.to_string())) }
+    }
+
+    /// Describes a renamed database.
+    pub fn db(db: &str, to: &str) -> Self {
+        Self { db: db.to_string(), to_db: to.to_string(), table: None }
+    }
+
+    /// Returns the new database and table name of a table, or None if the rename does not touch it.
+    pub fn map(&self, db: &str, table: &str) -> Option<(String, String)> {
+        if db != self.db {
+            return None;
+        }
+
+        match &self.table {
+            Some((from, to)) if from == table => Some((self.to_db.clone(), to.clone())),
+            Some(_) => None,
+            None => Some((self.to_db.clone(), table.to_string()))
         }
     }
 
-    return true;
-}
+    /// Moves the entries of the touched tables of a map to their new names.
+    pub fn rekey<V>(&self, map: &mut HashMap<(String, String), V>) {
+        let moved: Vec<(String, String)> = map.keys()
+            .filter(|(db, table)| self.map(db, table).is_some())
+            .cloned()
+            .collect();
 
-/// Test for checking if a directory exists.
-#[test]
-fn is_dir_exist_test() {
-    assert_eq!(is_dir_exist("table"), false);
+        for old in moved {
+            if let (Some(value), Some(new)) = (map.remove(&old), self.map(&old.0, &old.1)) {
+                map.insert(new, value);
+            }
+        }
+    }
 }
 
-/// Checks if a directory exists at the specified path.
-///
-/// # Arguments
-///
-/// * `path` - Path of the directory to be checked.
+/// Initializes the configured storage engine and replays the write-ahead log.
 ///
 /// # Returns
 ///
-/// Returns true if the directory exists; false otherwise.
-fn is_dir_exist(path: &str) -> bool {
-    let db_path: &str = &config::CONFIG.db_path;
+/// Returns true if the initialization is successful; false otherwise.
+pub fn init() -> bool {
+    let storage: &dyn StorageEngine = engine();
 
-    let format_path: String = format!("{}/{}", db_path, path);
-    let dir: &Path = Path::new(&format_path);
-    return dir.exists();
+    if !storage.init() {
+        return false;
+    }
+
+    if !storage.is_persistent() {
+        return true;
+    }
+
+    wal::recover()
 }
 
-/// Initializes the database directory based on the configured path.
+/// Updates the indexes, the expiry deadlines and the statistics of a table after a row was written
+/// and publishes the change to its subscribers.
 ///
-/// # Returns
+/// # Arguments
 ///
-/// Returns true if the initialization is successful; false otherwise.
-pub fn init() -> bool {
-    let db_path: &str = &config::CONFIG.db_path;
+/// * `db` - Database name.
+/// * `table` - Table name.
+/// * `key` - Row key.
+/// * `row` - Written row.
+/// * `previous` - Stored row replaced by the write, None if the key did not exist.
+pub fn on_put(db: &str, table: &str, key: &str, row: &Row, previous: Option<&Row>) {
+    // An expired row is replaced as if the key did not exist
+    let created: bool = previous.is_none_or(ttl::is_expired);
+
+    index::on_put(db, table, key, row);
+    ttl::on_put(db, table, key, row);
+    stats::on_put(db, table, row, previous);
+    cdc::on_put(db, table, key, row, created);
+}
+
+/// Updates the indexes, the expiry deadlines and the statistics of a table after a row was deleted
+/// and publishes the change to its subscribers.
+///
+/// `previous` is the deleted row, None if it could not be read.
+pub fn on_delete(db: &str, table: &str, key: &str, previous: Option<&Row>) {
+    index::on_delete(db, table, key);
+    ttl::on_delete(db, table, key);
+    stats::on_delete(db, table, previous);
+    cdc::on_delete(db, table, key);
+}
+
+/// Moves the indexes, the expiry deadlines, the statistics and the subscriptions of renamed tables to the new names.
+pub fn on_rename(rename: &Rename) {
+    index::on_rename(rename);
+    ttl::on_rename(rename);
+    stats::on_rename(rename);
+    cdc::on_rename(rename);
+}
 
-    return init_dir(db_path);
+/// Starts background maintenance of the storage engine.
+pub fn start() {
+    engine().start();
 }
 
 /// Test for creating a new database.
 #[test]
 fn create_db_test() {
     // assert_eq!(create_db("test_db"), true);
+    let _ = engine().delete_db("new_test_db");
     assert_eq!(create_db("new_test_db"), true);
 }
 
@@ -93,16 +195,7 @@ fn create_db_test() {
 ///
 /// Returns true if the database is successfully created; false if it already exists.
 pub fn create_db(name: &str) -> bool {
-    let db_path: &str = &config::CONFIG.db_path;
-    if !Path::new(db_path).exists() {
-        init();
-    }
-
-    if is_dir_exist(name) {
-        return false;
-    }
-
-    return init_dir(&format!("{}/{}", db_path, name));
+    engine().create_db(name)
 }
 
 /// Retrieves a list of rows within a specified database.
@@ -116,25 +209,7 @@ pub fn create_db(name: &str) -> bool {
 ///
 /// Returns a Result containing a vector of row names if successful, or an error message if unsuccessful.
 pub fn get_db(db: &str, name: &str) -> Result<Vec<String>, String> {
-    let db_path: &str = &config::CONFIG.db_path;
-
-    let data: Result<ReadDir, Error> = fs::read_dir(format!("{}/{}/{}", db_path, db, name));
-
-    match data {
-        Ok(dir) => {
-            let rows: Vec<String> = dir
-            .filter_map(|entry| {
-                entry
-                    .ok()
-                    .and_then(|e| e.file_name().to_str().map(String::from))
-                    .map(|s| s[..s.len() - 3].to_string()) // Remove the last 3 characters
-            })
-            .collect();
-
-            Ok(rows)
-        },
-        Err(err) => return Err(err.to_string())
-    }
+    table::get_table(db, name)
 }
 
 /// Test for checking if a database exists.
@@ -153,5 +228,5 @@ fn is_db_exist_test() {
 ///
 /// Returns true if the database exists; false otherwise.
 pub fn is_db_exist(name: &str) -> bool {
-    return is_dir_exist(name);
+    engine().db_exists(name)
 }
\ No newline at end of file
@@ -0,0 +1,211 @@
+//! File system safe encoding and checks of database, table and row names.
+//!
+//! Database and table names become directory names. Every byte outside of
+//! `[A-Za-z0-9_-]` is written as `%XX`, a dot is kept unless it starts the
+//! name, so no name can point outside of its parent directory (`..`, `a/b`)
+//! or clash with hidden files. The encoding is reversible and names made of
+//! the safe characters map to themselves.
+//!
+//! Row keys are stored as bytes. A key which is not UTF-8 (sent with
+//! `key_encoding: hex`) is carried as a string in which every byte outside
+//! of a UTF-8 sequence stands as the char `U+10FF00 + byte`. Those chars are
+//! reserved for it, so the mapping between bytes and keys is reversible.
+
+use std::path::{Component, Path};
+
+/// Max length of an encoded database or table name, the usual file name limit.
+pub const MAX_NAME_LEN: usize = 255;
+
+/// Max length of a row key in bytes.
+pub const MAX_KEY_LEN: usize = 16 * 1024;
+
+/// First of the chars which stand for single bytes of a key.
+const BYTE_ESCAPE: u32 = 0x10FF00;
+
+/// Encodes a name into a file system safe directory name.
+pub fn encode(name: &str) -> String {
+    let mut encoded: String = String::with_capacity(name.len());
+
+    for (i, byte) in name.bytes().enumerate() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'_' | b'-' => encoded.push(byte as char),
+            b'.' if i > 0 => encoded.push('.'),
+            _ => encoded.push_str(&format!("%{:02X}", byte))
+        }
+    }
+
+    encoded
+}
+
+/// Decodes a directory name back into the name.
+///
+/// # Returns
+///
+/// Returns None if the directory name is not a valid encoding.
+pub fn decode(encoded: &str) -> Option<String> {
+    let bytes: &[u8] = encoded.as_bytes();
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i: usize = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            decoded.push(hex_byte(encoded.get(i + 1..i + 3)?)?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+/// Parses two hex digits into a byte.
+fn hex_byte(hex: &str) -> Option<u8> {
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    u8::from_str_radix(hex, 16).ok()
+}
+
+/// Checks a database or table name.
+///
+/// # Arguments
+///
+/// * `kind` - What the name is for, used in the error message (`database`, `table`).
+/// * `name` - Name to check.
+///
+/// # Returns
+///
+/// Returns Ok(()) if the name can be stored; Err with a message saying why it can not otherwise.
+pub fn check_name(kind: &str, name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err(format!("{} name is empty", kind));
+    }
+
+    if encode(name).len() > MAX_NAME_LEN {
+        return Err(format!("{} name is too long, at most {} bytes are allowed after encoding", kind, MAX_NAME_LEN));
+    }
+
+    Ok(())
+}
+
+/// Checks a row key.
+///
+/// # Returns
+///
+/// Returns Ok(()) if the key can be stored; Err with a message saying why it can not otherwise.
+pub fn check_key(key: &str) -> Result<(), String> {
+    if key.is_empty() {
+        return Err("key is empty".to_string());
+    }
+
+    let bytes: Vec<u8> = key_to_bytes(key);
+    if bytes.len() > MAX_KEY_LEN {
+        return Err(format!("key is too long, at most {} bytes are allowed", MAX_KEY_LEN));
+    }
+
+    if key_from_bytes(&bytes) != key {
+        return Err("key holds reserved characters U+10FF00..U+10FFFF, send it hex encoded".to_string());
+    }
+
+    Ok(())
+}
+
+/// Resolves a path given by a client inside a directory of the server.
+///
+/// # Arguments
+///
+/// * `dir` - Directory the path has to stay in.
+/// * `path` - Relative path given by the client.
+///
+/// # Returns
+///
+/// Returns the path joined to the directory; Err if it is absolute or climbs out with `..`.
+pub fn confine_path(dir: &str, path: &str) -> Result<String, String> {
+    let relative: &Path = Path::new(path);
+
+    let inside: bool = relative.components().all(|part| matches!(part, Component::Normal(_) | Component::CurDir))
+        && relative.components().any(|part| matches!(part, Component::Normal(_)));
+    if !inside {
+        return Err(format!("path `{}` has to be relative and may not contain `..`", path));
+    }
+
+    Ok(Path::new(dir).join(relative).to_string_lossy().to_string())
+}
+
+/// Checks the names a row is stored under.
+pub fn check_row(db: &str, table: &str, key: &str) -> Result<(), String> {
+    check_name("database", db)?;
+    check_name("table", table)?;
+    check_key(key)
+}
+
+/// Decodes a hex encoded key, used for keys which can not be sent as a header line.
+///
+/// # Returns
+///
+/// Returns the key if the hex is valid; Err with a message otherwise.
+pub fn decode_hex_key(hex: &str) -> Result<String, String> {
+    if hex.len() % 2 == 1 {
+        return Err("hex key must have an even length".to_string());
+    }
+
+    let bytes: Vec<u8> = (0..hex.len())
+        .step_by(2)
+        .map(|i| hex.get(i..i + 2).and_then(hex_byte))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or("hex key has invalid digits")?;
+
+    Ok(key_from_bytes(&bytes))
+}
+
+/// Turns the stored bytes of a key into the key.
+///
+/// UTF-8 sequences are kept, other bytes and the bytes of the reserved chars
+/// become reserved chars, so any bytes give a key which [`key_to_bytes`] turns back.
+pub fn key_from_bytes(bytes: &[u8]) -> String {
+    let mut key: String = String::with_capacity(bytes.len());
+
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            if is_byte_escape(c) {
+                let mut buf: [u8; 4] = [0; 4];
+                c.encode_utf8(&mut buf).bytes().for_each(|byte| key.push(escape_byte(byte)));
+            } else {
+                key.push(c);
+            }
+        }
+
+        chunk.invalid().iter().for_each(|byte| key.push(escape_byte(*byte)));
+    }
+
+    key
+}
+
+/// Turns a key into the bytes it is stored as.
+pub fn key_to_bytes(key: &str) -> Vec<u8> {
+    let mut bytes: Vec<u8> = Vec::with_capacity(key.len());
+
+    for c in key.chars() {
+        if is_byte_escape(c) {
+            bytes.push((c as u32 - BYTE_ESCAPE) as u8);
+        } else {
+            let mut buf: [u8; 4] = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        }
+    }
+
+    bytes
+}
+
+/// Checks if a char stands for a single byte of a key.
+fn is_byte_escape(c: char) -> bool {
+    c as u32 >= BYTE_ESCAPE
+}
+
+/// Returns the char which stands for a byte of a key.
+fn escape_byte(byte: u8) -> char {
+    char::from_u32(BYTE_ESCAPE + byte as u32).unwrap()
+}
@@ -1,7 +1,4 @@
-use std::{fs::{self, remove_file, OpenOptions, File}, io::{self, Read}};
-use protobuf::{Message, Error };
-
-use crate::{db::{self, table}, protos::row::Row, config};
+use crate::{db::{self, table, engine::engine}, protos::row::Row};
 
 /// Adds a new row to a specified database table.
 ///
@@ -16,26 +13,7 @@ use crate::{db::{self, table}, protos::row::Row, config};
 ///
 /// Returns true if the addition is successful; false otherwise.
 pub fn add_row(db: &str, table: &str, key: &str, row: &mut Row) -> bool {
-    let db_path: &str = &config::CONFIG.db_path;
-
-    if !db::is_db_exist(db) {
-        db::create_db(db);
-    }
-
-    if !table::is_table_exist(db, table) {
-        table::create_table(db, table);
-    }
-
-    let file_path: &String = &format!("{}/{}/{}/{}.el", db_path, db, table, key);
-    let mut file: File = OpenOptions::new()
-        .write(true)
-        .append(true)
-        .create(true)
-        .read(true)
-        .open(&file_path)
-        .expect(&format!("[ ERROR ]: Can't open key - {}", key));
-
-    match row.write_to_writer(&mut file) {
+    match engine().write_row(db, table, key, row) {
         Ok(_) => {
             println!("[ INFO ]: Imported new key - {}", key);
             true
@@ -59,8 +37,6 @@ pub fn add_row(db: &str, table: &str, key: &str, row: &mut Row) -> bool {
 ///
 /// Returns a Result containing the retrieved row if successful, or an error message if unsuccessful.
 pub fn read_row(db: &str, table: &str, key: &str) -> Result<Row, String> {
-    let db_path: &str = &config::CONFIG.db_path;
-
     if !db::is_db_exist(db) {
         return Err("[ ERROR ] Row: DB is not exist".to_string())
     }
@@ -69,28 +45,11 @@ pub fn read_row(db: &str, table: &str, key: &str) -> Result<Row, String> {
         return Err("[ ERROR ] Row: Table is not exist".to_string())
     }
 
-    let file_path: &String = &format!("{}/{}/{}/{}.el", db_path, db, table, key);
-    let file_res: Result<File, std::io::Error> = OpenOptions::new()
-        .read(true)
-        .open(file_path);
-
-    let mut file: fs::File;
-    match file_res {
-        Ok(f) => file = f,
-        Err(_) => return Err("0".to_string())
+    match engine().read_row(db, table, key) {
+        Ok(Some(proto)) => Ok(proto),
+        Ok(None) => Err("0".to_string()),
+        Err(err) => Err(err)
     }
-
-    let mut proto: Row = Row::new();
-
-    let mut cont: Vec<u8> = Vec::with_capacity(file.metadata().unwrap().len().try_into().unwrap());
-    file.read_to_end(&mut cont).expect("[ ERROR ] Row: Can not read key");
-
-    let res: Result<(), Error> = proto.merge_from_bytes(&cont);
-
-    match res {
-        Ok(_) => return Ok(proto),
-        Err(_) => return Err("[ ERROR ] Row: Can not merge from bytes".to_string())
-    };
 }
 
 /// Deletes a specified row from a database table.
@@ -105,8 +64,6 @@ pub fn read_row(db: &str, table: &str, key: &str) -> Result<Row, String> {
 ///
 /// Returns true if the deletion is successful; false otherwise.
 pub fn delete_row(db: &str, table: &str, key: &str) -> bool {
-    let db_path: &str = &config::CONFIG.db_path;
-
     let exist: Result<(), String> = is_row_exist(db, table, key);
 
     match exist {
@@ -114,11 +71,7 @@ pub fn delete_row(db: &str, table: &str, key: &str) -> bool {
         Err(_) => return false
     }
 
-    let res: Result<(), io::Error> = remove_file(&format!("{}/{}/{}/{}.el", db_path, db, table, key));
-    match res {
-        Ok(_) => return true,
-        Err(_) => return false
-    }
+    engine().delete_row(db, table, key).unwrap_or_default()
 }
 
 /// Checks if a specified row exists in a database table.
@@ -133,8 +86,6 @@ pub fn delete_row(db: &str, table: &str, key: &str) -> bool {
 ///
 /// Returns Ok(()) if the row exists; Err with an error message otherwise.
 pub fn is_row_exist(db: &str, table: &str, key: &str) -> Result<(), String> {
-    let db_path: &str = &config::CONFIG.db_path;
-
     if !db::is_db_exist(db) {
         return Err("[ ERROR ] Row: DB is not exist".to_string())
     }
@@ -143,8 +94,9 @@ pub fn is_row_exist(db: &str, table: &str, key: &str) -> Result<(), String> {
         return Err("[ ERROR ] Row: Table is not exist".to_string())
     }
 
-    match fs::metadata(&format!("{}/{}/{}/{}.el", db_path, db, table, key)) {
-        Ok(_) => return Ok(()),
-        Err(_) => return Err("[ ERROR ] Row: is not exist".to_string())
+    if engine().row_exists(db, table, key) {
+        return Ok(());
     }
-}
\ No newline at end of file
+
+    Err("[ ERROR ] Row: is not exist".to_string())
+}
@@ -0,0 +1,622 @@
+//! JSON Schema enforcement for the json rows of a table.
+//!
+//! A table can have one schema, kept in the database metadata. Every json row
+//! written to the table is validated against it, and a table with a schema
+//! accepts json rows only. The common keywords of JSON Schema (draft 7 and
+//! later) are supported:
+//!
+//! * any value - `type`, `enum`, `const`, `allOf`, `anyOf`, `oneOf`, `not`,
+//!   `if` / `then` / `else` and local `$ref`s such as `#/definitions/price`
+//! * numbers - `minimum`, `maximum`, `exclusiveMinimum`, `exclusiveMaximum`, `multipleOf`
+//! * strings - `minLength`, `maxLength`, `pattern`
+//! * arrays - `items`, `prefixItems`, `minItems`, `maxItems`, `uniqueItems`, `contains`
+//! * objects - `properties`, `required`, `additionalProperties`, `patternProperties`,
+//!   `propertyNames`, `minProperties`, `maxProperties`
+//!
+//! Other keywords (`format`, `title`, ...) are ignored.
+//!
+//! The `pattern` and `patternProperties` expressions are compiled once, when
+//! the schema is set or read with the metadata, and kept with the schema.
+
+use std::collections::HashMap;
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+
+use crate::{db::{engine::engine, meta::{self, DbMeta}}, protos::row::Row};
+
+/// Max depth of nested `$ref`s, stops schemas which refer to themselves forever.
+const MAX_REF_DEPTH: usize = 32;
+
+/// Type names of the `type` keyword.
+const TYPE_NAMES: [&str; 7] = ["null", "boolean", "object", "array", "number", "integer", "string"];
+
+/// A JSON Schema along with its compiled expressions, stored as the schema itself.
+#[derive(Debug, Clone)]
+pub struct Schema {
+    /// The JSON Schema.
+    value: Value,
+
+    /// Compiled `pattern` and `patternProperties` expressions by their source.
+    patterns: HashMap<String, Regex>
+}
+
+impl Schema {
+    /// Checks a schema and compiles its expressions.
+    ///
+    /// # Returns
+    ///
+    /// Returns the schema; Err with an error message if it can not be used for validation.
+    pub fn compile(value: Value) -> Result<Self, String> {
+        check_schema(&value, "#")?;
+        Ok(Self::new(value))
+    }
+
+    /// Compiles the expressions of a schema, invalid expressions are left out and match nothing.
+    fn new(value: Value) -> Self {
+        let mut patterns: HashMap<String, Regex> = HashMap::new();
+        collect_patterns(&value, &mut patterns);
+
+        Self { value, patterns }
+    }
+
+    /// Returns the JSON Schema.
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    /// Validates a value against the schema.
+    ///
+    /// # Returns
+    ///
+    /// Returns the violations, empty if the value matches.
+    pub fn validate(&self, value: &Value) -> Vec<Violation> {
+        let mut errors: Vec<Violation> = Vec::new();
+        Validator { root: &self.value, patterns: &self.patterns, errors: &mut errors }.check(&self.value, value, "", 0);
+        errors
+    }
+}
+
+impl Serialize for Schema {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Schema {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Value::deserialize(deserializer).map(Schema::new)
+    }
+}
+
+/// A value which does not match the schema.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Violation {
+    /// JSON pointer of the failing value, empty for the whole row.
+    pub pointer: String,
+
+    /// What is wrong with the value.
+    pub message: String
+}
+
+/// Attaches a schema to a table, or removes it when the schema is `null`.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+/// * `table` - Table name.
+/// * `schema` - The JSON Schema.
+///
+/// # Returns
+///
+/// Returns Ok(()) if the schema was stored; Err with an error message if the schema is invalid.
+pub fn set(db: &str, table: &str, schema: Value) -> Result<(), String> {
+    if !engine().table_exists(db, table) {
+        return Err("no such table".to_string());
+    }
+
+    let mut db_meta: DbMeta = meta::get_db_meta(db);
+    if schema.is_null() {
+        if db_meta.schemas.remove(table).is_none() {
+            return Ok(());
+        }
+    } else {
+        db_meta.schemas.insert(table.to_string(), Schema::compile(schema)?);
+    }
+    meta::set_db_meta(db, &db_meta)?;
+
+    println!("[ INFO ] Schema: set schema of {}/{}", db, table);
+
+    Ok(())
+}
+
+/// Returns the schema of a table.
+pub fn get(db: &str, table: &str) -> Option<Value> {
+    meta::get_db_meta(db).schemas.get(table).map(|schema| schema.value().clone())
+}
+
+/// Drops the schema of a deleted table.
+pub fn forget_table(db: &str, table: &str) {
+    let mut db_meta: DbMeta = meta::get_db_meta(db);
+    if db_meta.schemas.remove(table).is_some() {
+        if let Err(err) = meta::set_db_meta(db, &db_meta) {
+            println!("[ ERROR ] Schema: Can't drop schema of {}/{} - {}", db, table, err);
+        }
+    }
+}
+
+/// Validates a row against the schema of its table.
+///
+/// # Returns
+///
+/// Returns the violations, empty if the row matches or the table has no schema.
+pub fn violations(db: &str, table: &str, row: &Row) -> Vec<Violation> {
+    let schema: Schema = match meta::get_db_meta(db).schemas.remove(table) {
+        Some(schema) => schema,
+        None => return Vec::new()
+    };
+
+    if row.type_() != "json" {
+        return vec![Violation { pointer: String::new(), message: "table has a schema, only json rows are accepted".to_string() }];
+    }
+
+    match serde_json::from_str::<Value>(row.value()) {
+        Ok(value) => schema.validate(&value),
+        Err(err) => vec![Violation { pointer: String::new(), message: err.to_string() }]
+    }
+}
+
+/// Validates a row against the schema of its table.
+///
+/// # Returns
+///
+/// Returns Ok(()) if the row matches; Err with a `422` JSON response listing the violations otherwise.
+pub fn check(db: &str, table: &str, row: &Row) -> Result<(), String> {
+    let errors: Vec<Violation> = violations(db, table, row);
+    if errors.is_empty() {
+        return Ok(());
+    }
+
+    Err(error_response(&errors))
+}
+
+/// Builds the `422` JSON response listing the violations.
+pub fn error_response(errors: &[Violation]) -> String {
+    let response: Value = serde_json::json!({
+        "code": 422,
+        "message": "value does not match the table schema",
+        "errors": errors
+    });
+
+    response.to_string() + "\njson"
+}
+
+/// Joins violations into one line of text.
+pub fn describe(errors: &[Violation]) -> String {
+    errors.iter()
+        .map(|v| format!("{}: {}", if v.pointer.is_empty() { "/" } else { &v.pointer }, v.message))
+        .collect::<Vec<String>>()
+        .join("; ")
+}
+
+/// Walks a value and its schema at the same time.
+struct Validator<'a> {
+    /// The whole schema, `$ref`s are resolved against it.
+    root: &'a Value,
+
+    /// Compiled expressions of the schema.
+    patterns: &'a HashMap<String, Regex>,
+
+    /// Collected violations.
+    errors: &'a mut Vec<Violation>
+}
+
+impl<'a> Validator<'a> {
+    /// Matches a text against a compiled expression, invalid expressions match nothing.
+    fn is_match(&self, pattern: &str, text: &str) -> bool {
+        self.patterns.get(pattern).is_some_and(|regex| regex.is_match(text))
+    }
+
+    /// Records a violation.
+    fn fail(&mut self, pointer: &str, message: String) {
+        self.errors.push(Violation { pointer: pointer.to_string(), message });
+    }
+
+    /// Checks if a value matches a subschema without recording the violations.
+    fn matches(&mut self, schema: &'a Value, value: &Value, pointer: &str, depth: usize) -> bool {
+        let mut errors: Vec<Violation> = Vec::new();
+        Validator { root: self.root, patterns: self.patterns, errors: &mut errors }.check(schema, value, pointer, depth);
+        errors.is_empty()
+    }
+
+    /// Validates a value at `pointer` against a schema.
+    fn check(&mut self, schema: &'a Value, value: &Value, pointer: &str, depth: usize) {
+        let schema: &Map<String, Value> = match schema {
+            Value::Bool(true) => return,
+            Value::Bool(false) => return self.fail(pointer, "no value is allowed here".to_string()),
+            Value::Object(schema) => schema,
+            _ => return
+        };
+
+        if let Some(Value::String(reference)) = schema.get("$ref") {
+            match resolve(self.root, reference) {
+                Some(_) if depth >= MAX_REF_DEPTH => self.fail(pointer, "schema refers to itself too deep".to_string()),
+                Some(target) => self.check(target, value, pointer, depth + 1),
+                None => self.fail(pointer, format!("unknown schema reference {}", reference))
+            }
+        }
+
+        if let Some(expected) = schema.get("type") {
+            let names: Vec<&str> = match expected {
+                Value::String(name) => vec![name.as_str()],
+                Value::Array(names) => names.iter().filter_map(|n| n.as_str()).collect(),
+                _ => Vec::new()
+            };
+
+            if !names.iter().any(|name| is_type(value, name)) {
+                self.fail(pointer, format!("expected {}, found {}", names.join(" or "), type_name(value)));
+            }
+        }
+
+        if let Some(Value::Array(options)) = schema.get("enum") {
+            if !options.contains(value) {
+                self.fail(pointer, "value is not one of the allowed values".to_string());
+            }
+        }
+
+        if let Some(expected) = schema.get("const") {
+            if expected != value {
+                self.fail(pointer, format!("expected {}", expected));
+            }
+        }
+
+        self.check_combinators(schema, value, pointer, depth);
+
+        match value {
+            Value::Number(number) => self.check_number(schema, number.as_f64().unwrap_or(f64::NAN), pointer),
+            Value::String(text) => self.check_string(schema, text, pointer),
+            Value::Array(items) => self.check_array(schema, items, pointer, depth),
+            Value::Object(object) => self.check_object(schema, object, pointer, depth),
+            _ => {}
+        }
+    }
+
+    /// Validates `allOf`, `anyOf`, `oneOf`, `not` and `if`.
+    fn check_combinators(&mut self, schema: &'a Map<String, Value>, value: &Value, pointer: &str, depth: usize) {
+        if let Some(Value::Array(all)) = schema.get("allOf") {
+            for sub in all {
+                self.check(sub, value, pointer, depth);
+            }
+        }
+
+        if let Some(Value::Array(any)) = schema.get("anyOf") {
+            if !any.iter().any(|sub| self.matches(sub, value, pointer, depth)) {
+                self.fail(pointer, "value matches none of the anyOf schemas".to_string());
+            }
+        }
+
+        if let Some(Value::Array(one)) = schema.get("oneOf") {
+            let matched: usize = one.iter().filter(|sub| self.matches(sub, value, pointer, depth)).count();
+            if matched != 1 {
+                self.fail(pointer, format!("value matches {} of the oneOf schemas, expected exactly 1", matched));
+            }
+        }
+
+        if let Some(not) = schema.get("not") {
+            if self.matches(not, value, pointer, depth) {
+                self.fail(pointer, "value matches the not schema".to_string());
+            }
+        }
+
+        if let Some(condition) = schema.get("if") {
+            let branch: Option<&'a Value> = if self.matches(condition, value, pointer, depth) {
+                schema.get("then")
+            } else {
+                schema.get("else")
+            };
+
+            if let Some(branch) = branch {
+                self.check(branch, value, pointer, depth);
+            }
+        }
+    }
+
+    /// Validates the number keywords.
+    fn check_number(&mut self, schema: &Map<String, Value>, number: f64, pointer: &str) {
+        let limit = |name: &str| schema.get(name).and_then(|v| v.as_f64());
+
+        if let Some(min) = limit("minimum") {
+            if number < min {
+                self.fail(pointer, format!("must be at least {}", min));
+            }
+        }
+        if let Some(max) = limit("maximum") {
+            if number > max {
+                self.fail(pointer, format!("must be at most {}", max));
+            }
+        }
+        if let Some(min) = limit("exclusiveMinimum") {
+            if number <= min {
+                self.fail(pointer, format!("must be greater than {}", min));
+            }
+        }
+        if let Some(max) = limit("exclusiveMaximum") {
+            if number >= max {
+                self.fail(pointer, format!("must be less than {}", max));
+            }
+        }
+        if let Some(step) = limit("multipleOf") {
+            let ratio: f64 = number / step;
+            if step > 0.0 && (ratio - ratio.round()).abs() > 1e-9 {
+                self.fail(pointer, format!("must be a multiple of {}", step));
+            }
+        }
+    }
+
+    /// Validates the string keywords.
+    fn check_string(&mut self, schema: &Map<String, Value>, text: &str, pointer: &str) {
+        let length: u64 = text.chars().count() as u64;
+
+        if let Some(min) = schema.get("minLength").and_then(|v| v.as_u64()) {
+            if length < min {
+                self.fail(pointer, format!("must be at least {} characters long", min));
+            }
+        }
+        if let Some(max) = schema.get("maxLength").and_then(|v| v.as_u64()) {
+            if length > max {
+                self.fail(pointer, format!("must be at most {} characters long", max));
+            }
+        }
+        if let Some(Value::String(pattern)) = schema.get("pattern") {
+            if !self.is_match(pattern, text) {
+                self.fail(pointer, format!("does not match the pattern {}", pattern));
+            }
+        }
+    }
+
+    /// Validates the array keywords.
+    fn check_array(&mut self, schema: &'a Map<String, Value>, items: &[Value], pointer: &str, depth: usize) {
+        let len: u64 = items.len() as u64;
+
+        if let Some(min) = schema.get("minItems").and_then(|v| v.as_u64()) {
+            if len < min {
+                self.fail(pointer, format!("must have at least {} items", min));
+            }
+        }
+        if let Some(max) = schema.get("maxItems").and_then(|v| v.as_u64()) {
+            if len > max {
+                self.fail(pointer, format!("must have at most {} items", max));
+            }
+        }
+        if schema.get("uniqueItems") == Some(&Value::Bool(true)) {
+            let duplicate: bool = items.iter().enumerate().any(|(i, item)| items[..i].contains(item));
+            if duplicate {
+                self.fail(pointer, "items must be unique".to_string());
+            }
+        }
+
+        // `prefixItems` (or the draft 7 array form of `items`) checks items by position,
+        // `items` as a schema checks all the items after them
+        let (prefix, rest): (&'a [Value], Option<&'a Value>) = match (schema.get("prefixItems"), schema.get("items")) {
+            (Some(Value::Array(prefix)), rest) => (prefix.as_slice(), rest),
+            (None, Some(Value::Array(prefix))) => (prefix.as_slice(), schema.get("additionalItems")),
+            (_, rest) => (&[], rest)
+        };
+
+        for (i, item) in items.iter().enumerate() {
+            let sub: Option<&'a Value> = prefix.get(i).or(rest);
+            if let Some(sub) = sub {
+                self.check(sub, item, &format!("{}/{}", pointer, i), depth);
+            }
+        }
+
+        if let Some(contains) = schema.get("contains") {
+            let found: bool = items.iter().enumerate().any(|(i, item)| self.matches(contains, item, &format!("{}/{}", pointer, i), depth));
+            if !found {
+                self.fail(pointer, "no item matches the contains schema".to_string());
+            }
+        }
+    }
+
+    /// Validates the object keywords.
+    fn check_object(&mut self, schema: &'a Map<String, Value>, object: &Map<String, Value>, pointer: &str, depth: usize) {
+        let len: u64 = object.len() as u64;
+
+        if let Some(min) = schema.get("minProperties").and_then(|v| v.as_u64()) {
+            if len < min {
+                self.fail(pointer, format!("must have at least {} properties", min));
+            }
+        }
+        if let Some(max) = schema.get("maxProperties").and_then(|v| v.as_u64()) {
+            if len > max {
+                self.fail(pointer, format!("must have at most {} properties", max));
+            }
+        }
+
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for name in required.iter().filter_map(|n| n.as_str()) {
+                if !object.contains_key(name) {
+                    self.fail(&child(pointer, name), "required property is missing".to_string());
+                }
+            }
+        }
+
+        let properties: Option<&'a Map<String, Value>> = schema.get("properties").and_then(|p| p.as_object());
+        let patterns: Option<&'a Map<String, Value>> = schema.get("patternProperties").and_then(|p| p.as_object());
+
+        for (name, item) in object {
+            let path: String = child(pointer, name);
+            let mut known: bool = false;
+
+            if let Some(sub) = properties.and_then(|p| p.get(name)) {
+                known = true;
+                self.check(sub, item, &path, depth);
+            }
+
+            for (pattern, sub) in patterns.into_iter().flatten() {
+                if self.is_match(pattern, name) {
+                    known = true;
+                    self.check(sub, item, &path, depth);
+                }
+            }
+
+            if !known {
+                if let Some(additional) = schema.get("additionalProperties") {
+                    if additional == &Value::Bool(false) {
+                        self.fail(&path, "property is not allowed".to_string());
+                    } else {
+                        self.check(additional, item, &path, depth);
+                    }
+                }
+            }
+
+            if let Some(names) = schema.get("propertyNames") {
+                if !self.matches(names, &Value::String(name.clone()), &path, depth) {
+                    self.fail(&path, "property name does not match the propertyNames schema".to_string());
+                }
+            }
+        }
+    }
+}
+
+/// Checks that a schema can be used for validation.
+///
+/// # Arguments
+///
+/// * `schema` - The schema or a subschema.
+/// * `at` - Location of the subschema, used in the error message.
+fn check_schema(schema: &Value, at: &str) -> Result<(), String> {
+    let schema: &Map<String, Value> = match schema {
+        Value::Bool(_) => return Ok(()),
+        Value::Object(schema) => schema,
+        _ => return Err(format!("schema at {} must be an object or a boolean", at))
+    };
+
+    for (keyword, value) in schema {
+        let at: String = format!("{}/{}", at, escape(keyword));
+
+        match keyword.as_str() {
+            "type" => {
+                let names: Vec<&Value> = match value {
+                    Value::Array(names) => names.iter().collect(),
+                    name => vec![name]
+                };
+                if !names.iter().all(|name| name.as_str().is_some_and(|n| TYPE_NAMES.contains(&n))) {
+                    return Err(format!("{} must name the types {}", at, TYPE_NAMES.join(", ")));
+                }
+            },
+            "enum" | "required" | "allOf" | "anyOf" | "oneOf" | "prefixItems" if !value.is_array() => {
+                return Err(format!("{} must be an array", at));
+            },
+            "required" if !value.as_array().into_iter().flatten().all(|n| n.is_string()) => {
+                return Err(format!("{} must list property names", at));
+            },
+            "allOf" | "anyOf" | "oneOf" | "prefixItems" => {
+                for (i, sub) in value.as_array().into_iter().flatten().enumerate() {
+                    check_schema(sub, &format!("{}/{}", at, i))?;
+                }
+            },
+            "properties" | "patternProperties" | "definitions" | "$defs" => {
+                let subs: &Map<String, Value> = value.as_object().ok_or(format!("{} must be an object", at))?;
+                for (name, sub) in subs {
+                    if keyword == "patternProperties" {
+                        Regex::new(name).map_err(|e| format!("{} has an invalid pattern - {}", at, e))?;
+                    }
+                    check_schema(sub, &format!("{}/{}", at, escape(name)))?;
+                }
+            },
+            "items" if value.is_array() => {
+                for (i, sub) in value.as_array().into_iter().flatten().enumerate() {
+                    check_schema(sub, &format!("{}/{}", at, i))?;
+                }
+            },
+            "items" | "additionalItems" | "additionalProperties" | "contains" | "propertyNames" | "not" | "if" | "then" | "else" => {
+                check_schema(value, &at)?;
+            },
+            "minimum" | "maximum" | "exclusiveMinimum" | "exclusiveMaximum" | "multipleOf" if !value.is_number() => {
+                return Err(format!("{} must be a number", at));
+            },
+            "minLength" | "maxLength" | "minItems" | "maxItems" | "minProperties" | "maxProperties" if !value.is_u64() => {
+                return Err(format!("{} must be a non-negative integer", at));
+            },
+            "pattern" => {
+                let pattern: &str = value.as_str().ok_or(format!("{} must be a string", at))?;
+                Regex::new(pattern).map_err(|e| format!("{} is an invalid pattern - {}", at, e))?;
+            },
+            "$ref" => {
+                let reference: &str = value.as_str().ok_or(format!("{} must be a string", at))?;
+                if !reference.starts_with('#') {
+                    return Err(format!("{} only local references (#/...) are supported", at));
+                }
+            },
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Finds the subschema a local `$ref` such as `#/definitions/price` points at.
+fn resolve<'a>(root: &'a Value, reference: &str) -> Option<&'a Value> {
+    let pointer: &str = reference.strip_prefix('#')?;
+    if pointer.is_empty() {
+        return Some(root);
+    }
+
+    root.pointer(pointer)
+}
+
+/// Checks if a value is of a `type` keyword type.
+fn is_type(value: &Value, name: &str) -> bool {
+    match name {
+        "integer" => value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        name => type_name(value) == name || (name == "number" && value.is_number())
+    }
+}
+
+/// Returns the JSON Schema type name of a value.
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object"
+    }
+}
+
+/// Compiles the `pattern` and `patternProperties` expressions found anywhere in a schema.
+fn collect_patterns(schema: &Value, patterns: &mut HashMap<String, Regex>) {
+    match schema {
+        Value::Object(schema) => {
+            for (keyword, value) in schema {
+                let sources: Vec<&String> = match (keyword.as_str(), value) {
+                    ("pattern", Value::String(pattern)) => vec![pattern],
+                    ("patternProperties", Value::Object(subs)) => subs.keys().collect(),
+                    _ => Vec::new()
+                };
+
+                for source in sources {
+                    if let Ok(regex) = Regex::new(source) {
+                        patterns.insert(source.clone(), regex);
+                    }
+                }
+
+                collect_patterns(value, patterns);
+            }
+        },
+        Value::Array(items) => items.iter().for_each(|item| collect_patterns(item, patterns)),
+        _ => {}
+    }
+}
+
+/// Builds the JSON pointer of an object property.
+fn child(pointer: &str, name: &str) -> String {
+    format!("{}/{}", pointer, escape(name))
+}
+
+/// Escapes a JSON pointer token.
+fn escape(name: &str) -> String {
+    name.replace('~', "~0").replace('/', "~1")
+}
@@ -0,0 +1,1033 @@
+//! Log-structured segment storage for table rows.
+//!
+//! Every table directory holds a set of append-only segment files named
+//! `{id}.seg`. A write appends a record to the active segment and a delete
+//! appends a tombstone, so the latest record of a key always wins. The
+//! key -> position index lives in memory and is rebuilt from the segments
+//! when a table is opened. Overwritten and deleted records are dropped by
+//! the background compaction.
+//!
+//! Record layout (little endian):
+//!
+//! | kind (u8) | key length (u32) | value length (u32) | crc32 (u32) | key | value |
+//!
+//! The CRC-32 covers the key and the value and is checked on every read and
+//! when a segment is replayed.
+//! Segments of version 1 have no checksum field, they are still read but
+//! new records always go to a segment of the current version.
+
+use std::{collections::{BTreeMap, BTreeSet, HashMap}, fs::{self, File, OpenOptions, ReadDir}, io::{self, BufReader, Read, Seek, SeekFrom, Write}, path::Path, sync::{Arc, Mutex, MutexGuard}, thread, time::{self, SystemTime, UNIX_EPOCH}};
+use lazy_static::lazy_static;
+use protobuf::Message;
+
+use crate::{config, db::{checksum::{self, Crc32}, durability, engine::{fs::{db_dir, table_dir}, KeyRange, SkippedRecord}, names}, protos::row::Row};
+
+/// Magic bytes at the beginning of every segment file.
+const SEGMENT_MAGIC: &[u8; 4] = b"RDSG";
+
+/// Version of the record layout.
+const SEGMENT_VERSION: u8 = 2;
+
+/// Version of the record layout without checksums.
+const LEGACY_VERSION: u8 = 1;
+
+/// Size of the segment file header (magic + version).
+const HEADER_SIZE: u64 = 5;
+
+/// Size of the fixed part of a record (kind + key length + value length + crc32).
+const RECORD_HEADER_SIZE: u64 = 13;
+
+/// Size of the fixed part of a record of version 1 (kind + key length + value length).
+const LEGACY_RECORD_HEADER_SIZE: u64 = 9;
+
+/// Record kind of a written value.
+const KIND_PUT: u8 = 1;
+
+/// Record kind of a deleted key.
+const KIND_DELETE: u8 = 2;
+
+/// Extension of segment files.
+const SEGMENT_EXT: &str = "seg";
+
+/// Extension of rows stored by the old one-file-per-key layout.
+const LEGACY_EXT: &str = "el";
+
+/// Minimum amount of dead bytes in a table before it gets compacted.
+const COMPACTION_MIN_DEAD: u64 = 1024 * 1024;
+
+/// Pause between two compaction passes (in seconds).
+const COMPACTION_INTERVAL: u64 = 30;
+
+/// Position of the latest record of a key.
+#[derive(Debug, Clone, Copy)]
+struct RecordPos {
+    /// Segment id.
+    segment: u32,
+
+    /// Offset of the value inside the segment.
+    offset: u64,
+
+    /// Length of the value.
+    length: u32,
+
+    /// Checksum of the key and the value, None for records of version 1.
+    checksum: Option<u32>,
+
+    /// Size of the whole record, used for dead bytes accounting.
+    record_size: u64
+}
+
+/// A record read back from a segment.
+#[derive(Debug, PartialEq)]
+struct Record {
+    /// Record kind.
+    kind: u8,
+
+    /// Key bytes.
+    key: Vec<u8>,
+
+    /// Length of the value.
+    value_len: u32,
+
+    /// Stored checksum, None for records of version 1.
+    crc: Option<u32>,
+
+    /// The stored checksum matches the key and the value.
+    valid: bool,
+
+    /// Size of the whole record.
+    size: u64
+}
+
+/// Rows of a table as they were when it was captured, made by [`capture`].
+///
+/// Records are only ever appended, and the captured segments are held open,
+/// so neither later writes nor a compaction removing the files change what
+/// the capture reads. Yields the keys in lexicographic order with their values.
+pub struct Capture {
+    /// Table directory.
+    dir: String,
+
+    /// Captured keys with their records.
+    keys: std::collections::btree_map::IntoIter<String, RecordPos>,
+
+    /// Open captured segments by id.
+    files: HashMap<u32, File>
+}
+
+impl Iterator for Capture {
+    type Item = Result<(String, Vec<u8>), String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, pos): (String, RecordPos) = self.keys.next()?;
+        let path: String = segment_path(&self.dir, pos.segment);
+
+        let value: Result<Vec<u8>, String> = match self.files.get_mut(&pos.segment) {
+            Some(file) => read_at(file, &path, &key, &pos),
+            None => Err(format!("[ ERROR ] Segment: {} is not captured", path))
+        };
+
+        Some(value.map(|value| (key, value)))
+    }
+}
+
+/// Outcome of reading one record of a segment.
+#[derive(Debug, PartialEq)]
+enum Scan {
+    /// A record with a known kind which fits into the file.
+    Record(Record),
+
+    /// The record runs past the end of the file.
+    Incomplete,
+
+    /// The bytes do not frame a record.
+    Damaged
+}
+
+/// In-memory state of one table log.
+pub struct TableLog {
+    /// Table directory.
+    dir: String,
+
+    /// Latest record of every live key, sorted by key.
+    keys: BTreeMap<String, RecordPos>,
+
+    /// Size of every segment by id.
+    segments: BTreeMap<u32, u64>,
+
+    /// Id of the segment new records are appended to.
+    active: u32,
+
+    /// Segments of version 1, never appended to.
+    legacy: BTreeSet<u32>,
+
+    /// Open handle of the active segment.
+    writer: Option<File>,
+
+    /// Bytes taken by overwritten records and tombstones.
+    dead_bytes: u64,
+
+    /// Damaged records skipped by the replay.
+    skipped: Vec<SkippedRecord>
+}
+
+lazy_static! {
+    /// Opened table logs by table directory.
+    static ref TABLES: Mutex<HashMap<String, Arc<Mutex<TableLog>>>> = Mutex::new(HashMap::new());
+
+    /// Held for a whole compaction, directories are not moved or removed while segments are copied.
+    static ref COMPACTION: Mutex<()> = Mutex::new(());
+}
+
+impl TableLog {
+    /// Loads a table log by replaying all segments of the directory.
+    fn load(dir: &str) -> Result<Self, String> {
+        let mut log: TableLog = TableLog {
+            dir: dir.to_string(),
+            keys: BTreeMap::new(),
+            segments: BTreeMap::new(),
+            active: 0,
+            legacy: BTreeSet::new(),
+            writer: None,
+            dead_bytes: 0,
+            skipped: Vec::new()
+        };
+
+        for id in list_files(dir, SEGMENT_EXT)?.iter().filter_map(|name| name.parse::<u32>().ok()) {
+            log.replay(id)?;
+        }
+
+        log.import_legacy()?;
+
+        Ok(log)
+    }
+
+    /// Replays one segment into the index.
+    ///
+    /// Every record is checked against its checksum. A record with a wrong
+    /// checksum is still indexed, so reading its key reports the damage instead
+    /// of returning an older value. Once the bytes do not frame a record, as
+    /// left by a torn write or damaged lengths, the rest of the segment can not
+    /// be told apart from bytes inside a value: it is kept in place, listed for
+    /// the integrity scan, and new records go to a fresh segment.
+    fn replay(&mut self, id: u32) -> Result<(), String> {
+        let path: String = segment_path(&self.dir, id);
+        let file: File = File::open(&path).map_err(|e| format!("[ ERROR ] Segment: Can not open {} - {}", path, e))?;
+        let size: u64 = file.metadata().map_err(|e| e.to_string())?.len();
+        let mut reader: BufReader<File> = BufReader::new(file);
+
+        let mut header: [u8; HEADER_SIZE as usize] = [0; HEADER_SIZE as usize];
+        if reader.read_exact(&mut header).is_err() {
+            // A crash right after creating the file, write the header again
+            write_header(&path)?;
+            self.segments.insert(id, HEADER_SIZE);
+            self.active = id;
+            return Ok(());
+        }
+
+        if &header[..4] != SEGMENT_MAGIC || (header[4] != SEGMENT_VERSION && header[4] != LEGACY_VERSION) {
+            return Err(format!("[ ERROR ] Segment: {} is not a segment file", path));
+        }
+
+        let legacy: bool = header[4] == LEGACY_VERSION;
+        if legacy {
+            self.legacy.insert(id);
+        }
+
+        let mut offset: u64 = HEADER_SIZE;
+        while offset < size {
+            let scan: Scan = match read_record(&mut reader, size - offset, legacy).map_err(|e| e.to_string())? {
+                // A wrong checksum may come from damaged lengths, the framing holds if the next record checks out
+                Scan::Record(record) if !record.valid && !framed(&path, offset + record.size, size)? => Scan::Damaged,
+                scan => scan
+            };
+
+            let record: Record = match scan {
+                Scan::Record(record) => record,
+                _ => {
+                    println!("[ WARN ] Segment: skip damaged bytes {}..{} of {}", offset, size, path);
+                    self.skipped.push(SkippedRecord { segment: id, offset, length: size - offset, key: damaged_key(&path, offset, size, legacy) });
+                    offset = size;
+                    break;
+                }
+            };
+
+            let key: String = names::key_from_bytes(&record.key);
+            if !record.valid {
+                println!("[ WARN ] Segment: checksum mismatch of key {} at {} of {}", key, offset, path);
+            }
+
+            match record.kind {
+                KIND_PUT => {
+                    let pos: RecordPos = RecordPos {
+                        segment: id,
+                        offset: offset + record.size - record.value_len as u64,
+                        length: record.value_len,
+                        checksum: record.crc,
+                        record_size: record.size
+                    };
+                    if let Some(old) = self.keys.insert(key, pos) {
+                        self.dead_bytes += old.record_size;
+                    }
+                },
+                _ => {
+                    if let Some(old) = self.keys.remove(&key) {
+                        self.dead_bytes += old.record_size;
+                    }
+                    self.dead_bytes += record.size;
+                }
+            }
+
+            offset += record.size;
+        }
+
+        self.segments.insert(id, offset);
+        self.active = id;
+
+        Ok(())
+    }
+
+    /// Moves rows written by the one-file-per-key layout into the log.
+    fn import_legacy(&mut self) -> Result<(), String> {
+        let legacy: Vec<String> = list_files(&self.dir, LEGACY_EXT)?;
+        if legacy.is_empty() {
+            return Ok(());
+        }
+
+        for key in legacy.iter() {
+            let path: String = format!("{}/{}.{}", self.dir, key, LEGACY_EXT);
+            let bytes: Vec<u8> = fs::read(&path).map_err(|e| e.to_string())?;
+
+            // Old files could hold several appended rows, merging keeps the latest fields
+            let mut row: Row = Row::new();
+            if row.merge_from_bytes(&bytes).is_err() {
+                println!("[ WARN ] Segment: skip broken legacy row - {}", path);
+                continue;
+            }
+
+            let value: Vec<u8> = row.write_to_bytes().map_err(|e| e.to_string())?;
+            self.put(key, &value)?;
+        }
+
+        if let Some(writer) = self.writer.as_mut() {
+            writer.sync_all().map_err(|e| e.to_string())?;
+        }
+
+        for key in legacy.iter() {
+            let _ = fs::remove_file(format!("{}/{}.{}", self.dir, key, LEGACY_EXT));
+        }
+
+        println!("[ INFO ] Segment: imported {} legacy rows into {}", legacy.len(), self.dir);
+
+        Ok(())
+    }
+
+    /// Returns the handle of the active segment, starting a new one when it is full.
+    fn writer(&mut self) -> Result<&mut File, String> {
+        let max_size: u64 = config::CONFIG.segment_size as u64 * 1024 * 1024;
+        let active_size: u64 = self.segments.get(&self.active).cloned().unwrap_or(0);
+
+        // Records appended after skipped bytes could never be replayed
+        let sealed: bool = self.legacy.contains(&self.active) || self.skipped.iter().any(|skipped| skipped.segment == self.active);
+
+        if self.active == 0 || active_size >= max_size || sealed {
+            let id: u32 = self.active + 1;
+            let path: String = segment_path(&self.dir, id);
+            write_header(&path)?;
+
+            self.segments.insert(id, HEADER_SIZE);
+            self.active = id;
+            self.writer = None;
+        }
+
+        if self.writer.is_none() {
+            let path: String = segment_path(&self.dir, self.active);
+            let file: File = OpenOptions::new()
+                .append(true)
+                .open(&path)
+                .map_err(|e| format!("[ ERROR ] Segment: Can not open {} - {}", path, e))?;
+            self.writer = Some(file);
+        }
+
+        Ok(self.writer.as_mut().unwrap())
+    }
+
+    /// Appends a record to the active segment, keys and values over 4 GB are refused.
+    fn append(&mut self, kind: u8, key: &str, value: &[u8]) -> Result<RecordPos, String> {
+        let record: Vec<u8> = encode_record(kind, key, value)?;
+
+        self.writer()?.write_all(&record).map_err(|e| format!("[ ERROR ] Segment: Can not write key - {}. Reason: {}", key, e))?;
+
+        let size: &mut u64 = self.segments.get_mut(&self.active).unwrap();
+        let pos: RecordPos = RecordPos {
+            segment: self.active,
+            offset: *size + (record.len() - value.len()) as u64,
+            length: value.len() as u32,
+            checksum: Some(record_checksum(key, value)),
+            record_size: record.len() as u64
+        };
+        *size += record.len() as u64;
+
+        Ok(pos)
+    }
+
+    /// Returns a new handle of the active segment for flushing it outside of the table lock.
+    fn handle(&self) -> Result<File, String> {
+        match self.writer.as_ref() {
+            Some(file) => file.try_clone().map_err(|e| e.to_string()),
+            None => Err("[ ERROR ] Segment: No active segment".to_string())
+        }
+    }
+
+    /// Reads the value a record position points to and checks it against the record checksum.
+    fn read(&self, key: &str, pos: &RecordPos) -> Result<Vec<u8>, String> {
+        read_value(&self.dir, key, pos)
+    }
+
+    /// Writes a new value for the key.
+    fn put(&mut self, key: &str, value: &[u8]) -> Result<(), String> {
+        let pos: RecordPos = self.append(KIND_PUT, key, value)?;
+        if let Some(old) = self.keys.insert(key.to_string(), pos) {
+            self.dead_bytes += old.record_size;
+        }
+
+        Ok(())
+    }
+
+    /// Writes a tombstone for the key, returns false if the key is not present.
+    fn delete(&mut self, key: &str) -> Result<bool, String> {
+        if !self.keys.contains_key(key) {
+            return Ok(false);
+        }
+
+        let pos: RecordPos = self.append(KIND_DELETE, key, &[])?;
+        let old: RecordPos = self.keys.remove(key).unwrap();
+        self.dead_bytes += old.record_size + pos.record_size;
+
+        Ok(true)
+    }
+
+    /// Total size of all segments.
+    fn total_bytes(&self) -> u64 {
+        self.segments.values().sum()
+    }
+
+    /// Starts a compaction of all current segments.
+    ///
+    /// New records go to a fresh segment from now on, so the compacted segment
+    /// can take the id between the current segments and the ones written while
+    /// it is copied, and replays in the right place.
+    ///
+    /// # Returns
+    ///
+    /// Returns None if there is nothing to compact.
+    fn begin_compaction(&mut self) -> Result<Option<Compaction>, String> {
+        if self.segments.is_empty() {
+            return Ok(None);
+        }
+
+        let compaction: Compaction = Compaction {
+            dir: self.dir.clone(),
+            id: self.active + 1,
+            old: self.segments.keys().cloned().collect(),
+            keys: self.keys.clone()
+        };
+
+        let next: u32 = self.active + 2;
+        write_header(&segment_path(&self.dir, next))?;
+        self.segments.insert(next, HEADER_SIZE);
+        self.active = next;
+        self.writer = None;
+
+        Ok(Some(compaction))
+    }
+
+    /// Switches the keys over to a compacted segment and removes the old segments.
+    ///
+    /// Keys written or deleted while the segment was copied keep their newer state.
+    fn finish_compaction(&mut self, compaction: Compaction, keys: BTreeMap<String, RecordPos>, size: u64) {
+        let dropped: u64 = self.dead_bytes;
+
+        for (key, pos) in keys {
+            if let Some(current) = self.keys.get_mut(&key) {
+                let copied: &RecordPos = &compaction.keys[&key];
+                if current.segment == copied.segment && current.offset == copied.offset {
+                    *current = pos;
+                }
+            }
+        }
+
+        self.skipped.retain(|skipped| !compaction.old.contains(&skipped.segment));
+        for old in compaction.old.iter() {
+            self.segments.remove(old);
+            self.legacy.remove(old);
+        }
+        self.segments.insert(compaction.id, size);
+
+        // Everything which is not a live record or a segment header is garbage
+        let live: u64 = self.keys.values().map(|pos| pos.record_size).sum();
+        self.dead_bytes = self.total_bytes().saturating_sub(live + HEADER_SIZE * self.segments.len() as u64);
+
+        for old in compaction.old {
+            let _ = fs::remove_file(segment_path(&self.dir, old));
+        }
+
+        println!("[ INFO ] Segment: compacted {} - dropped {} bytes", self.dir, dropped.saturating_sub(self.dead_bytes));
+    }
+}
+
+/// A compaction in progress.
+struct Compaction {
+    /// Table directory.
+    dir: String,
+
+    /// Id of the compacted segment.
+    id: u32,
+
+    /// Segments replaced by the compacted one.
+    old: Vec<u32>,
+
+    /// Live keys of the old segments when the compaction started.
+    keys: BTreeMap<String, RecordPos>
+}
+
+impl Compaction {
+    /// Writes the live records into the compacted segment, without holding the table lock.
+    ///
+    /// # Returns
+    ///
+    /// Returns the new positions of the keys and the size of the segment.
+    fn copy(&self) -> Result<(BTreeMap<String, RecordPos>, u64), String> {
+        let path: String = segment_path(&self.dir, self.id);
+        write_header(&path)?;
+
+        let mut file: File = OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .map_err(|e| e.to_string())?;
+
+        let mut keys: BTreeMap<String, RecordPos> = BTreeMap::new();
+        let mut size: u64 = HEADER_SIZE;
+
+        // A damaged record stops the compaction, a fresh checksum would hide the damage
+        for (key, pos) in self.keys.iter() {
+            let value: Vec<u8> = read_value(&self.dir, key, pos)?;
+            let record: Vec<u8> = encode_record(KIND_PUT, key, &value)?;
+            file.write_all(&record).map_err(|e| e.to_string())?;
+
+            keys.insert(key.to_string(), RecordPos {
+                segment: self.id,
+                offset: size + (record.len() - value.len()) as u64,
+                length: value.len() as u32,
+                checksum: Some(record_checksum(key, &value)),
+                record_size: record.len() as u64
+            });
+            size += record.len() as u64;
+        }
+
+        // The new segment must be on disk before the old ones disappear
+        file.sync_all().map_err(|e| e.to_string())?;
+
+        Ok((keys, size))
+    }
+}
+
+/// Builds the path of a segment file.
+fn segment_path(dir: &str, id: u32) -> String {
+    format!("{}/{:010}.{}", dir, id, SEGMENT_EXT)
+}
+
+/// Creates a new segment file containing only the header.
+fn write_header(path: &str) -> Result<(), String> {
+    let mut file: File = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+        .map_err(|e| format!("[ ERROR ] Segment: Can not create {} - {}", path, e))?;
+
+    file.write_all(SEGMENT_MAGIC).map_err(|e| e.to_string())?;
+    file.write_all(&[SEGMENT_VERSION]).map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Reads the value a record position of a table directory points to and checks it against the record checksum.
+fn read_value(dir: &str, key: &str, pos: &RecordPos) -> Result<Vec<u8>, String> {
+    let path: String = segment_path(dir, pos.segment);
+    let mut file: File = File::open(&path).map_err(|e| format!("[ ERROR ] Segment: Can not open {} - {}", path, e))?;
+
+    read_at(&mut file, &path, key, pos)
+}
+
+/// Reads the value a record position points to from an open segment and checks it against the record checksum.
+fn read_at(file: &mut File, path: &str, key: &str, pos: &RecordPos) -> Result<Vec<u8>, String> {
+    let mut value: Vec<u8> = vec![0; pos.length as usize];
+    file.seek(SeekFrom::Start(pos.offset)).map_err(|e| e.to_string())?;
+    if let Err(e) = file.read_exact(&mut value) {
+        return Err(checksum::corrupted(key, &format!("record is cut off in {} - {}", path, e)));
+    }
+
+    match pos.checksum {
+        Some(crc) if crc != record_checksum(key, &value) => Err(checksum::corrupted(key, &format!("checksum mismatch in {}", path))),
+        _ => Ok(value)
+    }
+}
+
+/// Computes the checksum of a record from its key and value.
+fn record_checksum(key: &str, value: &[u8]) -> u32 {
+    bytes_checksum(&names::key_to_bytes(key), value)
+}
+
+/// Computes the checksum of a record from the bytes of its key and value.
+fn bytes_checksum(key: &[u8], value: &[u8]) -> u32 {
+    let mut crc: Crc32 = Crc32::new();
+    crc.update(key);
+    crc.update(value);
+    crc.finish()
+}
+
+/// Reads the record at the position of the reader.
+///
+/// The lengths of the record are checked against the `remaining` bytes of the
+/// file before anything is allocated for them.
+fn read_record<R: Read>(reader: &mut R, remaining: u64, legacy: bool) -> io::Result<Scan> {
+    let head_size: u64 = if legacy { LEGACY_RECORD_HEADER_SIZE } else { RECORD_HEADER_SIZE };
+    if remaining < head_size {
+        return Ok(Scan::Incomplete);
+    }
+
+    let mut head: Vec<u8> = vec![0; head_size as usize];
+    match reader.read_exact(&mut head) {
+        Ok(_) => {},
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(Scan::Incomplete),
+        Err(e) => return Err(e)
+    }
+
+    let kind: u8 = head[0];
+    let key_len: u32 = u32::from_le_bytes([head[1], head[2], head[3], head[4]]);
+    let value_len: u32 = u32::from_le_bytes([head[5], head[6], head[7], head[8]]);
+    let crc: Option<u32> = (!legacy).then(|| u32::from_le_bytes([head[9], head[10], head[11], head[12]]));
+
+    let known: bool = kind == KIND_PUT || kind == KIND_DELETE;
+    let size: u64 = head_size + key_len as u64 + value_len as u64;
+    if size > remaining {
+        return Ok(if known { Scan::Incomplete } else { Scan::Damaged });
+    }
+    if !known {
+        return Ok(Scan::Damaged);
+    }
+
+    let mut key: Vec<u8> = vec![0; key_len as usize];
+    let mut value: Vec<u8> = vec![0; value_len as usize];
+    reader.read_exact(&mut key)?;
+    reader.read_exact(&mut value)?;
+
+    let valid: bool = match crc {
+        Some(crc) => crc == bytes_checksum(&key, &value),
+        None => true
+    };
+
+    Ok(Scan::Record(Record { kind, key, value_len, crc, valid, size }))
+}
+
+/// Reads the key named by the header of a damaged record, if it lies within the damaged bytes.
+fn damaged_key(path: &str, offset: u64, end: u64, legacy: bool) -> Option<String> {
+    let head_size: u64 = if legacy { LEGACY_RECORD_HEADER_SIZE } else { RECORD_HEADER_SIZE };
+    let mut file: File = File::open(path).ok()?;
+    let mut head: Vec<u8> = vec![0; head_size as usize];
+    file.seek(SeekFrom::Start(offset)).ok()?;
+    file.read_exact(&mut head).ok()?;
+
+    let key_len: u64 = u32::from_le_bytes([head[1], head[2], head[3], head[4]]) as u64;
+    if key_len == 0 || offset + head_size + key_len > end {
+        return None;
+    }
+
+    let mut key: Vec<u8> = vec![0; key_len as usize];
+    file.read_exact(&mut key).ok()?;
+    Some(names::key_from_bytes(&key))
+}
+
+/// Checks if a valid record, or the end of the file, follows a record ending at `end`.
+fn framed(path: &str, end: u64, size: u64) -> Result<bool, String> {
+    if end == size {
+        return Ok(true);
+    }
+
+    let mut file: File = File::open(path).map_err(|e| e.to_string())?;
+    file.seek(SeekFrom::Start(end)).map_err(|e| e.to_string())?;
+
+    match read_record(&mut file, size - end, false).map_err(|e| e.to_string())? {
+        Scan::Record(record) => Ok(record.valid),
+        _ => Ok(false)
+    }
+}
+
+/// Serializes one record.
+///
+/// # Returns
+///
+/// Returns the record bytes, or an error message if the key or the value does not fit the u32 length fields.
+fn encode_record(kind: u8, key: &str, value: &[u8]) -> Result<Vec<u8>, String> {
+    let key: Vec<u8> = names::key_to_bytes(key);
+    let key_len: u32 = u32::try_from(key.len()).map_err(|_| format!("[ ERROR ] Segment: Key is too long - {} bytes", key.len()))?;
+    let value_len: u32 = u32::try_from(value.len()).map_err(|_| format!("[ ERROR ] Segment: Value is too long - {} bytes", value.len()))?;
+
+    let mut record: Vec<u8> = Vec::with_capacity(RECORD_HEADER_SIZE as usize + key.len() + value.len());
+    record.push(kind);
+    record.extend_from_slice(&key_len.to_le_bytes());
+    record.extend_from_slice(&value_len.to_le_bytes());
+    record.extend_from_slice(&bytes_checksum(&key, value).to_le_bytes());
+    record.extend_from_slice(&key);
+    record.extend_from_slice(value);
+
+    Ok(record)
+}
+
+/// Lists file names (without extension) with the given extension in a directory.
+fn list_files(dir: &str, ext: &str) -> Result<Vec<String>, String> {
+    let data: ReadDir = fs::read_dir(dir).map_err(|e| e.to_string())?;
+
+    let mut names: Vec<String> = data
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().to_str().map(String::from))
+        .filter_map(|name| name.strip_suffix(&format!(".{}", ext)).map(String::from))
+        .collect();
+    names.sort();
+
+    Ok(names)
+}
+
+/// Returns the log of a table, loading it from disk on first use.
+fn open(db: &str, table: &str) -> Result<Arc<Mutex<TableLog>>, String> {
+    let dir: String = table_dir(db, table);
+    let mut tables: MutexGuard<'_, HashMap<String, Arc<Mutex<TableLog>>>> = TABLES.lock().unwrap();
+
+    if let Some(log) = tables.get(&dir) {
+        return Ok(log.clone());
+    }
+
+    if !Path::new(&dir).exists() {
+        return Err("[ ERROR ] Segment: Table is not exist".to_string());
+    }
+
+    let log: Arc<Mutex<TableLog>> = Arc::new(Mutex::new(TableLog::load(&dir)?));
+    tables.insert(dir, log.clone());
+
+    Ok(log)
+}
+
+/// Writes a value for the key into the table log.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+/// * `table` - Table name.
+/// * `key` - Row key.
+/// * `value` - Serialized row.
+///
+/// # Returns
+///
+/// Returns Ok(()) once the record is appended and meets the durability level of the database.
+pub fn put(db: &str, table: &str, key: &str, value: &[u8]) -> Result<(), String> {
+    let handle: File = {
+        let log: Arc<Mutex<TableLog>> = open(db, table)?;
+        let mut log: MutexGuard<'_, TableLog> = log.lock().unwrap();
+
+        log.put(key, value)?;
+        log.handle()?
+    };
+
+    durability::sync(db, &handle)
+}
+
+/// Reads the latest value of the key from the table log.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+/// * `table` - Table name.
+/// * `key` - Row key.
+///
+/// # Returns
+///
+/// Returns Ok(None) if the key is not present.
+pub fn get(db: &str, table: &str, key: &str) -> Result<Option<Vec<u8>>, String> {
+    let log: Arc<Mutex<TableLog>> = open(db, table)?;
+    let log: MutexGuard<'_, TableLog> = log.lock().unwrap();
+
+    match log.keys.get(key) {
+        Some(pos) => Ok(Some(log.read(key, pos)?)),
+        None => Ok(None)
+    }
+}
+
+/// Writes a tombstone for the key.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+/// * `table` - Table name.
+/// * `key` - Row key.
+///
+/// # Returns
+///
+/// Returns Ok(true) if the key was deleted, Ok(false) if it was not present.
+pub fn delete(db: &str, table: &str, key: &str) -> Result<bool, String> {
+    let handle: File = {
+        let log: Arc<Mutex<TableLog>> = open(db, table)?;
+        let mut log: MutexGuard<'_, TableLog> = log.lock().unwrap();
+
+        if !log.delete(key)? {
+            return Ok(false);
+        }
+        log.handle()?
+    };
+
+    durability::sync(db, &handle)?;
+
+    Ok(true)
+}
+
+/// Checks if the key is present in the table log.
+pub fn contains(db: &str, table: &str, key: &str) -> Result<bool, String> {
+    let log: Arc<Mutex<TableLog>> = open(db, table)?;
+    let log: MutexGuard<'_, TableLog> = log.lock().unwrap();
+
+    Ok(log.keys.contains_key(key))
+}
+
+/// Returns all live keys of the table in lexicographic order.
+pub fn keys(db: &str, table: &str) -> Result<Vec<String>, String> {
+    let log: Arc<Mutex<TableLog>> = open(db, table)?;
+    let log: MutexGuard<'_, TableLog> = log.lock().unwrap();
+
+    Ok(log.keys.keys().cloned().collect())
+}
+
+/// Returns the damaged records skipped when the table was loaded.
+pub fn skipped(db: &str, table: &str) -> Result<Vec<SkippedRecord>, String> {
+    let log: Arc<Mutex<TableLog>> = open(db, table)?;
+    let log: MutexGuard<'_, TableLog> = log.lock().unwrap();
+
+    Ok(log.skipped.clone())
+}
+
+/// Captures the live keys of the table and holds its segments open.
+///
+/// # Returns
+///
+/// Returns the rows of the table as they are now, to be read while writes go on.
+pub fn capture(db: &str, table: &str) -> Result<Capture, String> {
+    let log: Arc<Mutex<TableLog>> = open(db, table)?;
+    let log: MutexGuard<'_, TableLog> = log.lock().unwrap();
+
+    let mut files: HashMap<u32, File> = HashMap::with_capacity(log.segments.len());
+    for id in log.segments.keys() {
+        let path: String = segment_path(&log.dir, *id);
+        let file: File = File::open(&path).map_err(|e| format!("[ ERROR ] Segment: Can not open {} - {}", path, e))?;
+        files.insert(*id, file);
+    }
+
+    Ok(Capture { dir: log.dir.clone(), keys: log.keys.clone().into_iter(), files })
+}
+
+/// Returns the live keys of the table which fall into the range.
+pub fn scan(db: &str, table: &str, range: &KeyRange) -> Result<Vec<String>, String> {
+    let log: Arc<Mutex<TableLog>> = open(db, table)?;
+    let log: MutexGuard<'_, TableLog> = log.lock().unwrap();
+
+    Ok(range.keys(&log.keys))
+}
+
+/// Drops the in-memory log of a table, must be called when the table directory is removed.
+pub fn forget(db: &str, table: &str) {
+    TABLES.lock().unwrap().remove(&table_dir(db, table));
+}
+
+/// Drops the in-memory logs of all tables of a database.
+fn forget_db(db: &str) {
+    let prefix: String = format!("{}/", db_dir(db));
+    TABLES.lock().unwrap().retain(|dir, _| !dir.starts_with(&prefix));
+}
+
+/// Removes a table directory along with its in-memory log.
+///
+/// A running compaction is waited for, so it never writes into a table
+/// which is created again under the same name.
+///
+/// # Returns
+///
+/// Returns Ok(()) if the directory was removed; Err with an error message otherwise.
+pub fn delete_table(db: &str, table: &str) -> Result<(), String> {
+    let _compaction: MutexGuard<'_, ()> = COMPACTION.lock().unwrap();
+    let status: Result<(), String> = fs::remove_dir_all(table_dir(db, table)).map_err(|e| e.to_string());
+    forget(db, table);
+
+    status
+}
+
+/// Removes a database directory along with the in-memory logs of its tables, like [`delete_table`].
+pub fn delete_db(db: &str) -> Result<(), String> {
+    let _compaction: MutexGuard<'_, ()> = COMPACTION.lock().unwrap();
+    let status: Result<(), String> = fs::remove_dir_all(db_dir(db)).map_err(|e| e.to_string());
+    forget_db(db);
+
+    status
+}
+
+/// Checks if a table log is still the registered log of its directory.
+fn registered(log: &Arc<Mutex<TableLog>>) -> bool {
+    TABLES.lock().unwrap().values().any(|other| Arc::ptr_eq(other, log))
+}
+
+/// Moves a table or a database directory along with the in-memory logs under it.
+///
+/// The logs are locked during the move, so no reader or writer sees the
+/// directory half moved.
+///
+/// # Arguments
+///
+/// * `from` - Current directory.
+/// * `to` - New directory, must not exist.
+///
+/// # Returns
+///
+/// Returns Ok(()) if the directory was moved; Err with an error message otherwise.
+pub fn rename_dir(from: &str, to: &str) -> Result<(), String> {
+    let _compaction: MutexGuard<'_, ()> = COMPACTION.lock().unwrap();
+    let mut tables: MutexGuard<'_, HashMap<String, Arc<Mutex<TableLog>>>> = TABLES.lock().unwrap();
+
+    let prefix: String = format!("{}/", from);
+    let moved: Vec<String> = tables.keys()
+        .filter(|dir| *dir == from || dir.starts_with(&prefix))
+        .cloned()
+        .collect();
+    let logs: Vec<(String, Arc<Mutex<TableLog>>)> = moved.iter()
+        .filter_map(|dir| tables.get(dir).map(|log| (dir.clone(), log.clone())))
+        .collect();
+    let mut locked: Vec<(String, MutexGuard<'_, TableLog>)> = logs.iter()
+        .map(|(dir, log)| (dir.clone(), log.lock().unwrap()))
+        .collect();
+
+    fs::rename(from, to).map_err(|e| format!("[ ERROR ] Segment: Can not move {} - {}", from, e))?;
+
+    for (dir, log) in locked.iter_mut() {
+        log.dir = format!("{}{}", to, &dir[from.len()..]);
+    }
+    for dir in moved {
+        if let Some(log) = tables.remove(&dir) {
+            tables.insert(format!("{}{}", to, &dir[from.len()..]), log);
+        }
+    }
+
+    Ok(())
+}
+
+/// Compacts a table log right away.
+pub fn compact(db: &str, table: &str) -> Result<(), String> {
+    compact_log(&open(db, table)?)
+}
+
+/// Rewrites the live records of a table log into a fresh segment and removes the old segments.
+///
+/// The table lock is only held to start the compaction and to switch over to
+/// the new segment, reads and writes go on while the records are copied.
+/// A failed copy removes the new segment again.
+fn compact_log(log: &Arc<Mutex<TableLog>>) -> Result<(), String> {
+    let _compaction: MutexGuard<'_, ()> = COMPACTION.lock().unwrap();
+
+    // The table could be deleted since the log was picked
+    if !registered(log) {
+        return Ok(());
+    }
+
+    let compaction: Compaction = match log.lock().unwrap().begin_compaction()? {
+        Some(compaction) => compaction,
+        None => return Ok(())
+    };
+
+    match compaction.copy() {
+        Ok((keys, size)) if registered(log) => {
+            log.lock().unwrap().finish_compaction(compaction, keys, size);
+            Ok(())
+        },
+        Ok(_) => {
+            let _ = fs::remove_file(segment_path(&compaction.dir, compaction.id));
+            Ok(())
+        },
+        Err(err) => {
+            let _ = fs::remove_file(segment_path(&compaction.dir, compaction.id));
+            Err(err)
+        }
+    }
+}
+
+/// Opens every table of every database, rebuilding the key indexes.
+///
+/// # Returns
+///
+/// Returns true if all tables were loaded; false otherwise.
+pub fn load_all() -> bool {
+    let db_path: &str = &config::CONFIG.db_path;
+    let start: u128 = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+    let mut tables_count: usize = 0;
+
+    let dbs: ReadDir = match fs::read_dir(db_path) {
+        Ok(dir) => dir,
+        Err(_) => return false
+    };
+
+    for db in dbs.filter_map(|e| e.ok()).filter(|e| e.path().is_dir()) {
+        let db_name: String = match names::decode(&db.file_name().to_string_lossy()) {
+            Some(name) => name,
+            None => continue
+        };
+        let tables: ReadDir = match fs::read_dir(db.path()) {
+            Ok(dir) => dir,
+            Err(_) => continue
+        };
+
+        for table in tables.filter_map(|e| e.ok()).filter(|e| e.path().is_dir()) {
+            let table_name: String = match names::decode(&table.file_name().to_string_lossy()) {
+                Some(name) => name,
+                None => continue
+            };
+            if let Err(err) = open(&db_name, &table_name) {
+                println!("{} - {}/{}", err, db_name, table_name);
+                return false;
+            }
+            tables_count += 1;
+        }
+    }
+
+    let end: u128 = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+    println!("[ INFO ] Segment: loaded {} tables in {}ms", tables_count, end - start);
+
+    true
+}
+
+/// Starts the background compaction of table logs.
+pub fn start_compaction() {
+    thread::spawn(|| {
+        loop {
+            thread::sleep(time::Duration::from_secs(COMPACTION_INTERVAL));
+
+            let logs: Vec<Arc<Mutex<TableLog>>> = TABLES.lock().unwrap().values().cloned().collect();
+            for log in logs {
+                // Compact only when at least half of the log is garbage
+                let due: bool = {
+                    let log: MutexGuard<'_, TableLog> = log.lock().unwrap();
+                    log.dead_bytes >= COMPACTION_MIN_DEAD && log.dead_bytes * 2 >= log.total_bytes()
+                };
+
+                if due {
+                    if let Err(err) = compact_log(&log) {
+                        println!("{}", err);
+                    }
+                }
+            }
+        }
+    });
+}
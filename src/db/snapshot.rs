@@ -0,0 +1,402 @@
+//! Consistent snapshots of databases.
+//!
+//! A snapshot is a single file. The first line is the JSON manifest, every
+//! other line is one row as JSON, ordered by database, table and key. The
+//! manifest holds the row count and the CRC-32 of the row lines of every
+//! table, so an archive can be verified before it is restored.
+//!
+//! Mutating requests hold the write gate shared. A snapshot takes it
+//! exclusively only while the tables are captured, the captured rows are
+//! written out afterwards while writes go on, and the archive is one point in
+//! time of all captured databases.
+//!
+//! Archives live in the snapshot directory of the configuration, the paths
+//! given by clients are relative to it.
+
+use std::{collections::{BTreeMap, HashMap}, fs::{self, File}, io::{self, BufRead, BufReader, BufWriter, Write}, path::Path};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::{cache::cache_db, config, db::{self, checksum::Crc32, engine::{engine, CapturedRows}, meta, names, ttl}, protos::row::Row, types};
+
+/// Name of the archive format in the manifest.
+pub const FORMAT: &str = "rdsync-snapshot";
+
+/// Version of the archive format.
+pub const VERSION: u32 = 1;
+
+/// Suffix of the databases a restore writes into before they replace the live ones.
+const STAGING_SUFFIX: &str = "~restore";
+
+lazy_static! {
+    /// Gate between writers (shared) and snapshots (exclusive).
+    static ref WRITE_GATE: RwLock<()> = RwLock::new(());
+}
+
+/// Manifest of a snapshot archive.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Manifest {
+    /// Always [`FORMAT`].
+    pub format: String,
+
+    /// Version of the archive format.
+    pub version: u32,
+
+    /// Unix time (seconds) the snapshot was taken at.
+    pub created_at: u64,
+
+    /// Captured databases.
+    pub databases: Vec<DbManifest>
+}
+
+/// One database of a snapshot.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DbManifest {
+    /// Database name.
+    pub name: String,
+
+    /// Serialized database metadata.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<String>,
+
+    /// Captured tables.
+    pub tables: Vec<TableManifest>
+}
+
+/// One table of a snapshot.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TableManifest {
+    /// Table name.
+    pub name: String,
+
+    /// Number of rows.
+    pub rows: u64,
+
+    /// CRC-32 of the row lines, as hex.
+    pub checksum: String
+}
+
+/// One row line of a snapshot.
+#[derive(Serialize, Deserialize, Debug)]
+struct SnapshotRow {
+    db: String,
+    table: String,
+    key: String,
+    value: String,
+    #[serde(rename = "type")]
+    _type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<u64>
+}
+
+/// Holds off snapshots while a write is applied.
+///
+/// # Returns
+///
+/// Returns a guard which has to be kept until the write is done.
+pub fn write_gate() -> RwLockReadGuard<'static, ()> {
+    WRITE_GATE.read().unwrap()
+}
+
+/// Takes the write gate exclusively, for writes which must not run next to any other write.
+pub fn exclusive_gate() -> RwLockWriteGuard<'static, ()> {
+    WRITE_GATE.write().unwrap()
+}
+
+/// Writes a consistent snapshot of databases into one file.
+///
+/// # Arguments
+///
+/// * `dbs` - Names of the databases to capture.
+/// * `path` - Path of the archive inside the snapshot directory.
+///
+/// # Returns
+///
+/// Returns the manifest of the written archive, or an error message.
+pub fn create(dbs: &[String], path: &str) -> Result<Manifest, String> {
+    let path: String = archive_path(path)?;
+
+    if let Some(missing) = dbs.iter().find(|db| !engine().db_exists(db)) {
+        return Err(format!("database `{}` does not exist", missing));
+    }
+
+    if let Some(dir) = Path::new(&path).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|e| format!("[ ERROR ] Snapshot: Can not create {} - {}", dir.display(), e))?;
+    }
+
+    let rows_path: String = format!("{}.rows", path);
+    let mut manifest: Manifest = Manifest {
+        format: FORMAT.to_string(),
+        version: VERSION,
+        created_at: ttl::now(),
+        databases: Vec::with_capacity(dbs.len())
+    };
+
+    // Writes only wait while the tables are captured, not while the rows are written out
+    let mut captured: Vec<(DbManifest, Vec<(String, CapturedRows)>)> = Vec::with_capacity(dbs.len());
+    {
+        let _gate: RwLockWriteGuard<'_, ()> = WRITE_GATE.write().unwrap();
+
+        for db in dbs {
+            let mut tables: Vec<(String, CapturedRows)> = Vec::new();
+            for table in engine().list_tables(db)? {
+                let rows: CapturedRows = engine().capture_table(db, &table)?;
+                tables.push((table, rows));
+            }
+
+            captured.push((DbManifest { name: db.clone(), meta: engine().read_db_meta(db), tables: Vec::new() }, tables));
+        }
+    }
+
+    {
+        let mut rows: BufWriter<File> = BufWriter::new(create_file(&rows_path)?);
+
+        for (mut db_manifest, tables) in captured {
+            for (table, table_rows) in tables {
+                let mut crc: Crc32 = Crc32::new();
+                let mut count: u64 = 0;
+
+                for captured_row in table_rows {
+                    let (key, row): (String, Row) = captured_row?;
+
+                    let line: String = to_line(&SnapshotRow {
+                        db: db_manifest.name.clone(),
+                        table: table.clone(),
+                        expires_at: if row.has_expires_at() { Some(row.expires_at()) } else { None },
+                        value: types::row_value(&row),
+                        _type: row.type_().to_string(),
+                        key
+                    })?;
+
+                    crc.update(line.as_bytes());
+                    rows.write_all(line.as_bytes()).map_err(|e| format!("[ ERROR ] Snapshot: Can not write - {}", e))?;
+                    count += 1;
+                }
+
+                db_manifest.tables.push(TableManifest { name: table, rows: count, checksum: crc.hex() });
+            }
+
+            manifest.databases.push(db_manifest);
+        }
+
+        rows.flush().map_err(|e| format!("[ ERROR ] Snapshot: Can not write - {}", e))?;
+    }
+
+    // The manifest goes first, so the archive is assembled after the rows are captured
+    let tmp_path: String = format!("{}.tmp", path);
+    let mut archive: BufWriter<File> = BufWriter::new(create_file(&tmp_path)?);
+    let mut rows: File = File::open(&rows_path).map_err(|e| e.to_string())?;
+
+    archive.write_all(to_line(&manifest)?.as_bytes())
+        .and_then(|_| io::copy(&mut rows, &mut archive).map(|_| ()))
+        .and_then(|_| archive.flush())
+        .and_then(|_| archive.get_ref().sync_all())
+        .map_err(|e| format!("[ ERROR ] Snapshot: Can not write {} - {}", tmp_path, e))?;
+
+    fs::rename(&tmp_path, &path).map_err(|e| format!("[ ERROR ] Snapshot: Can not write {} - {}", path, e))?;
+    let _ = fs::remove_file(&rows_path);
+
+    println!("[ INFO ] Snapshot: wrote {} databases to {}", manifest.databases.len(), path);
+
+    Ok(manifest)
+}
+
+/// Reads an archive and checks its rows against the manifest.
+///
+/// # Arguments
+///
+/// * `path` - Path of the archive inside the snapshot directory.
+///
+/// # Returns
+///
+/// Returns the manifest if every table matches its row count and checksum; Err with the first mismatch otherwise.
+pub fn verify(path: &str) -> Result<Manifest, String> {
+    check(&archive_path(path)?)
+}
+
+/// Checks the rows of an archive at a resolved path against its manifest.
+fn check(path: &str) -> Result<Manifest, String> {
+    let mut lines = open(path)?.lines();
+    let manifest: Manifest = read_manifest(lines.next())?;
+
+    let mut found: HashMap<(String, String), (u64, Crc32)> = HashMap::new();
+    for line in lines {
+        let line: String = line.map_err(|e| e.to_string())? + "\n";
+        let row: SnapshotRow = serde_json::from_str(&line).map_err(|e| format!("broken row line - {}", e))?;
+
+        let (count, crc) = found.entry((row.db, row.table)).or_insert((0, Crc32::new()));
+        *count += 1;
+        crc.update(line.as_bytes());
+    }
+
+    for db in manifest.databases.iter() {
+        for table in db.tables.iter() {
+            let (count, crc): (u64, Crc32) = found.remove(&(db.name.clone(), table.name.clone())).unwrap_or((0, Crc32::new()));
+
+            if count != table.rows || (count > 0 && crc.hex() != table.checksum) {
+                return Err(format!("table {}/{} does not match the manifest", db.name, table.name));
+            }
+        }
+    }
+
+    if let Some(((db, table), _)) = found.into_iter().next() {
+        return Err(format!("table {}/{} is not in the manifest", db, table));
+    }
+
+    Ok(manifest)
+}
+
+/// Restores databases from a verified archive.
+///
+/// The rows are written into staging databases first, the live databases are
+/// only replaced once every row is in place, so a failed restore leaves them
+/// as they were.
+///
+/// # Arguments
+///
+/// * `path` - Path of the archive inside the snapshot directory.
+/// * `source` - Database of the archive to restore, all of them if None.
+/// * `target` - New name of the restored database, needs a single restored database.
+/// * `replace` - Replace existing databases instead of failing.
+///
+/// # Returns
+///
+/// Returns the names of the restored databases, or an error message.
+pub fn restore(path: &str, source: Option<&str>, target: Option<&str>, replace: bool) -> Result<Vec<String>, String> {
+    let path: String = archive_path(path)?;
+    let manifest: Manifest = check(&path)?;
+
+    let chosen: Vec<&DbManifest> = match source {
+        Some(name) => match manifest.databases.iter().find(|db| db.name == name) {
+            Some(db) => vec![db],
+            None => return Err(format!("database `{}` is not in the archive", name))
+        },
+        None => manifest.databases.iter().collect()
+    };
+
+    if target.is_some() && chosen.len() != 1 {
+        return Err("a target name needs a single database to restore".to_string());
+    }
+
+    let names: BTreeMap<String, String> = chosen.iter()
+        .map(|db| (db.name.clone(), target.unwrap_or(&db.name).to_string()))
+        .collect();
+
+    for name in names.values() {
+        names::check_name("database", &staging_name(name))?;
+
+        if !replace && engine().db_exists(name) {
+            return Err(format!("database `{}` already exists", name));
+        }
+    }
+
+    if let Err(err) = write_staging(&path, &chosen, &names) {
+        for name in names.values() {
+            let _ = cache_db::delete_db(&staging_name(name));
+        }
+        return Err(err);
+    }
+
+    for name in names.values() {
+        if engine().db_exists(name) {
+            cache_db::delete_db(name)?;
+        }
+        cache_db::rename_db(&staging_name(name), name)?;
+    }
+
+    println!("[ INFO ] Snapshot: restored {} databases from {}", names.len(), path);
+
+    Ok(names.into_values().collect())
+}
+
+/// Writes the chosen databases of an archive into their staging databases.
+///
+/// # Arguments
+///
+/// * `path` - Resolved path of the archive.
+/// * `chosen` - Databases of the archive to restore.
+/// * `names` - Restored name of every chosen database.
+fn write_staging(path: &str, chosen: &[&DbManifest], names: &BTreeMap<String, String>) -> Result<(), String> {
+    for db in chosen.iter() {
+        // Left over by a restore which did not finish
+        let staging: String = staging_name(&names[&db.name]);
+        if engine().db_exists(&staging) {
+            cache_db::delete_db(&staging)?;
+        }
+
+        engine().create_db(&staging);
+
+        if let Some(content) = &db.meta {
+            engine().write_db_meta(&staging, content)?;
+        }
+        meta::forget_db(&staging);
+
+        for table in db.tables.iter() {
+            engine().create_table(&staging, &table.name);
+        }
+    }
+
+    for line in open(path)?.lines().skip(1) {
+        let line: String = line.map_err(|e| e.to_string())?;
+        let snap: SnapshotRow = serde_json::from_str(&line).map_err(|e| format!("broken row line - {}", e))?;
+
+        let staging: String = match names.get(&snap.db) {
+            Some(name) => staging_name(name),
+            None => continue
+        };
+
+        let mut row: Row = Row::new();
+        types::set_row_value(&mut row, &snap.value, &snap._type)?;
+        if let Some(at) = snap.expires_at {
+            row.set_expires_at(at);
+        }
+
+        engine().write_row(&staging, &snap.table, &snap.key, &row)?;
+        db::on_put(&staging, &snap.table, &snap.key, &row, None);
+    }
+
+    Ok(())
+}
+
+/// Returns the name of the database a restore writes into before it replaces `name`.
+fn staging_name(name: &str) -> String {
+    format!("{}{}", name, STAGING_SUFFIX)
+}
+
+/// Resolves the path of an archive inside the snapshot directory.
+fn archive_path(path: &str) -> Result<String, String> {
+    names::confine_path(&config::CONFIG.snapshot_path, path)
+}
+
+/// Serializes a value as one line of the archive.
+fn to_line<T: Serialize>(value: &T) -> Result<String, String> {
+    serde_json::to_string(value).map(|line| line + "\n").map_err(|e| e.to_string())
+}
+
+/// Creates a file of the archive.
+fn create_file(path: &str) -> Result<File, String> {
+    File::create(path).map_err(|e| format!("[ ERROR ] Snapshot: Can not create {} - {}", path, e))
+}
+
+/// Opens an archive for reading.
+fn open(path: &str) -> Result<BufReader<File>, String> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|e| format!("can not open {} - {}", path, e))
+}
+
+/// Parses the first line of an archive.
+fn read_manifest(line: Option<io::Result<String>>) -> Result<Manifest, String> {
+    let line: String = match line {
+        Some(Ok(line)) => line,
+        _ => return Err("archive has no manifest".to_string())
+    };
+
+    let manifest: Manifest = serde_json::from_str(&line).map_err(|e| format!("broken manifest - {}", e))?;
+    if manifest.format != FORMAT || manifest.version != VERSION {
+        return Err("unsupported archive format".to_string());
+    }
+
+    Ok(manifest)
+}
@@ -0,0 +1,405 @@
+//! Statistics of tables and databases.
+//!
+//! The row count, the value bytes, the per-type breakdown and the time of the
+//! last write of a table are kept up to date by the write hooks
+//! ([`crate::db::on_put`] and [`crate::db::on_delete`]), which get the row a
+//! write replaces. They are saved in a side file of the table every second.
+//! The first change after a save marks the file as outdated, so the tables
+//! written right before a crash are counted from the storage once on the
+//! next start.
+
+use std::{collections::{BTreeMap, HashMap}, sync::{Mutex, MutexGuard}, thread, time};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::{cache, db::{engine::engine, ttl, Rename}, protos::row::Row, types};
+
+/// Side file of a table with its statistics.
+const STATS_FILE: &str = "stats.json";
+
+/// How often the changed statistics are saved, in milliseconds.
+const SAVE_INTERVAL: u64 = 1000;
+
+/// Number of rows and value bytes.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Counts {
+    /// Number of rows.
+    pub rows: u64,
+
+    /// Total size of the values in bytes.
+    pub bytes: u64
+}
+
+impl Counts {
+    /// Adds a row of `size` bytes.
+    fn add(&mut self, size: u64) {
+        self.rows += 1;
+        self.bytes += size;
+    }
+
+    /// Removes a row of `size` bytes.
+    fn remove(&mut self, size: u64) {
+        self.rows = self.rows.saturating_sub(1);
+        self.bytes = self.bytes.saturating_sub(size);
+    }
+
+    /// Adds up two counts.
+    fn merge(&mut self, other: &Counts) {
+        self.rows += other.rows;
+        self.bytes += other.bytes;
+    }
+}
+
+/// Counted rows of one table.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+struct TableCounts {
+    /// Counts of all rows.
+    total: Counts,
+
+    /// Counts by value type.
+    types: BTreeMap<String, Counts>,
+
+    /// Time of the last write, Unix time in seconds.
+    #[serde(default)]
+    last_write: Option<u64>
+}
+
+impl TableCounts {
+    /// Counts a written row.
+    fn add(&mut self, row: &Row) {
+        let size: u64 = types::row_size(row) as u64;
+
+        self.total.add(size);
+        self.types.entry(row.type_().to_string()).or_default().add(size);
+    }
+
+    /// Stops counting a replaced or deleted row.
+    fn remove(&mut self, row: &Row) {
+        let size: u64 = types::row_size(row) as u64;
+
+        self.total.remove(size);
+        if let Some(counts) = self.types.get_mut(row.type_()) {
+            counts.remove(size);
+            if counts.rows == 0 {
+                self.types.remove(row.type_());
+            }
+        }
+    }
+}
+
+/// Content of the side file of a table.
+#[derive(Serialize, Deserialize)]
+struct SavedStats {
+    /// Saved counts.
+    counts: TableCounts,
+
+    /// The counts include every write, false once the table was changed after the save.
+    clean: bool
+}
+
+/// Statistics kept for one table.
+struct TableEntry {
+    /// Current counts.
+    counts: TableCounts,
+
+    /// Changed since the last save.
+    dirty: bool
+}
+
+/// Statistics of a table.
+#[derive(Serialize, Debug, Clone)]
+pub struct TableStats {
+    /// Table name.
+    pub table: String,
+
+    /// Number of rows.
+    pub rows: u64,
+
+    /// Total size of the values in bytes.
+    pub bytes: u64,
+
+    /// Rows and bytes by value type.
+    pub types: BTreeMap<String, Counts>,
+
+    /// Creation time, Unix time in seconds.
+    pub created_at: Option<u64>,
+
+    /// Time of the last write, Unix time in seconds.
+    pub last_write_at: Option<u64>,
+
+    /// Number of rows held in the cache.
+    pub cached_rows: usize
+}
+
+/// Statistics of a database.
+#[derive(Serialize, Debug, Clone)]
+pub struct DbStats {
+    /// Database name.
+    pub db: String,
+
+    /// Number of rows of all tables.
+    pub rows: u64,
+
+    /// Total size of the values of all tables in bytes.
+    pub bytes: u64,
+
+    /// Rows and bytes by value type.
+    pub types: BTreeMap<String, Counts>,
+
+    /// Creation time, Unix time in seconds.
+    pub created_at: Option<u64>,
+
+    /// Time of the last write to any table, Unix time in seconds.
+    pub last_write_at: Option<u64>,
+
+    /// Number of rows held in the cache.
+    pub cached_rows: usize,
+
+    /// Statistics of every table.
+    pub tables: Vec<TableStats>
+}
+
+lazy_static! {
+    /// Statistics by database and table name.
+    static ref STATS: Mutex<HashMap<(String, String), TableEntry>> = Mutex::new(HashMap::new());
+
+    /// Held while a side file is written, so the files are written in the order their content was taken.
+    static ref SAVING: Mutex<()> = Mutex::new(());
+}
+
+/// Updates the statistics of a table after a row was written.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+/// * `table` - Table name.
+/// * `row` - Written row.
+/// * `previous` - Stored row replaced by the write, None if the key did not exist.
+pub fn on_put(db: &str, table: &str, row: &Row, previous: Option<&Row>) {
+    change(db, table, |counts| {
+        if let Some(previous) = previous {
+            counts.remove(previous);
+        }
+        counts.add(row);
+    });
+}
+
+/// Updates the statistics of a table after a row was deleted.
+///
+/// A deleted row which could not be read is only taken off the row count.
+pub fn on_delete(db: &str, table: &str, previous: Option<&Row>) {
+    change(db, table, |counts| match previous {
+        Some(previous) => counts.remove(previous),
+        None => counts.total.rows = counts.total.rows.saturating_sub(1)
+    });
+}
+
+/// Moves the statistics of renamed tables to the new names.
+pub fn on_rename(rename: &Rename) {
+    rename.rekey(&mut STATS.lock().unwrap());
+}
+
+/// Drops the statistics of a deleted table.
+pub fn forget_table(db: &str, table: &str) {
+    STATS.lock().unwrap().remove(&(db.to_string(), table.to_string()));
+}
+
+/// Drops the statistics of a deleted database.
+pub fn forget_db(db: &str) {
+    STATS.lock().unwrap().retain(|(stats_db, _), _| stats_db != db);
+}
+
+/// Returns the statistics of a table.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+/// * `table` - Table name.
+///
+/// # Returns
+///
+/// Returns the statistics, or an error message if there is no such table.
+pub fn table_stats(db: &str, table: &str) -> Result<TableStats, String> {
+    if !engine().table_exists(db, table) {
+        return Err("no such table".to_string());
+    }
+
+    // Read before the statistics are locked, the cache is not locked under them
+    let cached_rows: usize = cache::cached_rows(db, Some(table));
+    let counts: TableCounts = entry(db, table, |entry| entry.counts.clone());
+
+    Ok(TableStats {
+        table: table.to_string(),
+        rows: counts.total.rows,
+        bytes: counts.total.bytes,
+        types: counts.types,
+        created_at: engine().created_at(db, Some(table)),
+        last_write_at: counts.last_write,
+        cached_rows
+    })
+}
+
+/// Returns the statistics of a database along with the ones of its tables.
+///
+/// # Returns
+///
+/// Returns the statistics, or an error message if there is no such database.
+pub fn db_stats(db: &str) -> Result<DbStats, String> {
+    if !engine().db_exists(db) {
+        return Err("no such database".to_string());
+    }
+
+    let mut stats: DbStats = DbStats {
+        db: db.to_string(),
+        rows: 0,
+        bytes: 0,
+        types: BTreeMap::new(),
+        created_at: engine().created_at(db, None),
+        last_write_at: None,
+        cached_rows: cache::cached_rows(db, None),
+        tables: Vec::new()
+    };
+
+    for table in engine().list_tables(db)? {
+        let table_stats: TableStats = table_stats(db, &table)?;
+
+        stats.rows += table_stats.rows;
+        stats.bytes += table_stats.bytes;
+        for (_type, counts) in table_stats.types.iter() {
+            stats.types.entry(_type.clone()).or_default().merge(counts);
+        }
+        stats.last_write_at = stats.last_write_at.max(table_stats.last_write_at);
+        stats.tables.push(table_stats);
+    }
+
+    Ok(stats)
+}
+
+/// Counts again the tables whose saved statistics are missing or outdated and starts saving the changes.
+///
+/// Runs on startup before any request is served.
+pub fn start() {
+    let mut counted: usize = 0;
+
+    for db in engine().list_dbs().unwrap_or_default() {
+        for table in engine().list_tables(&db).unwrap_or_default() {
+            let saved: Option<SavedStats> = read(&db, &table);
+            if saved.as_ref().is_some_and(|saved| saved.clean) {
+                continue;
+            }
+
+            match count(&db, &table) {
+                Ok(mut counts) => {
+                    counts.last_write = saved.and_then(|saved| saved.counts.last_write);
+                    STATS.lock().unwrap().insert((db.clone(), table.clone()), TableEntry { counts, dirty: true });
+                    save(&db, &table, true);
+                    counted += 1;
+                },
+                Err(err) => println!("[ ERROR ] Stats: Can't count {}/{} - {}", db, table, err)
+            }
+        }
+    }
+
+    println!("[ INFO ] Stats: counted {} tables", counted);
+
+    thread::spawn(|| {
+        loop {
+            thread::sleep(time::Duration::from_millis(SAVE_INTERVAL));
+            save_all();
+        }
+    });
+}
+
+/// Saves the statistics of all changed tables.
+pub fn save_all() {
+    let dirty: Vec<(String, String)> = STATS.lock().unwrap().iter()
+        .filter(|(_, entry)| entry.dirty)
+        .map(|(name, _)| name.clone())
+        .collect();
+
+    for (db, table) in dirty {
+        save(&db, &table, true);
+    }
+}
+
+/// Applies a change to the counts of a table and marks the saved file as outdated on the first change after a save.
+fn change<F>(db: &str, table: &str, f: F)
+where
+    F: FnOnce(&mut TableCounts)
+{
+    let first: bool = entry(db, table, |entry| {
+        f(&mut entry.counts);
+        entry.counts.last_write = Some(ttl::now());
+
+        let first: bool = !entry.dirty;
+        entry.dirty = true;
+        first
+    });
+
+    if first {
+        save(db, table, false);
+    }
+}
+
+/// Runs `f` on the locked statistics of a table, loading them from the side file first if needed.
+///
+/// A table without a side file is new and starts with no rows.
+fn entry<T, F>(db: &str, table: &str, f: F) -> T
+where
+    F: FnOnce(&mut TableEntry) -> T
+{
+    let name: (String, String) = (db.to_string(), table.to_string());
+    if !STATS.lock().unwrap().contains_key(&name) {
+        // Read before the statistics are locked
+        let counts: TableCounts = read(db, table).map(|saved| saved.counts).unwrap_or_default();
+        STATS.lock().unwrap().entry(name.clone()).or_insert(TableEntry { counts, dirty: false });
+    }
+
+    let mut stats: MutexGuard<'_, HashMap<(String, String), TableEntry>> = STATS.lock().unwrap();
+    f(stats.get_mut(&name).unwrap())
+}
+
+/// Writes the statistics of a table to its side file.
+///
+/// A clean save takes the changes made so far and clears the dirty flag, an
+/// outdated one only marks the file, and is skipped if a clean save already
+/// took the change.
+fn save(db: &str, table: &str, clean: bool) {
+    let _saving: MutexGuard<'_, ()> = SAVING.lock().unwrap();
+
+    let content: String = {
+        let mut stats: MutexGuard<'_, HashMap<(String, String), TableEntry>> = STATS.lock().unwrap();
+        let entry: &mut TableEntry = match stats.get_mut(&(db.to_string(), table.to_string())) {
+            Some(entry) if entry.dirty => entry,
+            _ => return
+        };
+
+        if clean {
+            entry.dirty = false;
+        }
+        serde_json::to_string(&SavedStats { counts: entry.counts.clone(), clean }).unwrap()
+    };
+
+    if let Err(err) = engine().write_table_file(db, table, STATS_FILE, &content) {
+        // The file is gone with a deleted table
+        if engine().table_exists(db, table) {
+            println!("[ ERROR ] Stats: Can't save {}/{} - {}", db, table, err);
+        }
+    }
+}
+
+/// Reads the side file of a table.
+fn read(db: &str, table: &str) -> Option<SavedStats> {
+    serde_json::from_str(&engine().read_table_file(db, table, STATS_FILE)?).ok()
+}
+
+/// Counts the rows of a table from the storage.
+fn count(db: &str, table: &str) -> Result<TableCounts, String> {
+    let mut counts: TableCounts = TableCounts::default();
+
+    for (_, row) in engine().read_table(db, table)? {
+        counts.add(&row);
+    }
+
+    Ok(counts)
+}
@@ -1,6 +1,4 @@
-use std::{io::Error, fs::{self, remove_dir_all, ReadDir}};
-
-use crate::{db, config, http::row_methods::Bunch};
+use crate::{db::engine::engine, http::row_methods::Bunch, protos::row::Row, types};
 
 /// Retrieves a list of keys representing rows in a specified database table.
 ///
@@ -13,25 +11,11 @@ use crate::{db, config, http::row_methods::Bunch};
 ///
 /// Returns a Result containing a vector of row keys if successful, or an error message if unsuccessful.
 pub fn get_table(db: &str, name: &str) -> Result<Vec<String>, String> {
-    let db_path: &str = &config::CONFIG.db_path;
-
-    let data: Result<ReadDir, Error> = fs::read_dir(format!("{}/{}/{}", db_path, db, name));
-
-    match data {
-        Ok(dir) => {
-            let rows: Vec<String> = dir
-            .filter_map(|entry| {
-                entry
-                    .ok()
-                    .and_then(|e| e.file_name().to_str().map(String::from))
-                    .map(|s| s[..s.len() - 3].to_string()) // Remove the last 3 characters
-            })
-            .collect();
-
-            Ok(rows)
-        },
-        Err(err) => return Err(err.to_string())
+    if !is_table_exist(db, name) {
+        return Err("{\"code\": 400, \"message\": \"no such table\"}\njson".to_string());
     }
+
+    engine().list_rows(db, name)
 }
 
 /// Retrieves data for all rows in a specified database table along with their keys.
@@ -45,41 +29,24 @@ pub fn get_table(db: &str, name: &str) -> Result<Vec<String>, String> {
 ///
 /// Returns a Result containing a vector of Bunch (key-value pairs) if successful, or an error message if unsuccessful.
 pub fn get_table_with_keys(db: &str, name: &str) -> Result<Vec<Bunch>, String> {
-    let db_path: &str = &config::CONFIG.db_path;
-
-    let data: Result<ReadDir, Error> = fs::read_dir(format!("{}/{}/{}", db_path, db, name));
-
-    match data {
-        Ok(dir) => {
-            let rows: Vec<String> = dir
-            .filter_map(|entry| {
-                entry
-                    .ok()
-                    .and_then(|e| e.file_name().to_str().map(String::from))
-                    .map(|s| s[..s.len() - 3].to_string()) // Remove the last 3 characters
-            })
-            .collect();
+    if !is_table_exist(db, name) {
+        return Err("{\"code\": 400, \"message\": \"no such table\"}\njson".to_string());
+    }
 
-            let mut row_data: Vec<Bunch> = Vec::with_capacity(rows.len());
+    let rows: Vec<(String, Row)> = match engine().read_table(db, name) {
+        Ok(rows) => rows,
+        Err(_) => return Err("{\"code\": 400, \"message\": \"no such table\"}\njson".to_string())
+    };
 
-            for r in rows.iter() {
-                match db::row::read_row(db, name, r) {
-                    Ok(data) => {
-                        let one: Bunch = Bunch {
-                            key: r.to_string(),
-                            value: data.value().into(),
-                            _type: data.type_().to_string(),
-                        };
-                        row_data.push(one)
-                    },
-                    Err(_) => {}
-                }
-            }
+    let row_data: Vec<Bunch> = rows.into_iter()
+        .map(|(key, data)| Bunch {
+            key,
+            value: types::row_value(&data).into(),
+            _type: data.type_().to_string(),
+        })
+        .collect();
 
-            Ok(row_data)
-        },
-        Err(_) => return Err("{\"code\": 400, \"message\": \"no such table\"}\njson".to_string())
-    }
+    Ok(row_data)
 }
 
 /// Creates a new table within a specified database.
@@ -93,13 +60,7 @@ pub fn get_table_with_keys(db: &str, name: &str) -> Result<Vec<Bunch>, String> {
 ///
 /// Returns true if the table creation is successful; false otherwise.
 pub fn create_table(db: &str, name: &str) -> bool {
-    let db_path: &str = &config::CONFIG.db_path;
-
-    if !db::is_db_exist(db) {
-        db::create_db(db);
-    }
-
-    return db::init_dir(&format!("{}/{}/{}", db_path, db, name));
+    engine().create_table(db, name)
 }
 
 /// Deletes a specified table from a database.
@@ -113,17 +74,7 @@ pub fn create_table(db: &str, name: &str) -> bool {
 ///
 /// Returns true if the table deletion is successful; false otherwise.
 pub fn delete_table(db: &str, name: &str) -> bool {
-    let db_path: &str = &config::CONFIG.db_path;
-
-    if !db::is_db_exist(db) {
-        db::create_db(db);
-    }
-
-    let status: Result<(), Error> = remove_dir_all(&format!("{}/{}/{}", db_path, db, name));
-    match status {
-        Ok(_) => return true,
-        Err(_) => return false,
-    }
+    engine().delete_table(db, name)
 }
 
 /// Checks if a specified table exists within a database.
@@ -137,5 +88,5 @@ pub fn delete_table(db: &str, name: &str) -> bool {
 ///
 /// Returns true if the table exists; false otherwise.
 pub fn is_table_exist(db: &str, name: &str) -> bool {
-    return db::is_dir_exist(&format!("{}/{}", db, name));
+    engine().table_exists(db, name)
 }
\ No newline at end of file
@@ -0,0 +1,515 @@
+//! Export and import of tables as NDJSON or CSV.
+//!
+//! NDJSON holds one `{"key", "value", "type"}` object per line. The value of a
+//! `json` row is written as JSON, all other values as strings.
+//!
+//! CSV is meant for tables of flat `json` rows: one column holds the row key,
+//! the other columns hold the top level fields. The `columns` spec maps CSV
+//! columns to fields as `column:field` pairs separated by commas; a column
+//! without a field keeps its name.
+//!
+//! An export flushes its output every [`EXPORT_BATCH`] rows, so a streamed
+//! export goes out in parts. An import logs every row it writes as its
+//! `put_row`, the input itself is never kept in the write-ahead log.
+
+use std::{collections::BTreeSet, io::{BufRead, Write}, ops::Bound};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{cache::{self, PutMode}, db::{engine::{engine, KeyRange}, schema, ttl, wal}, http::receiver::RequestHeaders, protos::row::Row, types};
+
+/// Number of keys read at once while exporting, and of rows written between two flushes of the output.
+const EXPORT_BATCH: usize = 512;
+
+/// Number of lines parsed before they are written while importing.
+const IMPORT_BATCH: usize = 1000;
+
+/// Max number of line errors listed in an import report.
+const MAX_REPORTED_ERRORS: usize = 100;
+
+/// Format of exported and imported data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// One JSON object per line.
+    Ndjson,
+
+    /// Comma separated values with a header line.
+    Csv
+}
+
+impl Format {
+    /// Parses a format name: `ndjson` or `csv`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "ndjson" | "jsonl" => Some(Format::Ndjson),
+            "csv" => Some(Format::Csv),
+            _ => None
+        }
+    }
+
+    /// Returns the name of the format.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Format::Ndjson => "ndjson",
+            Format::Csv => "csv"
+        }
+    }
+}
+
+/// Mapping between CSV columns and the fields of json rows.
+#[derive(Debug, Clone)]
+pub struct CsvColumns {
+    /// Column which holds the row key.
+    pub key_column: String,
+
+    /// Mapped columns as (column, field), in CSV order.
+    pub columns: Vec<(String, String)>
+}
+
+impl CsvColumns {
+    /// Builds the mapping from the key column name (default `key`) and a `column:field,...` spec.
+    pub fn parse(key_column: Option<&str>, spec: Option<&str>) -> Self {
+        let columns: Vec<(String, String)> = spec.unwrap_or_default()
+            .split(',')
+            .map(|pair| pair.trim())
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| match pair.split_once(':') {
+                Some((column, field)) => (column.trim().to_string(), field.trim().to_string()),
+                None => (pair.to_string(), pair.to_string())
+            })
+            .collect();
+
+        Self { key_column: key_column.unwrap_or("key").to_string(), columns }
+    }
+
+    /// Returns the field a CSV column is mapped to.
+    fn field<'a>(&'a self, column: &'a str) -> &'a str {
+        self.columns.iter()
+            .find(|(c, _)| c == column)
+            .map(|(_, field)| field.as_str())
+            .unwrap_or(column)
+    }
+}
+
+/// One line of NDJSON.
+#[derive(Serialize, Deserialize, Debug)]
+struct Record {
+    key: String,
+    value: Value,
+    #[serde(rename = "type", alias = "_type", default)]
+    _type: Option<String>
+}
+
+/// Result of an export.
+#[derive(Serialize, Debug, Default)]
+pub struct ExportReport {
+    /// Number of written rows.
+    pub exported: usize,
+
+    /// Number of rows which can not be written in the format.
+    pub skipped: usize
+}
+
+/// Error of one imported line.
+#[derive(Serialize, Debug)]
+pub struct LineError {
+    /// Line number, starting at 1.
+    pub line: usize,
+
+    /// What went wrong.
+    pub message: String
+}
+
+/// Result of an import.
+#[derive(Serialize, Debug, Default)]
+pub struct ImportReport {
+    /// Number of written rows.
+    pub imported: usize,
+
+    /// Number of lines which were not imported.
+    pub failed: usize,
+
+    /// Errors of the first failed lines.
+    pub errors: Vec<LineError>
+}
+
+impl ImportReport {
+    /// Records a failed line.
+    fn fail(&mut self, line: usize, message: String) {
+        self.failed += 1;
+        if self.errors.len() < MAX_REPORTED_ERRORS {
+            self.errors.push(LineError { line, message });
+        }
+    }
+}
+
+/// Streams all live rows of a table.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+/// * `table` - Table name.
+/// * `format` - Output format.
+/// * `columns` - CSV column mapping, all top level fields are written if it has no columns.
+/// * `out` - Output stream.
+///
+/// # Returns
+///
+/// Returns the number of exported and skipped rows, or an error message.
+pub fn export<W: Write>(db: &str, table: &str, format: Format, columns: &CsvColumns, out: &mut W) -> Result<ExportReport, String> {
+    if !engine().table_exists(db, table) {
+        return Err("no such table".to_string());
+    }
+
+    let mut report: ExportReport = ExportReport::default();
+    let write_err = |e: std::io::Error| format!("can not write the export - {}", e);
+
+    let mut csv_columns: CsvColumns = columns.clone();
+    if format == Format::Csv {
+        if csv_columns.columns.is_empty() {
+            csv_columns.columns = top_level_fields(db, table)?.into_iter().map(|f| (f.clone(), f)).collect();
+        }
+
+        let header: Vec<&str> = std::iter::once(csv_columns.key_column.as_str())
+            .chain(csv_columns.columns.iter().map(|(column, _)| column.as_str()))
+            .collect();
+        write_csv_record(out, &header).map_err(write_err)?;
+    }
+
+    for_each_row(db, table, |key, row| {
+        match format {
+            Format::Ndjson => {
+                let value: Value = match row.type_() {
+                    "json" => serde_json::from_str(row.value()).unwrap_or_else(|_| Value::String(row.value().to_string())),
+                    "list" | "set" | "hash" => serde_json::from_str(&types::row_value(row)).map_err(|e| e.to_string())?,
+                    _ => Value::String(types::row_value(row))
+                };
+
+                let record: Record = Record { key: key.to_string(), value, _type: Some(row.type_().to_string()) };
+                let line: String = serde_json::to_string(&record).map_err(|e| e.to_string())?;
+                writeln!(out, "{}", line).map_err(write_err)?;
+            },
+            Format::Csv => {
+                let obj: Map<String, Value> = match serde_json::from_str::<Value>(row.value()) {
+                    Ok(Value::Object(obj)) if row.type_() == "json" => obj,
+                    _ => {
+                        report.skipped += 1;
+                        return Ok(());
+                    }
+                };
+
+                let cells: Vec<String> = std::iter::once(key.to_string())
+                    .chain(csv_columns.columns.iter().map(|(_, field)| match obj.get(field) {
+                        None | Some(Value::Null) => String::new(),
+                        Some(Value::String(s)) => s.clone(),
+                        Some(other) => other.to_string()
+                    }))
+                    .collect();
+                let cells: Vec<&str> = cells.iter().map(|c| c.as_str()).collect();
+                write_csv_record(out, &cells).map_err(write_err)?;
+            }
+        }
+
+        report.exported += 1;
+        if report.exported.is_multiple_of(EXPORT_BATCH) {
+            out.flush().map_err(write_err)?;
+        }
+        Ok(())
+    })?;
+
+    out.flush().map_err(write_err)?;
+
+    Ok(report)
+}
+
+/// Reads rows from a stream and writes them to a table in batches.
+///
+/// Lines which can not be parsed, fail the type check or the write mode are
+/// reported and skipped, the rest of the input is still imported.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+/// * `table` - Table name.
+/// * `format` - Input format.
+/// * `columns` - CSV column mapping.
+/// * `mode` - Write mode of every row.
+/// * `input` - Input stream.
+///
+/// # Returns
+///
+/// Returns the import report, or an error message if the input can not be read.
+pub fn import<R: BufRead>(db: &str, table: &str, format: Format, columns: &CsvColumns, mode: PutMode, mut input: R) -> Result<ImportReport, String> {
+    let mut report: ImportReport = ImportReport::default();
+    let mut batch: Vec<(usize, String, Row)> = Vec::with_capacity(IMPORT_BATCH);
+    let mut line_no: usize = 0;
+
+    let header: Vec<String> = match format {
+        Format::Csv => match read_csv_record(&mut input, &mut line_no)? {
+            Some(header) => header,
+            None => return Ok(report)
+        },
+        Format::Ndjson => Vec::new()
+    };
+
+    let key_index: Option<usize> = header.iter().position(|column| *column == columns.key_column);
+    if format == Format::Csv && key_index.is_none() {
+        return Err(format!("key column `{}` is not in the header", columns.key_column));
+    }
+
+    loop {
+        let start: usize = line_no + 1;
+        let parsed: Option<Result<(String, Row), String>> = match format {
+            Format::Ndjson => {
+                let mut bytes: Vec<u8> = Vec::new();
+                let read: usize = input.read_until(b'\n', &mut bytes).map_err(|e| e.to_string())?;
+                line_no += 1;
+
+                if read == 0 {
+                    None
+                } else {
+                    match String::from_utf8(bytes) {
+                        Ok(line) if line.trim().is_empty() => continue,
+                        Ok(line) => Some(parse_ndjson(&line)),
+                        Err(_) => Some(Err("line is not valid UTF-8".to_string()))
+                    }
+                }
+            },
+            Format::Csv => match read_csv_record(&mut input, &mut line_no) {
+                Ok(Some(cells)) => Some(parse_csv(&header, key_index.unwrap_or(0), columns, cells)),
+                Ok(None) => None,
+                Err(err) => Some(Err(err))
+            }
+        };
+
+        match parsed {
+            Some(Ok((key, row))) => batch.push((start, key, row)),
+            Some(Err(err)) => report.fail(start, err),
+            None => break
+        }
+
+        if batch.len() >= IMPORT_BATCH {
+            write_batch(db, table, mode, &mut batch, &mut report);
+        }
+    }
+
+    write_batch(db, table, mode, &mut batch, &mut report);
+    println!("[ INFO ] Import: {} rows into {}/{}, {} failed", report.imported, db, table, report.failed);
+
+    Ok(report)
+}
+
+/// Writes the collected rows and empties the batch.
+fn write_batch(db: &str, table: &str, mode: PutMode, batch: &mut Vec<(usize, String, Row)>, report: &mut ImportReport) {
+    for (line, key, row) in batch.drain(..) {
+        let errors: Vec<schema::Violation> = schema::violations(db, table, &row);
+        if !errors.is_empty() {
+            report.fail(line, schema::describe(&errors));
+            continue;
+        }
+
+        match put_row(db, table, &key, &row, mode) {
+            Ok(true) => report.imported += 1,
+            Ok(false) if mode == PutMode::Insert => report.fail(line, "key already exists".to_string()),
+            Ok(false) => report.fail(line, "key does not exist".to_string()),
+            Err(err) => report.fail(line, err)
+        }
+    }
+}
+
+/// Writes one imported row, logged as its `put_row`.
+fn put_row(db: &str, table: &str, key: &str, row: &Row, mode: PutMode) -> Result<bool, String> {
+    let mut logged: Option<u64> = None;
+
+    if engine().is_persistent() {
+        let head: RequestHeaders = RequestHeaders {
+            db: db.to_string(),
+            table: table.to_string(),
+            key: key.to_string(),
+            _type: row.type_().to_string(),
+            params: [("mode".to_string(), mode.as_str().to_string())].into_iter().collect(),
+            ..Default::default()
+        };
+
+        logged = Some(wal::begin("put_row", &head, &types::row_value(row)).map_err(|err| {
+            println!("{}", err);
+            "can not write to WAL".to_string()
+        })?);
+    }
+
+    let result: Result<bool, String> = cache::put(db, table, key, row, mode);
+    if let Some(id) = logged {
+        wal::commit(id);
+    }
+
+    result
+}
+
+/// Parses one NDJSON line into a row.
+fn parse_ndjson(line: &str) -> Result<(String, Row), String> {
+    let record: Record = serde_json::from_str(line).map_err(|e| e.to_string())?;
+
+    let value: String = match record.value {
+        Value::String(s) => s,
+        other => other.to_string()
+    };
+    let _type: String = record._type.ok_or("missing type")?;
+
+    new_row(record.key, value, _type)
+}
+
+/// Turns one CSV record into a json row.
+fn parse_csv(header: &[String], key_index: usize, columns: &CsvColumns, cells: Vec<String>) -> Result<(String, Row), String> {
+    if cells.len() != header.len() {
+        return Err(format!("expected {} columns, found {}", header.len(), cells.len()));
+    }
+
+    let mut obj: Map<String, Value> = Map::new();
+    let mut key: String = String::new();
+
+    for (i, cell) in cells.into_iter().enumerate() {
+        if i == key_index {
+            key = cell;
+        } else if !cell.is_empty() {
+            obj.insert(columns.field(&header[i]).to_string(), cell_value(cell));
+        }
+    }
+
+    if key.is_empty() {
+        return Err("empty key".to_string());
+    }
+
+    new_row(key, Value::Object(obj).to_string(), "json".to_string())
+}
+
+/// Builds a checked row.
+fn new_row(key: String, value: String, _type: String) -> Result<(String, Row), String> {
+    types::is_valid_data(&value, &_type)?;
+
+    let mut row: Row = Row::new();
+    types::set_row_value(&mut row, &value, &_type)?;
+
+    Ok((key, row))
+}
+
+/// Reads a CSV cell as a number or a boolean if it is written like one, as a string otherwise.
+fn cell_value(cell: String) -> Value {
+    match cell.as_str() {
+        "true" => return Value::Bool(true),
+        "false" => return Value::Bool(false),
+        _ => {}
+    }
+
+    // Only take numbers which are written back the same way, `007` stays a string
+    match serde_json::from_str::<Value>(&cell) {
+        Ok(Value::Number(n)) if n.to_string() == cell => Value::Number(n),
+        _ => Value::String(cell)
+    }
+}
+
+/// Calls `f` for every live row of a table in key order.
+fn for_each_row<F>(db: &str, table: &str, mut f: F) -> Result<(), String>
+where
+    F: FnMut(&str, &Row) -> Result<(), String>
+{
+    let mut range: KeyRange = KeyRange { limit: EXPORT_BATCH, ..KeyRange::all() };
+
+    loop {
+        let keys: Vec<String> = engine().scan_rows(db, table, &range)?;
+
+        for key in keys.iter() {
+            match engine().read_row(db, table, key)? {
+                Some(row) if !ttl::is_expired(&row) => f(key, &row)?,
+                _ => {}
+            }
+        }
+
+        match keys.last() {
+            Some(last) if keys.len() == EXPORT_BATCH => range.start = Bound::Excluded(last.to_string()),
+            _ => return Ok(())
+        }
+    }
+}
+
+/// Collects the top level fields of the json rows of a table.
+fn top_level_fields(db: &str, table: &str) -> Result<Vec<String>, String> {
+    let mut fields: BTreeSet<String> = BTreeSet::new();
+
+    for_each_row(db, table, |_, row| {
+        if let Ok(Value::Object(obj)) = serde_json::from_str::<Value>(row.value()) {
+            fields.extend(obj.keys().cloned());
+        }
+        Ok(())
+    })?;
+
+    Ok(fields.into_iter().collect())
+}
+
+/// Writes one CSV record, quoting the cells which need it.
+fn write_csv_record<W: Write>(out: &mut W, cells: &[&str]) -> std::io::Result<()> {
+    let line: Vec<String> = cells.iter()
+        .map(|cell| {
+            if cell.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", cell.replace('"', "\"\""))
+            } else {
+                cell.to_string()
+            }
+        })
+        .collect();
+
+    writeln!(out, "{}", line.join(","))
+}
+
+/// Reads one CSV record, which spans several lines if a quoted cell contains line breaks.
+///
+/// # Returns
+///
+/// Returns the cells of the record, or None at the end of the input.
+fn read_csv_record<R: BufRead>(input: &mut R, line_no: &mut usize) -> Result<Option<Vec<String>>, String> {
+    let mut cells: Vec<String> = Vec::new();
+    let mut cell: String = String::new();
+    let mut quoted: bool = false;
+    let mut started: bool = false;
+
+    loop {
+        let mut bytes: Vec<u8> = Vec::new();
+        if input.read_until(b'\n', &mut bytes).map_err(|e| e.to_string())? == 0 {
+            if quoted {
+                return Err("unterminated quoted cell".to_string());
+            }
+            if !started {
+                return Ok(None);
+            }
+            break;
+        }
+        *line_no += 1;
+        let line: String = String::from_utf8(bytes).map_err(|_| "line is not valid UTF-8".to_string())?;
+
+        if !started && line.trim().is_empty() {
+            continue;
+        }
+        started = true;
+
+        let mut chars = line.trim_end_matches(['\n', '\r']).chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, quoted) {
+                ('"', true) if chars.peek() == Some(&'"') => {
+                    cell.push('"');
+                    chars.next();
+                },
+                ('"', true) => quoted = false,
+                ('"', false) if cell.is_empty() => quoted = true,
+                (',', false) => cells.push(std::mem::take(&mut cell)),
+                (c, _) => cell.push(c)
+            }
+        }
+
+        if !quoted {
+            break;
+        }
+        cell.push('\n');
+    }
+
+    cells.push(cell);
+    Ok(Some(cells))
+}
@@ -0,0 +1,340 @@
+//! Expiry of rows with a time to live.
+//!
+//! A row written with a `ttl` or `expires_at` header stores the unix time
+//! (seconds) it expires at. Expired rows are hidden from reads right away and
+//! deleted by the reaper thread. The deadlines of all expiring rows are kept
+//! in memory and every change of them is appended to a side file of the
+//! table, which is read back when the reaper starts. The file is not flushed
+//! on its own, so a power loss can drop the deadlines of the last writes: such
+//! rows are still hidden once expired, but stay stored until they are written
+//! again.
+
+use std::{collections::{BTreeSet, HashMap}, ops::Bound, thread, time::{self, SystemTime, UNIX_EPOCH}};
+use std::sync::{Mutex, MutexGuard, RwLockReadGuard};
+use lazy_static::lazy_static;
+
+use crate::{cache, db::{engine::{engine, KeyRange}, names, snapshot, Rename}, http::receiver::RequestHeaders, protos::row::Row};
+
+/// How often the reaper looks for expired rows, in milliseconds.
+const REAP_INTERVAL: u64 = 1000;
+
+/// Side file of a table with the deadlines of its rows, one `{expires_at} {key}` line per change.
+const DEADLINES_FILE: &str = "deadlines.log";
+
+/// Number of outdated lines after which a deadlines file is rewritten.
+const DEADLINES_SLACK: usize = 1024;
+
+/// Deadlines of the expiring rows.
+#[derive(Default)]
+struct Deadlines {
+    /// Rows ordered by the time they expire at: (expires_at, db, table, key).
+    by_time: BTreeSet<(u64, String, String, String)>,
+
+    /// Expiry time by row key, grouped by database and table name.
+    tables: HashMap<(String, String), HashMap<String, u64>>,
+
+    /// Number of lines in the deadlines file by database and table name.
+    logged: HashMap<(String, String), usize>
+}
+
+impl Deadlines {
+    /// Sets or clears the deadline of a row.
+    ///
+    /// Returns true if the deadline changed.
+    fn set(&mut self, db: &str, table: &str, key: &str, expires_at: Option<u64>) -> bool {
+        let table_id: (String, String) = (db.to_string(), table.to_string());
+        let keys: &mut HashMap<String, u64> = self.tables.entry(table_id.clone()).or_default();
+
+        let old: Option<u64> = keys.remove(key);
+        if let Some(old) = old {
+            self.by_time.remove(&(old, db.to_string(), table.to_string(), key.to_string()));
+        }
+
+        if let Some(at) = expires_at {
+            keys.insert(key.to_string(), at);
+            self.by_time.insert((at, db.to_string(), table.to_string(), key.to_string()));
+        } else if keys.is_empty() {
+            self.tables.remove(&table_id);
+        }
+
+        old != expires_at
+    }
+
+    /// Sets or clears the deadline of a row and appends the change to the deadlines file.
+    fn log(&mut self, db: &str, table: &str, key: &str, expires_at: Option<u64>) {
+        if !self.set(db, table, key, expires_at) {
+            return;
+        }
+
+        let line: String = format!("{} {}\n", expires_at.unwrap_or(0), names::encode(key));
+        if let Err(err) = engine().append_table_file(db, table, DEADLINES_FILE, &line) {
+            // The file is gone with a deleted table
+            if engine().table_exists(db, table) {
+                println!("[ ERROR ] TTL: Can't persist the deadline of {}/{}/{} - {}", db, table, key, err);
+            }
+            return;
+        }
+
+        let table_id: (String, String) = (db.to_string(), table.to_string());
+        let live: usize = self.tables.get(&table_id).map(|keys| keys.len()).unwrap_or(0);
+        let logged: &mut usize = self.logged.entry(table_id).or_default();
+        *logged += 1;
+
+        if *logged > live * 2 + DEADLINES_SLACK {
+            self.rewrite(db, table);
+        }
+    }
+
+    /// Rewrites the deadlines file of a table with the current deadlines only.
+    fn rewrite(&mut self, db: &str, table: &str) {
+        let table_id: (String, String) = (db.to_string(), table.to_string());
+        let keys: Option<&HashMap<String, u64>> = self.tables.get(&table_id);
+        let content: String = keys.iter()
+            .flat_map(|keys| keys.iter())
+            .map(|(key, at)| format!("{} {}\n", at, names::encode(key)))
+            .collect();
+
+        match engine().write_table_file(db, table, DEADLINES_FILE, &content) {
+            Ok(()) => {
+                let live: usize = keys.map(|keys| keys.len()).unwrap_or(0);
+                self.logged.insert(table_id, live);
+            },
+            Err(err) => println!("[ ERROR ] TTL: Can't rewrite the deadlines of {}/{} - {}", db, table, err)
+        }
+    }
+}
+
+lazy_static! {
+    /// Global deadlines of the expiring rows.
+    static ref DEADLINES: Mutex<Deadlines> = Mutex::new(Deadlines::default());
+}
+
+/// Returns the current unix time in seconds.
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+/// Checks if a row is expired.
+pub fn is_expired(row: &Row) -> bool {
+    row.has_expires_at() && row.expires_at() <= now()
+}
+
+/// Returns the seconds a row has left, or None if it never expires.
+pub fn remaining(row: &Row) -> Option<u64> {
+    if !row.has_expires_at() {
+        return None;
+    }
+
+    Some(row.expires_at().saturating_sub(now()))
+}
+
+/// Reads the expiry time of a new row from the `ttl` (seconds) or `expires_at` (unix seconds) header.
+///
+/// # Arguments
+///
+/// * `req` - Request headers.
+///
+/// # Returns
+///
+/// Returns Ok(None) if the row never expires; Err with a response message if the headers are invalid.
+pub fn from_headers(req: &RequestHeaders) -> Result<Option<u64>, String> {
+    match (req.param("ttl"), req.param("expires_at")) {
+        (Some(_), Some(_)) => Err("{\"code\": 400, \"message\": \"ttl and expires_at can not be used together\"}\njson".to_string()),
+        (Some(ttl), None) => match ttl.parse::<u64>().ok().filter(|secs| *secs > 0).map(|secs| now().checked_add(secs)) {
+            Some(Some(at)) => Ok(Some(at)),
+            Some(None) => Err("{\"code\": 400, \"message\": \"ttl is too large\"}\njson".to_string()),
+            None => Err("{\"code\": 400, \"message\": \"ttl must be a positive number of seconds\"}\njson".to_string())
+        },
+        (None, Some(at)) => match at.parse::<u64>() {
+            Ok(at) => Ok(Some(at)),
+            Err(_) => Err("{\"code\": 400, \"message\": \"expires_at must be a unix time in seconds\"}\njson".to_string())
+        },
+        (None, None) => Ok(None)
+    }
+}
+
+/// Records the deadline of a written row.
+pub fn on_put(db: &str, table: &str, key: &str, row: &Row) {
+    let expires_at: Option<u64> = if row.has_expires_at() { Some(row.expires_at()) } else { None };
+    let mut deadlines: MutexGuard<'_, Deadlines> = DEADLINES.lock().unwrap();
+
+    // Most rows never expire, skip the bookkeeping for them
+    if expires_at.is_none() && !deadlines.tables.contains_key(&(db.to_string(), table.to_string())) {
+        return;
+    }
+
+    deadlines.log(db, table, key, expires_at);
+}
+
+/// Drops the deadline of a deleted row.
+pub fn on_delete(db: &str, table: &str, key: &str) {
+    DEADLINES.lock().unwrap().log(db, table, key, None);
+}
+
+/// Moves the deadlines of renamed tables to the new names.
+pub fn on_rename(rename: &Rename) {
+    let mut deadlines: MutexGuard<'_, Deadlines> = DEADLINES.lock().unwrap();
+
+    let moved: Vec<(u64, String, String, String)> = deadlines.by_time.iter()
+        .filter(|(_, db, table, _)| rename.map(db, table).is_some())
+        .cloned()
+        .collect();
+    for (at, db, table, key) in moved {
+        deadlines.by_time.remove(&(at, db.clone(), table.clone(), key.clone()));
+        if let Some((to_db, to_table)) = rename.map(&db, &table) {
+            deadlines.by_time.insert((at, to_db, to_table, key));
+        }
+    }
+
+    rename.rekey(&mut deadlines.tables);
+    rename.rekey(&mut deadlines.logged);
+}
+
+/// Checks if the row behind a key is expired without reading it.
+pub fn is_key_expired(db: &str, table: &str, key: &str) -> bool {
+    let deadlines: MutexGuard<'_, Deadlines> = DEADLINES.lock().unwrap();
+
+    match deadlines.tables.get(&(db.to_string(), table.to_string())).and_then(|keys| keys.get(key)) {
+        Some(at) => *at <= now(),
+        None => false
+    }
+}
+
+/// Lists the keys of a table which fall into the range, leaving out expired rows.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+/// * `table` - Table name.
+/// * `range` - Range of keys to list.
+///
+/// # Returns
+///
+/// Returns at most `range.limit` keys of live rows in the order of the range.
+pub fn scan_rows(db: &str, table: &str, range: &KeyRange) -> Result<Vec<String>, String> {
+    if !DEADLINES.lock().unwrap().tables.contains_key(&(db.to_string(), table.to_string())) {
+        return engine().scan_rows(db, table, range);
+    }
+
+    let mut keys: Vec<String> = Vec::new();
+    let mut rest: KeyRange = range.clone();
+
+    loop {
+        let batch: Vec<String> = engine().scan_rows(db, table, &rest)?;
+        let full: bool = batch.len() == rest.limit;
+        let last: Option<String> = batch.last().cloned();
+        keys.extend(batch.into_iter().filter(|key| !is_key_expired(db, table, key)));
+
+        // Skipped expired keys leave room, the scan goes on after the last key until the limit is filled
+        match last {
+            Some(last) if full && keys.len() < range.limit => {
+                rest.limit = range.limit - keys.len();
+                if range.reverse {
+                    rest.end = Bound::Excluded(last);
+                } else {
+                    rest.start = Bound::Excluded(last);
+                }
+            },
+            _ => return Ok(keys)
+        }
+    }
+}
+
+/// Lists the keys of all live rows of a table.
+pub fn list_rows(db: &str, table: &str) -> Result<Vec<String>, String> {
+    scan_rows(db, table, &KeyRange::all())
+}
+
+/// Reads the persisted deadlines of all tables and starts the reaper thread.
+pub fn start() {
+    let mut found: usize = 0;
+
+    for db in engine().list_dbs().unwrap_or_default() {
+        for table in engine().list_tables(&db).unwrap_or_default() {
+            found += load_table(&db, &table);
+        }
+    }
+
+    println!("[ INFO ] TTL: tracking {} expiring rows", found);
+
+    thread::spawn(|| {
+        loop {
+            thread::sleep(time::Duration::from_millis(REAP_INTERVAL));
+            reap();
+        }
+    });
+}
+
+/// Reads the deadlines file of a table and rewrites it with the current deadlines only.
+///
+/// A table written before the deadlines were persisted has no file, its rows
+/// are read once to find them.
+///
+/// # Returns
+///
+/// Returns the number of expiring rows of the table.
+pub fn load_table(db: &str, table: &str) -> usize {
+    let keys: HashMap<String, u64> = match engine().read_table_file(db, table, DEADLINES_FILE) {
+        Some(content) => parse_file(&content),
+        None => engine().read_table(db, table).unwrap_or_default().into_iter()
+            .filter(|(_, row)| row.has_expires_at())
+            .map(|(key, row)| (key, row.expires_at()))
+            .collect()
+    };
+
+    let mut deadlines: MutexGuard<'_, Deadlines> = DEADLINES.lock().unwrap();
+    let found: usize = keys.len();
+    for (key, at) in keys {
+        deadlines.set(db, table, &key, Some(at));
+    }
+    deadlines.rewrite(db, table);
+
+    found
+}
+
+/// Parses a deadlines file, the last line of a key wins and a cleared deadline is 0.
+fn parse_file(content: &str) -> HashMap<String, u64> {
+    let mut keys: HashMap<String, u64> = HashMap::new();
+
+    for line in content.lines() {
+        // A line cut by a crash does not parse and is skipped
+        let parsed: Option<(u64, String)> = line.split_once(' ')
+            .and_then(|(at, key)| Some((at.parse::<u64>().ok()?, names::decode(key)?)));
+
+        match parsed {
+            Some((0, key)) => { keys.remove(&key); },
+            Some((at, key)) => { keys.insert(key, at); },
+            None => {}
+        }
+    }
+
+    keys
+}
+
+/// Deletes all rows which are expired by now.
+///
+/// # Returns
+///
+/// Returns the number of deleted rows.
+pub fn reap() -> usize {
+    let _gate: RwLockReadGuard<'_, ()> = snapshot::write_gate();
+
+    let due: Vec<(u64, String, String, String)> = {
+        let mut deadlines: MutexGuard<'_, Deadlines> = DEADLINES.lock().unwrap();
+        let now: u64 = now();
+        let due: Vec<(u64, String, String, String)> = deadlines.by_time.iter()
+            .take_while(|(at, ..)| *at <= now)
+            .cloned()
+            .collect();
+
+        for (_, db, table, key) in due.iter() {
+            deadlines.log(db, table, key, None);
+        }
+
+        due
+    };
+
+    due.iter()
+        .filter(|(_, db, table, key)| cache::delete_expired(db, table, key))
+        .count()
+}
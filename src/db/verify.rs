@@ -0,0 +1,161 @@
+//! Integrity scan of stored rows.
+//!
+//! Every row of a table is read straight from the storage, bypassing the
+//! cache, so the record checksums are checked and the rows are decoded.
+//! The scan goes on after a damaged row and reports all of them, along with
+//! the damaged records the storage skipped when the table was loaded.
+
+use serde::Serialize;
+
+use crate::db::engine::{engine, SkippedRecord};
+
+/// A row that could not be read.
+#[derive(Serialize, Debug, Clone)]
+pub struct Damage {
+    /// Row key.
+    pub key: String,
+
+    /// Why the row could not be read.
+    pub error: String
+}
+
+/// Result of the scan of one table.
+#[derive(Serialize, Debug, Clone)]
+pub struct TableReport {
+    /// Table name.
+    pub table: String,
+
+    /// Number of checked rows.
+    pub checked: u64,
+
+    /// Rows that could not be read.
+    pub damaged: Vec<Damage>,
+
+    /// Damaged records skipped when the table was loaded.
+    pub skipped: Vec<SkippedRecord>,
+
+    /// Set when the table itself could not be opened.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>
+}
+
+impl TableReport {
+    /// Checks if the table has no damage.
+    pub fn is_ok(&self) -> bool {
+        self.damaged.is_empty() && self.skipped.is_empty() && self.error.is_none()
+    }
+}
+
+/// Result of the scan of a database.
+#[derive(Serialize, Debug, Clone)]
+pub struct DbReport {
+    /// Database name.
+    pub db: String,
+
+    /// Number of checked rows of all tables.
+    pub checked: u64,
+
+    /// Number of rows that could not be read.
+    pub damaged: u64,
+
+    /// Number of damaged records skipped when the tables were loaded.
+    pub skipped: u64,
+
+    /// Results of every table.
+    pub tables: Vec<TableReport>
+}
+
+/// Reads every row of a table and collects the ones that can not be read.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+/// * `table` - Table name.
+///
+/// # Returns
+///
+/// Returns the report, or an error message if there is no such table.
+pub fn verify_table(db: &str, table: &str) -> Result<TableReport, String> {
+    if !engine().table_exists(db, table) {
+        return Err("no such table".to_string());
+    }
+
+    let mut report: TableReport = TableReport {
+        table: table.to_string(),
+        checked: 0,
+        damaged: Vec::new(),
+        skipped: Vec::new(),
+        error: None
+    };
+
+    let keys: Vec<String> = match engine().list_rows(db, table) {
+        Ok(keys) => keys,
+        Err(err) => {
+            report.error = Some(err);
+            return Ok(report);
+        }
+    };
+
+    match engine().skipped_records(db, table) {
+        Ok(skipped) => report.skipped = skipped,
+        Err(err) => report.error = Some(err)
+    }
+
+    for key in keys {
+        match engine().read_row(db, table, &key) {
+            Ok(Some(_)) => report.checked += 1,
+            // Deleted while the table was scanned
+            Ok(None) => {},
+            Err(error) => {
+                report.checked += 1;
+                report.damaged.push(Damage { key, error });
+            }
+        }
+    }
+
+    if !report.is_ok() {
+        println!("[ WARN ] Verify: {} damaged rows and {} skipped records in {}/{}", report.damaged.len(), report.skipped.len(), db, table);
+    }
+
+    Ok(report)
+}
+
+/// Scans every table of a database.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+///
+/// # Returns
+///
+/// Returns the report, or an error message if there is no such database.
+pub fn verify_db(db: &str) -> Result<DbReport, String> {
+    if !engine().db_exists(db) {
+        return Err("no such database".to_string());
+    }
+
+    let mut report: DbReport = DbReport {
+        db: db.to_string(),
+        checked: 0,
+        damaged: 0,
+        skipped: 0,
+        tables: Vec::new()
+    };
+
+    for table in engine().list_tables(db)? {
+        let table_report: TableReport = verify_table(db, &table).unwrap_or_else(|error| TableReport {
+            table: table.clone(),
+            checked: 0,
+            damaged: Vec::new(),
+            skipped: Vec::new(),
+            error: Some(error)
+        });
+
+        report.checked += table_report.checked;
+        report.damaged += table_report.damaged.len() as u64;
+        report.skipped += table_report.skipped.len() as u64;
+        report.tables.push(table_report);
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,348 @@
+//! Write-ahead log of mutating requests.
+//!
+//! Every mutating request is appended to `{db_path}/wal.log` before it is
+//! applied and marked as committed afterwards. Entries without a commit mark
+//! are replayed on startup. All logged requests are idempotent, so replaying
+//! a partially applied entry brings the storage to the state the request
+//! was meant to produce.
+//!
+//! Writes of different keys run side by side, so a request can be committed
+//! while an earlier one touching the same rows is not. Such an earlier entry
+//! is not replayed, it would overwrite the committed write with an older one.
+
+use std::{collections::{BTreeMap, HashSet}, fs::{self, File, OpenOptions}, io::Write, sync::{Mutex, MutexGuard}};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::{config, db::durability, http::receiver::RequestHeaders, tx_pool::req_handler};
+
+/// Requests which change the stored data and have to be logged.
+pub static MUTATIONS: [&str; 18] = [
+    "add_row",
+    "put_row",
+    "update_row",
+    "delete_row",
+    "delete_many",
+    "delete_where",
+    "add_bunch",
+    "add_table",
+    "delete_table",
+    "rename_table",
+    "create_index",
+    "drop_index",
+    "set_table_schema",
+    "create_db",
+    "delete_db",
+    "rename_db",
+    "set_durability",
+    "restore_db"
+];
+
+/// Mutations which take the write gate exclusively, no other write runs next to them.
+pub static EXCLUSIVE: [&str; 3] = [
+    "rename_table",
+    "rename_db",
+    "restore_db"
+];
+
+/// Requests which change one row in place and are logged as the `put_row` of the row they store.
+///
+/// Replaying the change itself would apply it twice if it was already written.
+pub static ROW_CHANGES: [&str; 9] = [
+    "patch_row",
+    "incr",
+    "decr",
+    "list_push",
+    "list_pop",
+    "set_add",
+    "set_remove",
+    "hash_set",
+    "hash_delete"
+];
+
+/// Requests which log every row they write as its `put_row`, their input can be larger than one entry should be.
+pub static ROW_WRITERS: [&str; 1] = [
+    "import_table"
+];
+
+/// Size of the log (in bytes) after which it is truncated once nothing is pending.
+const CHECKPOINT_SIZE: u64 = 16 * 1024 * 1024;
+
+/// One line of the log.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "state", rename_all = "lowercase")]
+enum WalRecord {
+    /// Request is about to be applied.
+    Begin {
+        id: u64,
+        req: String,
+        head: Box<RequestHeaders>,
+        body: String
+    },
+
+    /// Request was applied.
+    Commit {
+        id: u64
+    }
+}
+
+/// Write-ahead log file with the ids of not yet committed entries.
+pub struct Wal {
+    /// Path of the log file.
+    path: String,
+
+    /// Open handle of the log file.
+    file: File,
+
+    /// Id of the next entry.
+    next_id: u64,
+
+    /// Ids of entries which are logged but not committed.
+    pending: HashSet<u64>,
+
+    /// Current size of the log file.
+    size: u64
+}
+
+lazy_static! {
+    /// Global write-ahead log.
+    static ref WAL: Mutex<Option<Wal>> = Mutex::new(None);
+}
+
+impl Wal {
+    /// Opens the log file at the given path, creating it if needed.
+    pub fn open(path: &str) -> Result<Self, String> {
+        let file: File = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .map_err(|e| format!("[ ERROR ] WAL: Can not open {} - {}", path, e))?;
+        let size: u64 = file.metadata().map(|m| m.len()).unwrap_or(0);
+
+        // Continue numbering after the entries which are already in the file
+        let last_id: u64 = fs::read_to_string(path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| serde_json::from_str::<WalRecord>(line).ok())
+            .map(|record| match record {
+                WalRecord::Begin { id, .. } => id,
+                WalRecord::Commit { id } => id
+            })
+            .max()
+            .unwrap_or(0);
+
+        Ok(Self {
+            path: path.to_string(),
+            file,
+            next_id: last_id + 1,
+            pending: HashSet::new(),
+            size
+        })
+    }
+
+    /// Logs a request before it is applied.
+    ///
+    /// # Returns
+    ///
+    /// Returns the id of the log entry.
+    pub fn begin(&mut self, req: &str, head: &RequestHeaders, body: &str) -> Result<u64, String> {
+        let id: u64 = self.next_id;
+        self.write(&WalRecord::Begin {
+            id,
+            req: req.to_string(),
+            head: Box::new(head.clone()),
+            body: body.to_string()
+        })?;
+
+        self.next_id += 1;
+        self.pending.insert(id);
+
+        Ok(id)
+    }
+
+    /// Marks a log entry as applied and truncates the log when it is safe.
+    pub fn commit(&mut self, id: u64) -> Result<(), String> {
+        self.write(&WalRecord::Commit { id })?;
+        self.pending.remove(&id);
+
+        if self.pending.is_empty() && self.size >= CHECKPOINT_SIZE {
+            self.truncate()?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns a new handle of the log file for flushing it outside of the log lock.
+    fn handle(&self) -> Result<File, String> {
+        self.file.try_clone().map_err(|e| e.to_string())
+    }
+
+    /// Drops all entries of the log.
+    fn truncate(&mut self) -> Result<(), String> {
+        self.file.set_len(0).map_err(|e| format!("[ ERROR ] WAL: Can not truncate {} - {}", self.path, e))?;
+        self.size = 0;
+
+        Ok(())
+    }
+
+    /// Appends one record as a JSON line.
+    fn write(&mut self, record: &WalRecord) -> Result<(), String> {
+        let mut line: String = serde_json::to_string(record).map_err(|e| e.to_string())?;
+        line.push('\n');
+
+        self.file.write_all(line.as_bytes()).map_err(|e| format!("[ ERROR ] WAL: Can not write {} - {}", self.path, e))?;
+        self.size += line.len() as u64;
+
+        Ok(())
+    }
+}
+
+/// Builds the path of the global log file.
+fn wal_path() -> String {
+    format!("{}/wal.log", config::CONFIG.db_path)
+}
+
+/// Checks if a request changes the stored data.
+pub fn is_mutation(req: &str) -> bool {
+    MUTATIONS.contains(&req)
+}
+
+/// Checks if a mutation has to run alone.
+pub fn is_exclusive(req: &str) -> bool {
+    EXCLUSIVE.contains(&req)
+}
+
+/// Checks if a request changes one row in place.
+pub fn is_row_change(req: &str) -> bool {
+    ROW_CHANGES.contains(&req)
+}
+
+/// Checks if a request logs the rows it writes one by one.
+pub fn is_row_writer(req: &str) -> bool {
+    ROW_WRITERS.contains(&req)
+}
+
+/// Logs a request to the global write-ahead log.
+///
+/// # Arguments
+///
+/// * `req` - Request type.
+/// * `head` - Request headers.
+/// * `body` - Request body.
+///
+/// # Returns
+///
+/// Returns the id of the log entry, which has to be passed to [`commit`] once the request is applied.
+/// The entry is flushed according to the durability level of the request database.
+pub fn begin(req: &str, head: &RequestHeaders, body: &str) -> Result<u64, String> {
+    let (id, handle): (u64, File) = {
+        let mut wal: MutexGuard<'_, Option<Wal>> = WAL.lock().unwrap();
+
+        if wal.is_none() {
+            *wal = Some(Wal::open(&wal_path())?);
+        }
+
+        let log: &mut Wal = wal.as_mut().unwrap();
+        (log.begin(req, head, body)?, log.handle()?)
+    };
+
+    durability::sync(&head.db, &handle)?;
+
+    Ok(id)
+}
+
+/// Marks an entry of the global write-ahead log as applied.
+pub fn commit(id: u64) {
+    let mut wal: MutexGuard<'_, Option<Wal>> = WAL.lock().unwrap();
+
+    if let Some(log) = wal.as_mut() {
+        if let Err(err) = log.commit(id) {
+            println!("{}", err);
+        }
+    }
+}
+
+/// Replays not committed entries of the global write-ahead log.
+///
+/// # Returns
+///
+/// Returns true if the recovery is successful; false otherwise.
+pub fn recover() -> bool {
+    match recover_from(&wal_path()) {
+        Ok(_) => true,
+        Err(err) => {
+            println!("{}", err);
+            false
+        }
+    }
+}
+
+/// Replays not committed entries of the log at the given path and truncates it.
+///
+/// # Arguments
+///
+/// * `path` - Path of the log file.
+///
+/// # Returns
+///
+/// Returns the number of replayed entries.
+pub fn recover_from(path: &str) -> Result<usize, String> {
+    let content: String = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(_) => return Ok(0)
+    };
+
+    // Entries by id with the line they begin on, and the rows of committed entries with the line of their commit
+    let mut incomplete: BTreeMap<u64, (usize, String, RequestHeaders, String)> = BTreeMap::new();
+    let mut committed: Vec<(usize, RequestHeaders)> = Vec::new();
+
+    for (line_no, line) in content.lines().enumerate() {
+        // The last line could be torn by a crash in the middle of a write
+        match serde_json::from_str::<WalRecord>(line) {
+            Ok(WalRecord::Begin { id, req, head, body }) => {
+                incomplete.insert(id, (line_no, req, *head, body));
+            },
+            Ok(WalRecord::Commit { id }) => {
+                if let Some((_, _, head, _)) = incomplete.remove(&id) {
+                    committed.push((line_no, head));
+                }
+            },
+            Err(_) => {
+                println!("[ WARN ] WAL: skip broken entry in {}", path);
+            }
+        }
+    }
+
+    let mut replayed: usize = 0;
+    for (id, (line_no, req, head, body)) in incomplete {
+        // A write committed after this entry began may have been applied after it, replaying would revert it
+        if committed.iter().any(|(commit_no, other)| *commit_no > line_no && overlaps(&head, other)) {
+            println!("[ INFO ] WAL: skip entry {} - `{}`, a later committed write changed the same rows", id, req);
+            continue;
+        }
+
+        replayed += 1;
+        println!("[ INFO ] WAL: replay entry {} - `{}`", id, req);
+        if let Err(err) = req_handler::apply(&req, &head, &body) {
+            println!("[ WARN ] WAL: entry {} was not applied - {}", id, err);
+        }
+    }
+
+    let mut wal: Wal = Wal::open(path)?;
+    wal.truncate()?;
+
+    if replayed > 0 {
+        println!("[ INFO ] WAL: replayed {} entries", replayed);
+    }
+
+    Ok(replayed)
+}
+
+/// Checks if two logged requests can change the same rows.
+///
+/// A request without a key covers its whole table, one without a table its whole database.
+fn overlaps(a: &RequestHeaders, b: &RequestHeaders) -> bool {
+    a.db == b.db
+        && (a.table.is_empty() || b.table.is_empty() || a.table == b.table)
+        && (a.key.is_empty() || b.key.is_empty() || a.key == b.key)
+}
@@ -0,0 +1,50 @@
+use serde_json::json;
+
+use crate::{db::{cdc, engine::engine}, http::receiver::RequestHeaders};
+
+/// Subscribes the session to the changes of the database of the request.
+///
+/// The `table` header narrows the subscription to one table and the `prefix`
+/// header to the keys starting with it. The events are sent with the `rud`
+/// of this request.
+///
+/// # Arguments
+///
+/// * `req` - RequestHeaders naming the watched rows.
+/// * `session` - Client session which receives the events.
+///
+/// # Returns
+///
+/// Returns a Result with the id of the subscription in the `subscription` field.
+pub fn subscribe(req: &RequestHeaders, session: &str) -> Result<String, String> {
+    if req.db.is_empty() {
+        return Err("{\"code\": 400, \"message\": \"db header is required\"}\njson".to_string());
+    }
+    if !engine().db_exists(&req.db) {
+        return Err("{\"code\": 404, \"message\": \"no such database\"}\njson".to_string());
+    }
+
+    let table: Option<&str> = (!req.table.is_empty()).then_some(req.table.as_str());
+    match cdc::subscribe(session, &req.rud, &req.db, table, req.param("prefix").unwrap_or_default()) {
+        Some(id) => Ok(json!({"code": 200, "subscription": id}).to_string() + "\njson"),
+        None => Err("{\"code\": 410, \"message\": \"session is closed\"}\njson".to_string())
+    }
+}
+
+/// Cancels the subscription given in the `subscription` header.
+///
+/// # Arguments
+///
+/// * `req` - RequestHeaders containing the `subscription` header.
+/// * `session` - Client session which subscribed.
+///
+/// # Returns
+///
+/// Returns a Result indicating the status of the operation.
+pub fn unsubscribe(req: &RequestHeaders, session: &str) -> Result<String, String> {
+    if cdc::unsubscribe(req.param("subscription").unwrap_or_default(), session) {
+        Ok("{\"code\": 200, \"message\": \"Subscription was cancelled\"}\njson".to_string())
+    } else {
+        Err("{\"code\": 404, \"message\": \"no such subscription\"}\njson".to_string())
+    }
+}
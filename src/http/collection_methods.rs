@@ -0,0 +1,237 @@
+//! Server-side operations of the `list`, `set` and `hash` row types.
+//!
+//! Changes run under the key lock of the row, so concurrent pushes or adds
+//! to the same row are all kept. A push, add or field set creates a missing
+//! row, a pop, remove or field delete leaves it missing. A row which is
+//! emptied stays stored with no items.
+
+use std::collections::HashMap;
+use serde_json::json;
+
+use crate::{cache, db::checksum, http::{receiver::RequestHeaders, row_methods}, protos::row::Row, types::collection};
+
+/// Error of a change which needs an existing row.
+const NO_ROW: &str = "key does not exist";
+
+/// Adds the JSON array of the body to the front (`side: left`) or the back of a list.
+///
+/// # Arguments
+///
+/// * `req` - RequestHeaders naming the row.
+/// * `body` - JSON array of strings.
+///
+/// # Returns
+///
+/// Returns a Result with the new length of the list or an error message.
+pub fn list_push(req: &RequestHeaders, body: &str) -> Result<String, String> {
+    let left: bool = side(req)?;
+    let items: Vec<String> = collection::parse_items(body).map_err(bad_request)?;
+    let mut len: usize = 0;
+
+    change(req, "list", true, |row| {
+        len = collection::list_push(row, items, left);
+    })?;
+
+    Ok(len.to_string() + "\nuint")
+}
+
+/// Removes items from the front (`side: left`) or the back of a list.
+///
+/// The `count` header sets how many items are removed, 1 by default.
+///
+/// # Returns
+///
+/// Returns a Result with the removed items as a list or an error message.
+pub fn list_pop(req: &RequestHeaders) -> Result<String, String> {
+    let left: bool = side(req)?;
+    let count: usize = match req.param("count") {
+        Some(count) => count.parse::<usize>().map_err(|_| bad_request("count must be a positive number".to_string()))?,
+        None => 1
+    };
+    let mut items: Vec<String> = Vec::new();
+
+    change(req, "list", false, |row| {
+        items = collection::list_pop(row, count, left);
+    })?;
+
+    Ok(serde_json::to_string(&items).unwrap() + "\nlist")
+}
+
+/// Returns the items of a list between the `start` and `stop` headers, both included.
+///
+/// Negative positions count from the end, the whole list is returned by default.
+///
+/// # Returns
+///
+/// Returns a Result with the items as a list or an error message.
+pub fn list_range(req: &RequestHeaders) -> Result<String, String> {
+    let start: i64 = position(req, "start", 0)?;
+    let stop: i64 = position(req, "stop", -1)?;
+
+    let items: Vec<String> = match read(req, "list")? {
+        Some(row) => collection::list_range(&row, start, stop),
+        None => Vec::new()
+    };
+
+    Ok(serde_json::to_string(&items).unwrap() + "\nlist")
+}
+
+/// Adds the JSON array of the body to a set.
+///
+/// # Returns
+///
+/// Returns a Result with the number of items which were not in the set yet or an error message.
+pub fn set_add(req: &RequestHeaders, body: &str) -> Result<String, String> {
+    let items: Vec<String> = collection::parse_items(body).map_err(bad_request)?;
+    let mut added: usize = 0;
+
+    change(req, "set", true, |row| {
+        added = collection::set_add(row, items);
+    })?;
+
+    Ok(added.to_string() + "\nuint")
+}
+
+/// Removes the JSON array of the body from a set.
+///
+/// # Returns
+///
+/// Returns a Result with the number of removed items or an error message.
+pub fn set_remove(req: &RequestHeaders, body: &str) -> Result<String, String> {
+    let items: Vec<String> = collection::parse_items(body).map_err(bad_request)?;
+    let mut removed: usize = 0;
+
+    change(req, "set", false, |row| {
+        removed = collection::set_remove(row, &items);
+    })?;
+
+    Ok(removed.to_string() + "\nuint")
+}
+
+/// Returns the items of a set, an empty set if the key does not exist.
+pub fn set_members(req: &RequestHeaders) -> Result<String, String> {
+    let row: Row = read(req, "set")?.unwrap_or_else(|| collection::new_row("set"));
+
+    Ok(collection::value(&row) + "\nset")
+}
+
+/// Checks if the item given in the body is in a set.
+pub fn set_contains(req: &RequestHeaders, body: &str) -> Result<String, String> {
+    let found: bool = match read(req, "set")? {
+        Some(row) => collection::set_contains(&row, body),
+        None => false
+    };
+
+    Ok(found.to_string() + "\nbool")
+}
+
+/// Sets the fields of the JSON object of the body in a hash.
+///
+/// # Returns
+///
+/// Returns a Result with the number of fields which were not in the hash yet or an error message.
+pub fn hash_set(req: &RequestHeaders, body: &str) -> Result<String, String> {
+    let fields: HashMap<String, String> = collection::parse_fields(body).map_err(bad_request)?;
+    let mut added: usize = 0;
+
+    change(req, "hash", true, |row| {
+        added = collection::hash_set(row, fields);
+    })?;
+
+    Ok(added.to_string() + "\nuint")
+}
+
+/// Returns the field of a hash named in the body.
+///
+/// # Returns
+///
+/// Returns a Result with the value of the field or an error message if the key or the field does not exist.
+pub fn hash_get(req: &RequestHeaders, body: &str) -> Result<String, String> {
+    let row: Row = match read(req, "hash")? {
+        Some(row) => row,
+        None => return Err(json!({"code": 404, "message": NO_ROW}).to_string() + "\njson")
+    };
+
+    match row.fields.get(body) {
+        Some(value) => Ok(value.clone() + "\nstring"),
+        None => Err(json!({"code": 404, "message": "field does not exist"}).to_string() + "\njson")
+    }
+}
+
+/// Deletes the fields of a hash named in the JSON array of the body.
+///
+/// # Returns
+///
+/// Returns a Result with the number of deleted fields or an error message.
+pub fn hash_delete(req: &RequestHeaders, body: &str) -> Result<String, String> {
+    let names: Vec<String> = collection::parse_items(body).map_err(bad_request)?;
+    let mut deleted: usize = 0;
+
+    change(req, "hash", false, |row| {
+        deleted = collection::hash_delete(row, &names);
+    })?;
+
+    Ok(deleted.to_string() + "\nuint")
+}
+
+/// Changes a collection row in place.
+///
+/// A missing row is created empty if `create` is set, otherwise `op` is not
+/// called and nothing is written.
+fn change<F>(req: &RequestHeaders, data_type: &str, create: bool, op: F) -> Result<(), String>
+where
+    F: FnOnce(&mut Row)
+{
+    let result: Result<Row, String> = row_methods::change_row(req, |current| {
+        let mut row: Row = match current {
+            Some(row) => row,
+            None if create => collection::new_row(data_type),
+            None => return Err(NO_ROW.to_string())
+        };
+        collection::check_type(&row, data_type)?;
+
+        op(&mut row);
+        Ok(row)
+    });
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(err) if err == NO_ROW => Ok(()),
+        Err(err) if err.ends_with("\njson") => Err(err),
+        Err(err) => Err(bad_request(err))
+    }
+}
+
+/// Reads a collection row, None if the key does not exist.
+fn read(req: &RequestHeaders, data_type: &str) -> Result<Option<Row>, String> {
+    let row: Row = match cache::get(&req.db, &req.table, &req.key) {
+        Ok(row) => row,
+        Err(err) if checksum::is_corrupted(&err) => return Err(json!({"code": 500, "message": err}).to_string() + "\njson"),
+        Err(_) => return Ok(None)
+    };
+
+    collection::check_type(&row, data_type).map_err(bad_request)?;
+    Ok(Some(row))
+}
+
+/// Reads the `side` header, true for `left`.
+fn side(req: &RequestHeaders) -> Result<bool, String> {
+    match req.param("side") {
+        Some("left") => Ok(true),
+        Some("right") | None => Ok(false),
+        Some(_) => Err(bad_request("side must be left or right".to_string()))
+    }
+}
+
+/// Reads a list position header.
+fn position(req: &RequestHeaders, name: &str, default: i64) -> Result<i64, String> {
+    match req.param(name) {
+        Some(value) => value.parse::<i64>().map_err(|_| bad_request(format!("{} must be a number", name))),
+        None => Ok(default)
+    }
+}
+
+/// Builds a 400 response.
+fn bad_request(message: String) -> String {
+    json!({"code": 400, "message": message}).to_string() + "\njson"
+}
@@ -1,4 +1,6 @@
-use crate::{cache::cache_db, http::receiver::RequestHeaders};
+use serde_json::json;
+
+use crate::{cache::cache_db, config::Durability, db::{engine::engine, names, meta::{self, DbMeta}, snapshot::{self, Manifest}, stats, ttl, verify}, http::{pagination::Page, receiver::RequestHeaders}};
 
 /// Deletes a database based on the information provided in the request headers.
 ///
@@ -14,4 +16,205 @@ use crate::{cache::cache_db, http::receiver::RequestHeaders};
 /// - If an error occurs during the deletion, it returns an empty string.
 pub fn delete(req: &RequestHeaders) -> Result<String, String> {
     return cache_db::delete_db(&req.db);
-}
\ No newline at end of file
+}
+
+/// Lists the names of all databases.
+///
+/// With a `limit` or `cursor` header only one page of names is returned along with the `next_cursor`.
+///
+/// # Arguments
+///
+/// * `req` - RequestHeaders with the optional pagination headers.
+///
+/// # Returns
+///
+/// Returns a Result with a JSON array of names or an error message.
+pub fn list(req: &RequestHeaders) -> Result<String, String> {
+    let dbs: Vec<String> = match engine().list_dbs() {
+        Ok(dbs) => dbs,
+        Err(err) => return Err(json!({"code": 500, "message": err}).to_string() + "\njson")
+    };
+
+    match Page::from_headers(req)? {
+        Some(page) => Ok(page.names_response(dbs)),
+        None => Ok(json!(dbs).to_string() + "\njson")
+    }
+}
+
+/// Creates an empty database.
+///
+/// # Arguments
+///
+/// * `req` - RequestHeaders containing the database name.
+///
+/// # Returns
+///
+/// Returns a Result indicating the status of the operation.
+pub fn create(req: &RequestHeaders) -> Result<String, String> {
+    if let Err(err) = names::check_name("database", &req.db) {
+        return Err(json!({"code": 400, "message": err}).to_string() + "\njson");
+    }
+
+    if engine().create_db(&req.db) {
+        Ok("{\"code\": 200, \"message\": \"DB was created\"}\njson".to_string())
+    } else {
+        Err("{\"code\": 409, \"message\": \"DataBase already exists\"}\njson".to_string())
+    }
+}
+
+/// Renames a database to the name in the `to` header.
+///
+/// The rename runs while no other write does, the tables, metadata and cached rows move along.
+///
+/// # Arguments
+///
+/// * `req` - RequestHeaders containing the database name and the `to` header.
+///
+/// # Returns
+///
+/// Returns a Result indicating the status of the operation.
+pub fn rename(req: &RequestHeaders) -> Result<String, String> {
+    let to: &str = match req.param("to") {
+        Some(to) => to,
+        None => return Err("{\"code\": 400, \"message\": \"to header is required\"}\njson".to_string())
+    };
+
+    match cache_db::rename_db(&req.db, to) {
+        Ok(_) => Ok("{\"code\": 200, \"message\": \"DB was renamed\"}\njson".to_string()),
+        Err(err) => Err(json!({"code": 400, "message": err}).to_string() + "\njson")
+    }
+}
+
+/// Sets the durability level of a database from the `durability` header.
+///
+/// # Arguments
+///
+/// * `req` - RequestHeaders containing the database name and the `durability` header
+///   (`none`, `fsync-every-write`, `group-commit` or `default` to use the server level).
+///
+/// # Returns
+///
+/// Returns a Result indicating the status of the operation.
+pub fn set_durability(req: &RequestHeaders) -> Result<String, String> {
+    let mut db_meta: DbMeta = meta::get_db_meta(&req.db);
+
+    match req.param("durability").unwrap_or("default") {
+        "default" => db_meta.durability = None,
+        name => match Durability::parse(name) {
+            Some(level) => db_meta.durability = Some(level.as_str().to_string()),
+            None => return Err("{\"code\": 400, \"message\": \"unknown durability level\"}\njson".to_string())
+        }
+    }
+
+    match meta::set_db_meta(&req.db, &db_meta) {
+        Ok(_) => Ok("{\"code\": 200, \"message\": \"Durability was set\"}\njson".to_string()),
+        Err(err) => Err(("{\"code\": 500, \"message\": \"".to_owned() + &err + "\"}\njson").to_string())
+    }
+}
+
+/// Writes a consistent snapshot of the database, or of all databases if no `db` header is given.
+///
+/// # Arguments
+///
+/// * `req` - RequestHeaders containing the database name and an optional `path` header of the archive,
+///   relative to the snapshot directory of the configuration. Without a path the archive is named
+///   after the database and the time.
+///
+/// # Returns
+///
+/// Returns a Result with the path and the manifest of the archive.
+pub fn snapshot(req: &RequestHeaders) -> Result<String, String> {
+    let dbs: Vec<String> = if req.db.is_empty() {
+        engine().list_dbs().map_err(|err| ("{\"code\": 500, \"message\": \"".to_owned() + &err + "\"}\njson").to_string())?
+    } else {
+        vec![req.db.clone()]
+    };
+
+    let path: String = match req.param("path") {
+        Some(path) => path.to_string(),
+        None => format!("{}-{}.snap", if req.db.is_empty() { "all" } else { &req.db }, ttl::now())
+    };
+
+    match snapshot::create(&dbs, &path) {
+        Ok(manifest) => Ok(json!({"code": 200, "path": path, "manifest": manifest}).to_string() + "\njson"),
+        Err(err) => Err(json!({"code": 500, "message": err}).to_string() + "\njson")
+    }
+}
+
+/// Restores databases from a snapshot archive after verifying it against its manifest.
+///
+/// # Arguments
+///
+/// * `req` - RequestHeaders containing:
+///   - `path` - path of the archive inside the snapshot directory,
+///   - `db` - database of the archive to restore, all of them if empty,
+///   - `target` - optional new name of the restored database,
+///   - `replace` - `true` to replace existing databases.
+///
+/// # Returns
+///
+/// Returns a Result with the names of the restored databases.
+pub fn restore(req: &RequestHeaders) -> Result<String, String> {
+    let path: &str = match req.param("path") {
+        Some(path) => path,
+        None => return Err("{\"code\": 400, \"message\": \"path header is required\"}\njson".to_string())
+    };
+    let source: Option<&str> = if req.db.is_empty() { None } else { Some(&req.db) };
+
+    match snapshot::restore(path, source, req.param("target"), req.flag("replace")) {
+        Ok(dbs) => Ok(json!({"code": 200, "message": "Snapshot was restored", "databases": dbs}).to_string() + "\njson"),
+        Err(err) => Err(json!({"code": 400, "message": err}).to_string() + "\njson")
+    }
+}
+
+/// Reads and verifies the manifest of a snapshot archive given in the `path` header,
+/// relative to the snapshot directory.
+///
+/// # Returns
+///
+/// Returns a Result with the manifest if every table matches it.
+pub fn verify_snapshot(req: &RequestHeaders) -> Result<String, String> {
+    let path: &str = match req.param("path") {
+        Some(path) => path,
+        None => return Err("{\"code\": 400, \"message\": \"path header is required\"}\njson".to_string())
+    };
+
+    let result: Result<Manifest, String> = snapshot::verify(path);
+    match result {
+        Ok(manifest) => Ok(json!({"code": 200, "manifest": manifest}).to_string() + "\njson"),
+        Err(err) => Err(json!({"code": 400, "message": err}).to_string() + "\njson")
+    }
+}
+
+/// Reports the row count, value bytes, per-type breakdown, creation and last write time
+/// and cached rows of a database and each of its tables.
+///
+/// # Arguments
+///
+/// * `req` - RequestHeaders containing the database name.
+///
+/// # Returns
+///
+/// A `Result` containing the statistics as JSON or an error message.
+pub fn stats(req: &RequestHeaders) -> Result<String, String> {
+    match stats::db_stats(&req.db) {
+        Ok(stats) => Ok(json!(stats).to_string() + "\njson"),
+        Err(err) => Err(json!({"code": 400, "message": err}).to_string() + "\njson")
+    }
+}
+
+/// Reads every row of all tables of a database from the storage and reports the damaged ones.
+///
+/// # Arguments
+///
+/// * `req` - RequestHeaders containing the database name.
+///
+/// # Returns
+///
+/// Returns a Result with the report as JSON or an error message.
+pub fn verify(req: &RequestHeaders) -> Result<String, String> {
+    match verify::verify_db(&req.db) {
+        Ok(report) => Ok(json!(report).to_string() + "\njson"),
+        Err(err) => Err(json!({"code": 400, "message": err}).to_string() + "\njson")
+    }
+}
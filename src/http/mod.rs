@@ -4,12 +4,16 @@ pub mod row_methods;
 pub mod table_methods;
 pub mod db_methods;
 pub mod receiver;
+pub mod pagination;
+pub mod tx_methods;
+pub mod collection_methods;
+pub mod cdc_methods;
 
-use std::{net::{TcpListener, TcpStream}, thread, sync::{Mutex, MutexGuard}, collections::HashMap, io::Write};
+use std::{net::{TcpListener, TcpStream}, thread, sync::{Mutex, MutexGuard}, collections::HashMap, io::{self, Write}};
 use lazy_static::lazy_static;
 use uuid::Uuid;
 
-use crate::{tx_pool::add_tx, config::CONFIG};
+use crate::{tx_pool::{add_tx, transaction}, config::CONFIG, db::cdc, types};
 use receiver::RequestHeaders;
 
 /// A structure representing the clients connected to the server with writable streams.
@@ -49,8 +53,16 @@ fn add_stream(stream: TcpStream) -> String {
     return address.to_string();
 }
 
+/// Checks if the client of a session is still connected.
+pub fn is_connected(session: &str) -> bool {
+    CLIENTS.lock().unwrap().writable.contains_key(session)
+}
+
 /// Sends a message to a specific client identified by the provided address.
 ///
+/// A client which is already gone is skipped, its session is cleaned up when
+/// its connection is closed.
+///
 /// # Arguments
 ///
 /// * `rud` - The Rudiment identifier.
@@ -59,12 +71,105 @@ fn add_stream(stream: TcpStream) -> String {
 pub fn send(rud: &str, data: &str, to: &str) {
     let cl: MutexGuard<'_, Clients> = CLIENTS.lock().unwrap();
 
-    let mut client: &TcpStream = cl.writable.get(to).unwrap();
-    client.write(format!("rud: {}\n{}", rud, data).as_bytes()).unwrap();
+    let mut client: &TcpStream = match cl.writable.get(to) {
+        Some(client) => client,
+        None => return
+    };
+    if let Err(err) = client.write_all(format!("rud: {}\n{}", rud, data).as_bytes()) {
+        println!("[ ERROR ] Can't send a message to {} - {}", to, err);
+    }
 
     return;
 }
 
+/// Sends the response of a request to a specific client.
+///
+/// A `bytes` value is sent as its raw bytes after a `length` line:
+/// `rud: {rud}\nlength: {n}\n{bytes}\nbytes`, unless the request asked for
+/// `encoding: base64`. Any other response is sent as is.
+///
+/// # Arguments
+///
+/// * `head` - The headers of the request.
+/// * `data` - The response of the request.
+/// * `to` - The address (Uuid) of the target client.
+pub fn respond(head: &RequestHeaders, data: &str, to: &str) {
+    let raw: Option<Vec<u8>> = match data.strip_suffix("\nbytes") {
+        Some(value) if head.param("encoding") != Some("base64") => types::decode_bytes(value).ok(),
+        _ => None
+    };
+
+    let raw: Vec<u8> = match raw {
+        Some(raw) => raw,
+        None => return send(&head.rud, data, to)
+    };
+
+    let mut message: Vec<u8> = format!("rud: {}\nlength: {}\n", head.rud, raw.len()).into_bytes();
+    message.extend_from_slice(&raw);
+    message.extend_from_slice(b"\nbytes");
+
+    let cl: MutexGuard<'_, Clients> = CLIENTS.lock().unwrap();
+
+    let mut client: &TcpStream = match cl.writable.get(to) {
+        Some(client) => client,
+        None => return
+    };
+    if let Err(err) = client.write_all(&message) {
+        println!("[ ERROR ] Can't send a message to {} - {}", to, err);
+    }
+}
+
+/// Writes the response of a request in parts, one frame `rud: {rud}\n{data}\n{kind}` per flush.
+///
+/// Used to stream responses too large to be built at once, the request sends
+/// its closing response the usual way.
+pub struct Frames<'a> {
+    /// Request flag of the request.
+    rud: &'a str,
+
+    /// The address (Uuid) of the target client.
+    to: &'a str,
+
+    /// Last line of every frame, such as the data format.
+    kind: &'a str,
+
+    /// Data written since the last frame.
+    buf: Vec<u8>
+}
+
+impl<'a> Frames<'a> {
+    /// Creates the frames of a response sent to a client.
+    pub fn new(rud: &'a str, to: &'a str, kind: &'a str) -> Self {
+        Self { rud, to, kind, buf: Vec::new() }
+    }
+}
+
+impl Write for Frames<'_> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    /// Sends the data written since the last frame, fails if the client is gone.
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+
+        let mut message: Vec<u8> = format!("rud: {}\n", self.rud).into_bytes();
+        message.append(&mut self.buf);
+        message.push(b'\n');
+        message.extend_from_slice(self.kind.as_bytes());
+
+        let cl: MutexGuard<'_, Clients> = CLIENTS.lock().unwrap();
+        let mut client: &TcpStream = match cl.writable.get(self.to) {
+            Some(client) => client,
+            None => return Err(io::Error::new(io::ErrorKind::NotConnected, "the client is gone"))
+        };
+        client.write_all(&message)
+    }
+}
+
 /// Handles the communication with a connected client, receiving and processing messages.
 ///
 /// # Arguments
@@ -73,19 +178,24 @@ pub fn send(rud: &str, data: &str, to: &str) {
 fn handle_client(stream: TcpStream) {
     let address: String = add_stream(stream.try_clone().unwrap());
     loop {
-        let req: (String, String);
-        match receiver::deserialize(stream.try_clone().as_mut().unwrap(), &address) {
-            Ok(data) => {req = data},
+        let req: (String, Vec<u8>) = match receiver::deserialize(stream.try_clone().as_mut().unwrap(), &address) {
+            Ok(data) => data,
             Err(a) => {
                 let mut c: MutexGuard<'_, Clients> = CLIENTS.lock().unwrap();
                 c.writable.remove(&a);
+                drop(c);
+
+                // Nothing of an unfinished transaction is written
+                transaction::drop_session(&a);
+                cdc::drop_session(&a);
                 break;
             }
-        }
+        };
 
         let head: (String, RequestHeaders) = receiver::get_header(req.0);
+        let body: String = receiver::body_text(&head.1, &req.1);
 
-        add_tx(&head.0, head.1, &req.1, &address);
+        add_tx(&head.0, head.1, &body, &address);
     }
 }
 
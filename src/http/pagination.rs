@@ -0,0 +1,131 @@
+//! Cursor based pagination of table responses.
+//!
+//! A cursor is the hex encoded key of the last returned row. The next page
+//! starts right after that key in lexicographic order, so a cursor stays
+//! valid while rows are added or deleted.
+
+use std::ops::Bound;
+use serde::Serialize;
+
+use crate::{db::engine::KeyRange, http::receiver::RequestHeaders};
+
+/// Pagination requested through the `limit` and `cursor` headers.
+pub struct Page {
+    /// Max number of returned items.
+    pub limit: usize,
+
+    /// Key the page starts after.
+    pub after: Option<String>
+}
+
+/// One page of a paginated response.
+#[derive(Serialize)]
+pub struct PageResponse<T: Serialize> {
+    /// Items of the page.
+    pub data: Vec<T>,
+
+    /// Cursor of the next page, `null` on the last page.
+    pub next_cursor: Option<String>
+}
+
+impl Page {
+    /// Reads pagination from the request headers.
+    ///
+    /// # Returns
+    ///
+    /// Returns Ok(None) if the request is not paginated, Err with a response message if the headers are invalid.
+    pub fn from_headers(req: &RequestHeaders) -> Result<Option<Self>, String> {
+        if req.param("limit").is_none() && req.param("cursor").is_none() {
+            return Ok(None);
+        }
+
+        let limit: usize = match req.param("limit") {
+            Some(l) => match l.parse::<usize>() {
+                Ok(limit) if limit > 0 => limit,
+                _ => return Err("{\"code\": 400, \"message\": \"limit must be a positive number\"}\njson".to_string())
+            },
+            None => usize::MAX
+        };
+
+        let after: Option<String> = match req.param("cursor") {
+            Some(cursor) => match decode_cursor(cursor) {
+                Some(key) => Some(key),
+                None => return Err("{\"code\": 400, \"message\": \"invalid cursor\"}\njson".to_string())
+            },
+            None => None
+        };
+
+        Ok(Some(Self { limit, after }))
+    }
+
+    /// Builds the key range of the page, fetching one extra key to find out if there is a next page.
+    pub fn range(&self) -> KeyRange {
+        KeyRange {
+            start: match &self.after {
+                Some(key) => Bound::Excluded(key.clone()),
+                None => Bound::Unbounded
+            },
+            end: Bound::Unbounded,
+            limit: self.limit.saturating_add(1),
+            reverse: false
+        }
+    }
+
+    /// Cuts the fetched items to the page size.
+    ///
+    /// # Arguments
+    ///
+    /// * `items` - Items fetched with [`Page::range`], at most `limit + 1`.
+    /// * `key_of` - Returns the row key of an item.
+    ///
+    /// # Returns
+    ///
+    /// Returns the items of the page and the cursor of the next page.
+    pub fn split<T>(&self, mut items: Vec<T>, key_of: impl Fn(&T) -> &str) -> (Vec<T>, Option<String>) {
+        let mut next_cursor: Option<String> = None;
+
+        if items.len() > self.limit {
+            items.truncate(self.limit);
+            next_cursor = items.last().map(|item| encode_cursor(key_of(item)));
+        }
+
+        (items, next_cursor)
+    }
+
+    /// Cuts the fetched items to the page size and serializes the response.
+    pub fn response<T: Serialize>(&self, items: Vec<T>, key_of: impl Fn(&T) -> &str) -> String {
+        let (data, next_cursor): (Vec<T>, Option<String>) = self.split(items, key_of);
+
+        simd_json::to_string(&PageResponse { data, next_cursor }).unwrap() + "\njson"
+    }
+
+    /// Serializes the page of a sorted list of names, such as databases or tables.
+    pub fn names_response(&self, names: Vec<String>) -> String {
+        let range: KeyRange = self.range();
+        let names: Vec<String> = names.into_iter()
+            .filter(|name| range.contains(name))
+            .take(range.limit)
+            .collect();
+
+        self.response(names, |name| name.as_str())
+    }
+}
+
+/// Encodes a row key into an opaque cursor.
+pub fn encode_cursor(key: &str) -> String {
+    key.as_bytes().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Decodes a cursor back into a row key.
+pub fn decode_cursor(cursor: &str) -> Option<String> {
+    if !cursor.len().is_multiple_of(2) {
+        return None;
+    }
+
+    let bytes: Option<Vec<u8>> = (0..cursor.len())
+        .step_by(2)
+        .map(|i| cursor.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+        .collect();
+
+    bytes.and_then(|b| String::from_utf8(b).ok())
+}
@@ -1,12 +1,18 @@
 //! Module for deserializing TCP stream data.
 
-use std::{net::TcpStream, io::Read};
+use std::{net::TcpStream, io::Read, collections::HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::types;
 
 /// Number of sections expected in the transmission.
 const SECTIONS_IN_TX: i32 = 2;
 
+/// Max size of a body sent with a `length` header.
+const MAX_BODY_LEN: usize = 64 * 1024 * 1024;
+
 /// Represents the headers of a request.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct RequestHeaders {
     /// Request flag.
     pub rud: String,
@@ -21,7 +27,23 @@ pub struct RequestHeaders {
     pub key: String,
 
     /// Type of value data.
-    pub _type: String
+    pub _type: String,
+
+    /// Additional headers of the request.
+    #[serde(default)]
+    pub params: HashMap<String, String>
+}
+
+impl RequestHeaders {
+    /// Returns the value of an additional header.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(name).map(|v| v.as_str())
+    }
+
+    /// Checks if an additional header is set to `true` or `1`.
+    pub fn flag(&self, name: &str) -> bool {
+        matches!(self.param(name), Some("true") | Some("1"))
+    }
 }
 
 /// Parses a header string into a request type and [`RequestHeaders`].
@@ -56,20 +78,28 @@ pub fn get_header(header: String) -> (String, RequestHeaders) {
         db: String::new(),
         table: String::new(),
         key: String::new(),
-        _type: String::new()
+        _type: String::new(),
+        params: HashMap::new()
     };
 
     let req: Vec<&str> = header.split("\n").collect();
     for data in req {
-        let slice: Vec<&str> = data.split(" ").collect();
-
-        match slice[0] {
-            "req:" => {req_type = slice[1].to_string()},
-            "rud:" => {req_struct.rud = slice[1].to_string()},
-            "db:" => {req_struct.db = slice[1].to_string()},
-            "table:" => {req_struct.table = slice[1].to_string()},
-            "key:" => {req_struct.key = slice[1].to_string()}
-            "type:" => {req_struct._type = slice[1].to_string()}
+        // The value is the rest of the line, so it can hold spaces
+        let (name, value): (&str, &str) = match data.trim_end_matches('\r').split_once(' ') {
+            Some((name, value)) => (name, value),
+            None => continue
+        };
+
+        match name {
+            "req:" => {req_type = value.to_string()},
+            "rud:" => {req_struct.rud = value.to_string()},
+            "db:" => {req_struct.db = value.to_string()},
+            "table:" => {req_struct.table = value.to_string()},
+            "key:" => {req_struct.key = value.to_string()}
+            "type:" => {req_struct._type = value.to_string()}
+            name if name.ends_with(':') => {
+                req_struct.params.insert(name.trim_end_matches(':').to_string(), value.to_string());
+            },
             _ => {},
         }
     }
@@ -77,13 +107,39 @@ pub fn get_header(header: String) -> (String, RequestHeaders) {
     return (req_type, req_struct);
 }
 
-/// Deserializes a [`TcpStream`] into header and body string sections.
+/// Reads the `length` header of a serialized request header.
+fn body_length(header: &[u8]) -> Option<usize> {
+    String::from_utf8_lossy(header)
+        .lines()
+        .find_map(|line| line.trim_end_matches('\r').strip_prefix("length: ")?.trim().parse::<usize>().ok())
+}
+
+/// Converts a raw body into the text passed on to the request handlers.
+///
+/// A body of the `bytes` type is encoded into base64, unless the client sent it
+/// encoded already (`encoding: base64`). Any other body is read as trimmed UTF-8.
+///
+/// # Arguments
+///
+/// * `head` - Headers of the request.
+/// * `body` - Raw body of the request.
+pub fn body_text(head: &RequestHeaders, body: &[u8]) -> String {
+    if head._type == "bytes" && head.param("encoding") != Some("base64") {
+        return types::encode_bytes(body);
+    }
+
+    String::from_utf8_lossy(body).trim().to_string()
+}
+
+/// Deserializes a [`TcpStream`] into header and body sections.
 ///
 /// Reads bytes from the stream and separates into header and body sections,
-/// returning each section as a separate string.
+/// returning the header as a string and the body as raw bytes.
 ///
 /// Uses flags and counter to track current section. Each section starts
-/// with a 0x1 or 0x2 byte and ends with a 0x17 byte.
+/// with a 0x1 or 0x2 byte and ends with a 0x17 byte. If the header holds a
+/// `length: N` line, the body is read as exactly N bytes, so it may contain
+/// the section bytes too (binary values).
 ///
 /// # Arguments
 ///
@@ -94,8 +150,8 @@ pub fn get_header(header: String) -> (String, RequestHeaders) {
 ///
 /// [`Result`] containing:
 ///
-/// - Ok variant: Tuple with header and body [`String`]s
-/// - Err variant: Client [`address`] string on read error
+/// - Ok variant: Tuple with header [`String`] and body bytes
+/// - Err variant: Client [`address`] string on read error or when the client disconnected
 ///
 /// # Examples
 ///
@@ -106,7 +162,7 @@ pub fn get_header(header: String) -> (String, RequestHeaders) {
 /// let addr = stream.peer_addr()?;
 /// let (header, body) = deserialize(&mut stream, &addr)?;
 /// ```
-pub fn deserialize(mut stream: &TcpStream, address: &str) -> Result<(String, String), String> {
+pub fn deserialize(mut stream: &TcpStream, address: &str) -> Result<(String, Vec<u8>), String> {
     let mut is_header_section: bool = false;
     let mut is_body_section: bool = false;
     let mut section_number: i32 = 0;
@@ -120,6 +176,8 @@ pub fn deserialize(mut stream: &TcpStream, address: &str) -> Result<(String, Str
         let mut temp_buf: [u8; 1] = [0; 1];
         let bytes_read: usize;
         match stream.read(&mut temp_buf) {
+            // The client closed the connection
+            Ok(0) => {return Err(address.to_string());}
             Ok(size) => {bytes_read = size}
             Err(_) => {return Err(address.to_string());}
         }
@@ -129,7 +187,16 @@ pub fn deserialize(mut stream: &TcpStream, address: &str) -> Result<(String, Str
                 continue;
             },
             2 => {
-                is_body_section = true;
+                match body_length(&header_buf) {
+                    Some(length) if length > MAX_BODY_LEN => return Err(address.to_string()),
+                    Some(length) => {
+                        body_buf.resize(length, 0);
+                        if stream.read_exact(&mut body_buf).is_err() {
+                            return Err(address.to_string());
+                        }
+                    },
+                    None => {is_body_section = true}
+                }
                 continue;
             },
             23 => {
@@ -152,7 +219,6 @@ pub fn deserialize(mut stream: &TcpStream, address: &str) -> Result<(String, Str
     }
 
     let head_str: String = String::from_utf8_lossy(&header_buf).to_string();
-    let body_str: String = String::from_utf8_lossy(&body_buf).trim().to_string();
 
-    Ok((head_str, body_str))
+    Ok((head_str, body_buf))
 }
\ No newline at end of file
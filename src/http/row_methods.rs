@@ -1,6 +1,6 @@
-use crate::{db::{row, json_filter}, protos::row::Row, cache, types, http::receiver};
+use crate::{db::{checksum, engine::engine, json_filter, json_patch, names, schema, ttl, wal}, protos::row::Row, cache::{self, PutMode}, types, http::{receiver, pagination::Page}, tx_pool::transaction::{self, RowWrite}};
 
-use serde_json::Value;
+use serde_json::{json, Value};
 use simd_json::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -26,31 +26,148 @@ pub fn get(req: &receiver::RequestHeaders) -> Result<String, String> {
 
     match data {
         Ok(r) => {
-            let value = r.clone();
-            
-            return Ok(value.value().to_string() + "\n" + r.type_());
+            Ok(types::row_value(&r) + "\n" + r.type_())
+        },
+        Err(err) if checksum::is_corrupted(&err) => {
+            Err(json!({"code": 500, "message": err}).to_string() + "\njson")
         },
         Err(err) => {
-            return Err(err);
+            Err(err)
         }
     }
 }
 
+/// Max number of rows of one `multi_get` request.
+const MAX_MULTI_GET: usize = 1000;
+
+/// A row asked for by `multi_get`: a key of the header table, a `[table, key]` pair or a `{table, key}` object.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum MultiGetItem {
+    /// Key of a row of the header table.
+    Key(String),
+
+    /// Table name and key.
+    Pair(String, String),
+
+    /// Table name and key as an object.
+    Row { table: String, key: String }
+}
+
+/// Outcome of one row of `multi_get`.
+#[derive(Serialize, Debug)]
+pub struct MultiGetResult {
+    /// Table name.
+    pub table: String,
+
+    /// Row key.
+    pub key: String,
+
+    /// False if the row does not exist, is expired or can not be read.
+    pub found: bool,
+
+    /// Row value, base64 encoded for the `bytes` type.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+
+    /// Type of the value.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub _type: Option<String>,
+
+    /// Why the row can not be read, not set for a missing row.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>
+}
+
+/// Retrieves several rows of one database in one request.
+///
+/// The body lists the rows as keys of the header table, `[table, key]` pairs or
+/// `{"table": ..., "key": ...}` objects. The rows are served from the cache where possible.
+///
+/// # Arguments
+///
+/// * `req` - A reference to the `RequestHeaders` containing information about the request.
+/// * `body` - JSON array of the rows.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON array with the value and type of every row in the order
+/// they were asked for, a missing row has `found` set to false.
+pub fn multi_get(req: &receiver::RequestHeaders, body: &str) -> Result<String, String> {
+    let items: Vec<MultiGetItem> = match serde_json::from_str(body) {
+        Ok(items) => items,
+        Err(err) => return Err(json!({"code": 400, "message": err.to_string()}).to_string() + "\njson")
+    };
+
+    if items.len() > MAX_MULTI_GET {
+        return Err(json!({"code": 400, "message": format!("at most {} rows can be read at once", MAX_MULTI_GET)}).to_string() + "\njson");
+    }
+
+    let names: Vec<(String, String)> = items.into_iter()
+        .map(|item| match item {
+            MultiGetItem::Key(key) => (req.table.clone(), key),
+            MultiGetItem::Pair(table, key) | MultiGetItem::Row { table, key } => (table, key)
+        })
+        .collect();
+
+    // Names which can not be stored are reported without a lookup
+    let valid: Vec<(String, String)> = names.iter()
+        .filter(|(table, key)| names::check_row(&req.db, table, key).is_ok())
+        .cloned()
+        .collect();
+    let mut rows: std::vec::IntoIter<Result<Row, String>> = cache::get_many(&req.db, &valid).into_iter();
+
+    let results: Vec<MultiGetResult> = names.into_iter()
+        .map(|(table, key)| {
+            let mut result: MultiGetResult = MultiGetResult { table, key, found: false, value: None, _type: None, error: None };
+
+            if let Err(err) = names::check_row(&req.db, &result.table, &result.key) {
+                result.error = Some(err);
+                return result;
+            }
+
+            match rows.next().unwrap() {
+                Ok(row) => {
+                    result.found = true;
+                    result.value = Some(types::row_value(&row));
+                    result._type = Some(row.type_().to_string());
+                },
+                Err(err) if checksum::is_corrupted(&err) => result.error = Some(err),
+                Err(_) => {}
+            }
+
+            result
+        })
+        .collect();
+
+    Ok(serde_json::to_string(&results).unwrap() + "\njson")
+}
+
 //TODO: filtering non json
+/// Filters the json rows of the specified table with a json object.
+///
+/// With a `limit` or `cursor` header only one page of matches is returned along with the `next_cursor`.
+///
+/// # Arguments
+///
+/// * `req` - A reference to the `RequestHeaders` containing information about the request.
+/// * `data` - The filter json object.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON string with the matching rows or an error message.
 pub fn filter(req: &receiver::RequestHeaders, data: &str) -> Result<String, String> {
-    if req._type == "json" {
-        match json_filter::filter(&req.db, &req.table, data) {
-            Ok(d) => {
-                let str = serde_json::to_string(&d).unwrap();
-                return Ok(str);
-            },
-            Err(e) => {
-                return Err(e);
-            }
-        }
-    } else {
-        Err("Now support only json filtering".to_string())
+    if req._type != "json" {
+        return Err("Now support only json filtering".to_string());
     }
+
+    if let Some(page) = Page::from_headers(req)? {
+        let matches: Vec<Bunch> = json_filter::filter_range(&req.db, &req.table, data, &page.range())?;
+        return Ok(page.response(matches, |b| b.key.as_str()));
+    }
+
+    let matches: Vec<Bunch> = json_filter::filter(&req.db, &req.table, data)?;
+    Ok(serde_json::to_string(&matches).unwrap())
 }
 
 /// Adds a new key-value pair to the specified table and database.
@@ -72,7 +189,9 @@ pub fn add(req: &receiver::RequestHeaders, value: &str) -> Result<String, String
         }
     }
 
-    let status: bool = cache::add(&req.db, &req.table, &req.key, &value, &req._type);
+    let row: Row = new_row(req, value)?;
+    schema::check(&req.db, &req.table, &row)?;
+    let status: bool = cache::add_row(&req.db, &req.table, &req.key, &row);
 
     if status {
         return Ok("{\"code\": 200, \"message\": \"New value was add\"}\njson".to_string());
@@ -81,8 +200,271 @@ pub fn add(req: &receiver::RequestHeaders, value: &str) -> Result<String, String
     }
 }
 
+/// Reports the remaining lifetime of a row in seconds.
+///
+/// # Arguments
+///
+/// * `req` - A reference to the `RequestHeaders` containing information about the request.
+///
+/// # Returns
+///
+/// A `Result` containing the seconds left, `-1` if the row never expires, or an error if there is no such row.
+pub fn ttl(req: &receiver::RequestHeaders) -> Result<String, String> {
+    let row: Row = cache::get(&req.db, &req.table, &req.key)?;
+
+    match ttl::remaining(&row) {
+        Some(secs) => Ok(secs.to_string() + "\nint"),
+        None => Ok("-1\nint".to_string())
+    }
+}
+
+/// Builds a row from the request value, type and expiry headers.
+pub fn new_row(req: &receiver::RequestHeaders, value: &str) -> Result<Row, String> {
+    let mut row: Row = Row::new();
+    types::set_row_value(&mut row, value, &req._type)?;
+
+    if let Some(at) = ttl::from_headers(req)? {
+        row.set_expires_at(at);
+    }
+
+    Ok(row)
+}
+
+/// Writes a row with the mode given in the `mode` header: `nx`, `xx` or `upsert` (default).
+///
+/// # Arguments
+///
+/// * `req` - A reference to the `RequestHeaders` containing information about the request.
+/// * `value` - The value to be written.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON status message or an error message.
+pub fn put(req: &receiver::RequestHeaders, value: &str) -> Result<String, String> {
+    let mode: PutMode = match PutMode::parse(req.param("mode").unwrap_or("upsert")) {
+        Some(mode) => mode,
+        None => return Err("{\"code\": 400, \"message\": \"mode must be nx, xx or upsert\"}\njson".to_string())
+    };
+
+    write(req, value, mode)
+}
+
+/// Replaces the value of an existing row, fails if the key does not exist.
+///
+/// # Arguments
+///
+/// * `req` - A reference to the `RequestHeaders` containing information about the request.
+/// * `value` - The new value.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON status message or an error message.
+pub fn update(req: &receiver::RequestHeaders, value: &str) -> Result<String, String> {
+    write(req, value, PutMode::Update)
+}
+
+/// Validates the value and writes the row with the given mode.
+fn write(req: &receiver::RequestHeaders, value: &str, mode: PutMode) -> Result<String, String> {
+    if let Err(err) = types::is_valid_data(value, &req._type) {
+        return Err(("{\"code\": 400, \"message\": \"".to_string() + &err + "\"}\njson").to_string());
+    }
+
+    let row: Row = new_row(req, value)?;
+    schema::check(&req.db, &req.table, &row)?;
+
+    match cache::put(&req.db, &req.table, &req.key, &row, mode) {
+        Ok(true) => Ok("{\"code\": 200, \"message\": \"Value was written\"}\njson".to_string()),
+        Ok(false) if mode == PutMode::Insert => Err("{\"code\": 409, \"message\": \"key already exists\"}\njson".to_string()),
+        Ok(false) => Err("{\"code\": 404, \"message\": \"key does not exist\"}\njson".to_string()),
+        Err(err) => Err(("{\"code\": 500, \"message\": \"".to_owned() + &err + "\"}\njson").to_string())
+    }
+}
+
+/// Applies a JSON merge patch (RFC 7396) or a JSON patch (RFC 6902) to a stored json row.
+///
+/// The kind of patch is taken from the `patch` header (`merge` or `json`), or detected
+/// from the body: an array is a JSON patch, an object a merge patch. The patched
+/// row is logged as a `put_row` by [`change_row`].
+///
+/// # Arguments
+///
+/// * `req` - A reference to the `RequestHeaders` containing information about the request.
+/// * `body` - The patch document.
+///
+/// # Returns
+///
+/// A `Result` containing the patched value or an error message.
+pub fn patch(req: &receiver::RequestHeaders, body: &str) -> Result<String, String> {
+    let patch: Value = match serde_json::from_str(body) {
+        Ok(patch) => patch,
+        Err(err) => return Err(("{\"code\": 400, \"message\": \"".to_string() + &err.to_string() + "\"}\njson").to_string())
+    };
+
+    let result: Result<Row, String> = change_row(req, |current| {
+        patch_row(req, current.ok_or("key does not exist")?, &patch)
+    });
+
+    match result {
+        Ok(row) => Ok(row.value().to_string() + "\n" + row.type_()),
+        Err(err) if err.ends_with("\njson") => Err(err),
+        Err(err) => Err(("{\"code\": 400, \"message\": \"".to_string() + &err.replace('"', "'") + "\"}\njson").to_string())
+    }
+}
+
+/// Adds the delta in the body (1 if empty) to a numeric row atomically and returns the new value.
+///
+/// A missing row is created with the value 0 and the type of the request (`int` if none is given)
+/// before the delta is added. An existing row keeps its type, `decr` subtracts the delta, which
+/// it takes without a sign.
+///
+/// # Arguments
+///
+/// * `req` - A reference to the `RequestHeaders` containing information about the request.
+/// * `body` - The delta.
+/// * `decr` - Subtract the delta instead of adding it.
+///
+/// # Returns
+///
+/// A `Result` containing the new value and type or an error message.
+pub fn incr(req: &receiver::RequestHeaders, body: &str, decr: bool) -> Result<String, String> {
+    let delta: &str = match body.trim() {
+        "" => "1",
+        delta => delta
+    };
+    if decr && delta.starts_with(['-', '+']) {
+        return Err(json!({"code": 400, "message": "decr takes the delta without a sign"}).to_string() + "\njson");
+    }
+    let delta: String = match decr {
+        true => format!("-{}", delta),
+        false => delta.strip_prefix('+').unwrap_or(delta).to_string()
+    };
+
+    let result: Result<Row, String> = change_row(req, |current| {
+        let mut row: Row = match current {
+            Some(row) => row,
+            None => {
+                let _type: &str = if req._type.is_empty() { "int" } else { &req._type };
+                if !types::is_numeric(_type) {
+                    return Err(format!("{} rows can not be incremented", _type));
+                }
+
+                let mut row: Row = Row::new();
+                types::set_row_value(&mut row, "0", _type)?;
+                row
+            }
+        };
+
+        let value: String = types::add_number(row.value(), row.type_(), &delta)?;
+        row.set_value(value);
+        Ok(row)
+    });
+
+    match result {
+        Ok(row) => Ok(row.value().to_string() + "\n" + row.type_()),
+        Err(err) if err.ends_with("\njson") => Err(err),
+        Err(err) => Err(json!({"code": 400, "message": err}).to_string() + "\njson")
+    }
+}
+
+/// Changes or creates one row in place under its key lock.
+///
+/// The `ttl` and `expires_at` headers set the expiry time of the changed row
+/// and the table schema is checked. The new row is logged to the write-ahead
+/// log as a `put_row` under the key lock, so replaying the log after a crash
+/// does not apply the change twice.
+///
+/// # Arguments
+///
+/// * `req` - A reference to the `RequestHeaders` naming the row.
+/// * `change` - Builds the new row from the current one, None if the key does not exist.
+///
+/// # Returns
+///
+/// A `Result` containing the stored row or an error message, a JSON response if it ends with `json`.
+pub fn change_row<F>(req: &receiver::RequestHeaders, change: F) -> Result<Row, String>
+where
+    F: FnOnce(Option<Row>) -> Result<Row, String>
+{
+    let expires_at: Option<u64> = ttl::from_headers(req)?;
+    let mut logged: Option<u64> = None;
+
+    let result: Result<Row, String> = cache::upsert(&req.db, &req.table, &req.key, |current| {
+        let mut row: Row = change(current)?;
+        if let Some(at) = expires_at {
+            row.set_expires_at(at);
+        }
+        schema::check(&req.db, &req.table, &row)?;
+
+        if engine().is_persistent() {
+            let mut head: receiver::RequestHeaders = req.clone();
+            head._type = row.type_().to_string();
+            head.params.retain(|name, _| name != "ttl" && name != "expires_at" && name != "mode");
+            if row.has_expires_at() {
+                head.params.insert("expires_at".to_string(), row.expires_at().to_string());
+            }
+            logged = Some(wal::begin("put_row", &head, &types::row_value(&row)).map_err(|err| {
+                println!("{}", err);
+                "{\"code\": 500, \"message\": \"Can not write to WAL\"}\njson".to_string()
+            })?);
+        }
+
+        Ok(row)
+    });
+
+    if let Some(id) = logged {
+        wal::commit(id);
+    }
+
+    result
+}
+
+/// Applies a patch document to a json row, the kind of patch is taken from the `patch` header.
+///
+/// # Returns
+///
+/// The patched row, or an error message if the row is not json or the patch does not apply.
+pub fn patch_row(req: &receiver::RequestHeaders, mut row: Row, patch: &Value) -> Result<Row, String> {
+    if row.type_() != "json" {
+        return Err("only json rows can be patched".to_string());
+    }
+
+    let doc: Value = serde_json::from_str(row.value()).map_err(|e| e.to_string())?;
+    let value: String = json_patch::patch(&doc, patch, req.param("patch"))?.to_string();
+    types::is_valid_data(&value, row.type_())?;
+
+    row.set_value(value);
+    Ok(row)
+}
+
+/// Outcome of one element of a bunch.
+#[derive(Serialize, Debug)]
+pub struct BunchResult {
+    /// Position of the element in the bunch.
+    pub index: usize,
+
+    /// Row key.
+    pub key: String,
+
+    /// `written`, `invalid`, `exists` or `not_written`.
+    pub status: String,
+
+    /// Why the element is invalid or was not written.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    /// Schema violations of the element.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub violations: Vec<schema::Violation>
+}
+
 /// Adds a bunch of key-value pairs to the specified table and database.
 ///
+/// Every element is validated before anything is written and every key has
+/// to be new. By default the bunch is written as a whole: if one element is
+/// invalid, its key exists or one write fails, no row is written. With the
+/// `best_effort` header the valid elements are written and the others are
+/// reported.
+///
 /// # Arguments
 ///
 /// * `req` - A reference to the `RequestHeaders` containing information about the request.
@@ -90,31 +472,132 @@ pub fn add(req: &receiver::RequestHeaders, value: &str) -> Result<String, String
 ///
 /// # Returns
 ///
-/// A `Result` indicating success or an error message.
+/// A `Result` containing a JSON response with the outcome of every element.
 pub fn bunch(req: &receiver::RequestHeaders, value: &str) -> Result<String, String> {
-    let res: Result<Vec<Bunch>, serde_json::Error> = serde_json::from_str::<Vec<Bunch>>(&value);
-    let mut bunch: Vec<Bunch> = Vec::with_capacity(1024);
+    let (rows, results): (Vec<RowWrite>, Vec<BunchResult>) = check_bunch(req, value)?;
 
-    match res {
-        Ok(b) => {bunch = b},
-        Err(err) => {
-            println!("{}", err);
-            return Ok("0".to_string())
+    match transaction::write_rows(&req.db, &rows) {
+        Ok(written) => Ok(bunch_response(200, "bunch was add", written, results)),
+        Err(err) => Err(bunch_response(500, &format!("nothing was written - {}", err), 0, results))
+    }
+}
+
+/// Validates the elements of a bunch and builds the rows to write.
+///
+/// The caller has to keep other writers out until the rows are written,
+/// otherwise a key found to be new could be written in the meantime.
+///
+/// # Returns
+///
+/// The rows to write with the outcome of every element, or the error response if the bunch is refused.
+pub fn check_bunch(req: &receiver::RequestHeaders, value: &str) -> Result<(Vec<RowWrite>, Vec<BunchResult>), String> {
+    let bunch: Vec<Bunch> = match serde_json::from_str::<Vec<Bunch>>(value) {
+        Ok(bunch) => bunch,
+        Err(err) => return Err(json!({"code": 400, "message": err.to_string()}).to_string() + "\njson")
+    };
+
+    let best_effort: bool = req.flag("best_effort");
+    let expires_at: Option<u64> = ttl::from_headers(req)?;
+
+    let mut results: Vec<BunchResult> = Vec::with_capacity(bunch.len());
+    let mut rows: Vec<RowWrite> = Vec::with_capacity(bunch.len());
+    for (index, elem) in bunch.iter().enumerate() {
+        let mut result: BunchResult = BunchResult {
+            index,
+            key: elem.key.clone(),
+            status: "written".to_string(),
+            error: None,
+            violations: Vec::new()
+        };
+
+        // A key repeated in the bunch exists once its first element is written
+        let repeated: bool = rows.iter().any(|(_, key, _)| *key == elem.key);
+        match bunch_row(req, elem, expires_at) {
+            Ok(_) if repeated => {
+                result.status = "exists".to_string();
+                result.error = Some("key is repeated in the bunch".to_string());
+            },
+            Ok(row) => match cache::get(&req.db, &req.table, &elem.key) {
+                Ok(_) => {
+                    result.status = "exists".to_string();
+                    result.error = Some("key already exists".to_string());
+                },
+                Err(err) if checksum::is_corrupted(&err) => {
+                    result.status = "invalid".to_string();
+                    result.error = Some(err);
+                },
+                Err(_) => rows.push((req.table.clone(), elem.key.clone(), Some(row)))
+            },
+            Err((error, violations)) => {
+                result.status = "invalid".to_string();
+                result.error = Some(error);
+                result.violations = violations;
+            }
         }
+
+        results.push(result);
     }
 
-    let status: bool = bunch.iter().all(|elem| {
-        let mut row: Row = Row::new();
-        row.set_value(elem.value.to_string());
-        row.set_type(elem._type.to_string());
-        row::add_row(&req.db, &req.table, &elem.key, &mut row)
-    });
+    if rows.len() < results.len() && !best_effort {
+        let code: u16 = if results.iter().any(|r| !r.violations.is_empty()) {
+            422
+        } else if results.iter().any(|r| r.status == "exists") {
+            409
+        } else {
+            400
+        };
+        return Err(bunch_response(code, "bunch has invalid rows, nothing was written", 0, results));
+    }
 
-    if status {
-        return Ok("bunch was add".to_string());
-    } else {
-        return Ok(format!("can't add bunch"));
+    Ok((rows, results))
+}
+
+/// Validates one element of a bunch and builds its row.
+///
+/// # Returns
+///
+/// The row, or the error with the schema violations of the element.
+fn bunch_row(req: &receiver::RequestHeaders, elem: &Bunch, expires_at: Option<u64>) -> Result<Row, (String, Vec<schema::Violation>)> {
+    names::check_key(&elem.key).map_err(|err| (err, Vec::new()))?;
+
+    let value: String = match &elem.value {
+        Value::String(s) if elem._type == "bytes" => s.clone(),
+        value => value.to_string()
+    };
+    types::is_valid_data(&value, &elem._type).map_err(|err| (err, Vec::new()))?;
+
+    let mut row: Row = Row::new();
+    types::set_row_value(&mut row, &value, &elem._type).map_err(|err| (err, Vec::new()))?;
+    if let Some(at) = expires_at {
+        row.set_expires_at(at);
+    }
+
+    let violations: Vec<schema::Violation> = schema::violations(&req.db, &req.table, &row);
+    if !violations.is_empty() {
+        return Err(("value does not match the table schema".to_string(), violations));
+    }
+
+    Ok(row)
+}
+
+/// Builds the JSON response of a bunch, the rows of a failed bunch are marked as not written.
+///
+/// Elements which are invalid or whose key exists are counted as invalid.
+pub fn bunch_response(code: u16, message: &str, written: usize, mut results: Vec<BunchResult>) -> String {
+    let invalid: usize = results.iter().filter(|r| r.status != "written").count();
+    if written == 0 {
+        for result in results.iter_mut().filter(|r| r.status == "written") {
+            result.status = "not_written".to_string();
+        }
     }
+
+    json!({
+        "code": code,
+        "message": message,
+        "written": written,
+        "invalid": invalid,
+        "results": results
+    }).to_string() + "\njson"
 }
 
 // pub fn edit(req: Request<Body>) -> Result<Response<Body>, Infallible> {
@@ -142,4 +625,78 @@ pub fn delete(req: &receiver::RequestHeaders) -> Result<String, String> {
         Ok(_) => Ok("{\"code\": 200, \"message\": \"Row was delete\"}\njson".to_string()),
         Err(err) => Ok(("{\"code\": 400, \"message\": \"".to_owned()+ &err +"\"}\njson").to_string())
     }
-}
\ No newline at end of file
+}
+
+/// Deletes the rows of the keys listed in the body from the specified table.
+///
+/// With the `dry_run` header nothing is deleted, the response lists the rows which would be.
+///
+/// # Arguments
+///
+/// * `req` - A reference to the `RequestHeaders` containing information about the request.
+/// * `body` - JSON array of keys.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON response with the number and the keys of the deleted rows.
+pub fn delete_many(req: &receiver::RequestHeaders, body: &str) -> Result<String, String> {
+    let keys: Vec<String> = match serde_json::from_str(body) {
+        Ok(keys) => keys,
+        Err(err) => return Err(json!({"code": 400, "message": err.to_string()}).to_string() + "\njson")
+    };
+
+    // Expired rows which are not reaped yet are not counted
+    let keys: Vec<String> = keys.into_iter()
+        .filter(|key| matches!(engine().read_row(&req.db, &req.table, key), Ok(Some(row)) if !ttl::is_expired(&row)))
+        .collect();
+
+    Ok(delete_keys(req, keys))
+}
+
+/// Deletes the json rows of the specified table which match the filter in the body.
+///
+/// The filter is the same json object `filter_row` takes. With the `dry_run` header
+/// nothing is deleted, the response lists the rows which would be.
+///
+/// # Arguments
+///
+/// * `req` - A reference to the `RequestHeaders` containing information about the request.
+/// * `body` - The filter json object.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON response with the number and the keys of the deleted rows.
+pub fn delete_where(req: &receiver::RequestHeaders, body: &str) -> Result<String, String> {
+    let keys: Vec<String> = json_filter::filter(&req.db, &req.table, body)?
+        .into_iter()
+        .map(|row| row.key)
+        .collect();
+
+    Ok(delete_keys(req, keys))
+}
+
+/// Deletes the rows of the keys from the storage and the cache, unless the request is a dry run.
+fn delete_keys(req: &receiver::RequestHeaders, keys: Vec<String>) -> String {
+    let dry_run: bool = req.flag("dry_run");
+    let matched: usize = keys.len();
+
+    let keys: Vec<String> = if dry_run {
+        keys
+    } else {
+        keys.into_iter()
+            .filter(|key| cache::delete(&req.db, &req.table, key).is_ok())
+            .collect()
+    };
+
+    if !dry_run {
+        println!("[ INFO ] Row: deleted {} rows of {}/{}", keys.len(), req.db, req.table);
+    }
+
+    json!({
+        "code": 200,
+        "dry_run": dry_run,
+        "matched": matched,
+        "deleted": if dry_run { 0 } else { keys.len() },
+        "keys": keys
+    }).to_string() + "\njson"
+}
@@ -1,9 +1,16 @@
-use crate::{db::table::{create_table, get_table, get_table_with_keys}, cache::delete_table};
+use std::ops::Bound;
 
-use super::{row_methods::Bunch, receiver};
+use std::{fs::{self, File}, io::{BufReader, BufWriter, Cursor}, path::Path};
+use serde_json::{json, Value};
+
+use crate::{config, db::{engine::{engine, KeyRange}, index, names, schema, stats, transfer::{self, CsvColumns, Format}, ttl, verify}, protos::row::Row, cache::{self, delete_table, PutMode}, types};
+
+use super::{row_methods::Bunch, receiver, pagination::{Page, PageResponse}, Frames};
 
 /// Retrieves all keys in the specified table and database.
 ///
+/// With a `limit` or `cursor` header only one page of keys is returned along with the `next_cursor`.
+///
 /// # Arguments
 ///
 /// * `req` - A reference to the `RequestHeaders` containing information about the request.
@@ -12,7 +19,18 @@ use super::{row_methods::Bunch, receiver};
 ///
 /// A `Result` containing a JSON string with the retrieved keys or an error message.
 pub fn get(req: &receiver::RequestHeaders) -> Result<String, String> {
-    let status: Result<Vec<String>, String> = get_table(&req.db, &req.table);
+    if !engine().table_exists(&req.db, &req.table) {
+        return Ok("{\"code\": 400, \"message\": \"no such table\"}\njson".to_string());
+    }
+
+    if let Some(page) = Page::from_headers(req)? {
+        return match ttl::scan_rows(&req.db, &req.table, &page.range()) {
+            Ok(keys) => Ok(page.response(keys, |k| k.as_str())),
+            Err(err) => Ok(err)
+        };
+    }
+
+    let status: Result<Vec<String>, String> = ttl::list_rows(&req.db, &req.table);
 
     match status {
         Ok(mut data) => {
@@ -28,6 +46,8 @@ pub fn get(req: &receiver::RequestHeaders) -> Result<String, String> {
 
 /// Retrieves all keys along with their values and types in the specified table and database.
 ///
+/// With a `limit` or `cursor` header only one page of rows is returned along with the `next_cursor`.
+///
 /// # Arguments
 ///
 /// * `req` - A reference to the `RequestHeaders` containing information about the request.
@@ -36,10 +56,34 @@ pub fn get(req: &receiver::RequestHeaders) -> Result<String, String> {
 ///
 /// A `Result` containing a JSON string with the retrieved keys, values, and types or an error message.
 pub fn get_with_keys(req: &receiver::RequestHeaders) -> Result<String, String> {
-    let status: Result<Vec<Bunch>, String> = get_table_with_keys(&req.db, &req.table);
+    if !engine().table_exists(&req.db, &req.table) {
+        return Ok("{\"code\": 400, \"message\": \"no such table\"}\njson".to_string());
+    }
+
+    if let Some(page) = Page::from_headers(req)? {
+        let keys: Vec<String> = match ttl::scan_rows(&req.db, &req.table, &page.range()) {
+            Ok(keys) => keys,
+            Err(err) => return Ok(err)
+        };
+
+        let (keys, next_cursor): (Vec<String>, Option<String>) = page.split(keys, |k| k.as_str());
+        let data: Vec<Bunch> = read_bunch(req, keys);
+
+        return Ok(simd_json::to_string(&PageResponse { data, next_cursor }).unwrap() + "\njson");
+    }
+
+    let status: Result<Vec<(String, Row)>, String> = engine().read_table(&req.db, &req.table);
 
     match status {
-        Ok(mut data) => {
+        Ok(rows) => {
+            let mut data: Vec<Bunch> = rows.into_iter()
+                .filter(|(_, row)| !ttl::is_expired(row))
+                .map(|(key, row)| Bunch {
+                    key,
+                    value: types::row_value(&row).into(),
+                    _type: row.type_().to_string(),
+                })
+                .collect();
             let s: String = simd_json::to_string(&mut data).unwrap();
         
             return Ok(s + "\njson");
@@ -60,7 +104,7 @@ pub fn get_with_keys(req: &receiver::RequestHeaders) -> Result<String, String> {
 ///
 /// A `Result` indicating success (1) or failure (0).
 pub fn create(req: &receiver::RequestHeaders) -> Result<String, String> {
-    let status = create_table(&req.db, &req.table);
+    let status = engine().create_table(&req.db, &req.table);
 
     if status {
         Ok("1".to_string())
@@ -86,4 +130,323 @@ pub fn delete(req: &receiver::RequestHeaders) -> Result<String, String>  {
     } else {
         Ok("0".to_string())
     }
-}
\ No newline at end of file
+}
+
+/// Lists the names of all tables of the specified database.
+///
+/// With a `limit` or `cursor` header only one page of names is returned along with the `next_cursor`.
+///
+/// # Arguments
+///
+/// * `req` - A reference to the `RequestHeaders` containing information about the request.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON array of names or an error message.
+pub fn list(req: &receiver::RequestHeaders) -> Result<String, String> {
+    if !engine().db_exists(&req.db) {
+        return Err("{\"code\": 400, \"message\": \"DataBase not exist\"}\njson".to_string());
+    }
+
+    let tables: Vec<String> = match engine().list_tables(&req.db) {
+        Ok(tables) => tables,
+        Err(err) => return Err(json!({"code": 500, "message": err}).to_string() + "\njson")
+    };
+
+    match Page::from_headers(req)? {
+        Some(page) => Ok(page.names_response(tables)),
+        None => Ok(json!(tables).to_string() + "\njson")
+    }
+}
+
+/// Renames the specified table to the name in the `to` header.
+///
+/// The rename runs while no other write does, the indexes, schema, deadlines and cached rows move along.
+///
+/// # Arguments
+///
+/// * `req` - A reference to the `RequestHeaders` containing information about the request.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON status message or an error message.
+pub fn rename(req: &receiver::RequestHeaders) -> Result<String, String> {
+    let to: &str = match req.param("to") {
+        Some(to) => to,
+        None => return Err("{\"code\": 400, \"message\": \"to header is required\"}\njson".to_string())
+    };
+
+    match cache::rename_table(&req.db, &req.table, to) {
+        Ok(_) => Ok("{\"code\": 200, \"message\": \"Table was renamed\"}\njson".to_string()),
+        Err(err) => Err(json!({"code": 400, "message": err}).to_string() + "\njson")
+    }
+}
+
+/// Retrieves the keys of the specified table which fall into a range, in lexicographic order.
+///
+/// Headers: `start` (inclusive), `end` (exclusive), `limit`, `reverse` and `values`
+/// to return the values and types along with the keys.
+///
+/// # Arguments
+///
+/// * `req` - A reference to the `RequestHeaders` containing information about the request.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON string with the keys (or key, value and type objects) or an error message.
+pub fn scan_range(req: &receiver::RequestHeaders) -> Result<String, String> {
+    if !engine().table_exists(&req.db, &req.table) {
+        return Ok("{\"code\": 400, \"message\": \"no such table\"}\njson".to_string());
+    }
+
+    let limit: usize = match req.param("limit") {
+        Some(l) => match l.parse::<usize>() {
+            Ok(limit) => limit,
+            Err(_) => return Err("{\"code\": 400, \"message\": \"limit must be a positive number\"}\njson".to_string())
+        },
+        None => usize::MAX
+    };
+
+    let range: KeyRange = KeyRange {
+        start: req.param("start").map(|s| Bound::Included(s.to_string())).unwrap_or(Bound::Unbounded),
+        end: req.param("end").map(|s| Bound::Excluded(s.to_string())).unwrap_or(Bound::Unbounded),
+        limit,
+        reverse: req.flag("reverse")
+    };
+
+    let keys: Vec<String> = match ttl::scan_rows(&req.db, &req.table, &range) {
+        Ok(keys) => keys,
+        Err(err) => return Err(("{\"code\": 400, \"message\": \"".to_owned() + &err + "\"}\njson").to_string())
+    };
+
+    if !req.flag("values") {
+        let s: String = simd_json::to_string(&keys).unwrap();
+        return Ok(s + "\njson");
+    }
+
+    let data: Vec<Bunch> = read_bunch(req, keys);
+
+    let s: String = simd_json::to_string(&data).unwrap();
+    Ok(s + "\njson")
+}
+
+/// Reads the rows of the given keys, skipping the ones deleted in the meantime.
+fn read_bunch(req: &receiver::RequestHeaders, keys: Vec<String>) -> Vec<Bunch> {
+    keys.into_iter()
+        .filter_map(|key| {
+            let row: Row = cache::get(&req.db, &req.table, &key).ok()?;
+            Some(Bunch {
+                key,
+                value: types::row_value(&row).into(),
+                _type: row.type_().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Creates an index on the JSON path given in the `path` header of the specified table.
+///
+/// # Arguments
+///
+/// * `req` - A reference to the `RequestHeaders` containing information about the request.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON status message or an error message.
+pub fn create_index(req: &receiver::RequestHeaders) -> Result<String, String> {
+    let path: &str = match req.param("path") {
+        Some(path) => path,
+        None => return Err("{\"code\": 400, \"message\": \"path header is required\"}\njson".to_string())
+    };
+
+    match index::create(&req.db, &req.table, path) {
+        Ok(true) => Ok("{\"code\": 200, \"message\": \"Index was created\"}\njson".to_string()),
+        Ok(false) => Ok("{\"code\": 200, \"message\": \"Index already exists\"}\njson".to_string()),
+        Err(err) => Err(("{\"code\": 400, \"message\": \"".to_owned() + &err + "\"}\njson").to_string())
+    }
+}
+
+/// Drops the index on the JSON path given in the `path` header of the specified table.
+///
+/// # Arguments
+///
+/// * `req` - A reference to the `RequestHeaders` containing information about the request.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON status message or an error message.
+pub fn drop_index(req: &receiver::RequestHeaders) -> Result<String, String> {
+    let path: &str = match req.param("path") {
+        Some(path) => path,
+        None => return Err("{\"code\": 400, \"message\": \"path header is required\"}\njson".to_string())
+    };
+
+    match index::drop(&req.db, &req.table, path) {
+        Ok(true) => Ok("{\"code\": 200, \"message\": \"Index was dropped\"}\njson".to_string()),
+        Ok(false) => Err("{\"code\": 400, \"message\": \"no such index\"}\njson".to_string()),
+        Err(err) => Err(("{\"code\": 500, \"message\": \"".to_owned() + &err + "\"}\njson").to_string())
+    }
+}
+
+/// Reports the row count, value bytes, per-type breakdown, creation and last write time
+/// and cached rows of the specified table.
+///
+/// # Arguments
+///
+/// * `req` - A reference to the `RequestHeaders` containing information about the request.
+///
+/// # Returns
+///
+/// A `Result` containing the statistics as JSON or an error message.
+pub fn stats(req: &receiver::RequestHeaders) -> Result<String, String> {
+    match stats::table_stats(&req.db, &req.table) {
+        Ok(stats) => Ok(json!(stats).to_string() + "\njson"),
+        Err(err) => Err(json!({"code": 400, "message": err}).to_string() + "\njson")
+    }
+}
+
+/// Reads every row of the specified table from the storage and reports the damaged ones.
+///
+/// # Arguments
+///
+/// * `req` - A reference to the `RequestHeaders` containing information about the request.
+///
+/// # Returns
+///
+/// A `Result` containing the report as JSON or an error message.
+pub fn verify(req: &receiver::RequestHeaders) -> Result<String, String> {
+    match verify::verify_table(&req.db, &req.table) {
+        Ok(report) => Ok(json!(report).to_string() + "\njson"),
+        Err(err) => Err(json!({"code": 400, "message": err}).to_string() + "\njson")
+    }
+}
+
+/// Attaches the JSON Schema in the body to the specified table, a `null` body removes it.
+///
+/// # Arguments
+///
+/// * `req` - A reference to the `RequestHeaders` containing information about the request.
+/// * `body` - The JSON Schema.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON status message or an error message.
+pub fn set_schema(req: &receiver::RequestHeaders, body: &str) -> Result<String, String> {
+    let value: Value = match serde_json::from_str(body) {
+        Ok(value) => value,
+        Err(err) => return Err(json!({"code": 400, "message": err.to_string()}).to_string() + "\njson")
+    };
+
+    match schema::set(&req.db, &req.table, value) {
+        Ok(_) => Ok("{\"code\": 200, \"message\": \"Schema was set\"}\njson".to_string()),
+        Err(err) => Err(json!({"code": 400, "message": err}).to_string() + "\njson")
+    }
+}
+
+/// Retrieves the JSON Schema of the specified table.
+///
+/// # Arguments
+///
+/// * `req` - A reference to the `RequestHeaders` containing information about the request.
+///
+/// # Returns
+///
+/// A `Result` containing the schema or an error message if the table has none.
+pub fn get_schema(req: &receiver::RequestHeaders) -> Result<String, String> {
+    match schema::get(&req.db, &req.table) {
+        Some(value) => Ok(value.to_string() + "\njson"),
+        None => Err("{\"code\": 404, \"message\": \"table has no schema\"}\njson".to_string())
+    }
+}
+
+/// Exports the specified table as NDJSON or CSV.
+///
+/// Headers: `format` (`ndjson` by default or `csv`), `columns` and `key_column` for CSV,
+/// and `path` to write the export to a file on the server, relative to its transfer directory.
+///
+/// Without a path the rows are streamed to the session while they are read, in frames of the
+/// response ending with the format line, and a JSON report closes the response.
+///
+/// # Arguments
+///
+/// * `req` - A reference to the `RequestHeaders` containing information about the request.
+/// * `session` - The client session the rows are streamed to.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON report with the number of exported and skipped rows.
+pub fn export(req: &receiver::RequestHeaders, session: &str) -> Result<String, String> {
+    let format: Format = transfer_format(req)?;
+    let columns: CsvColumns = CsvColumns::parse(req.param("key_column"), req.param("columns"));
+
+    let path: String = match req.param("path") {
+        Some(path) => transfer_path(path)?,
+        None => {
+            let mut frames: Frames = Frames::new(&req.rud, session, format.as_str());
+            return match transfer::export(&req.db, &req.table, format, &columns, &mut frames) {
+                Ok(report) => Ok(json!({"code": 200, "exported": report.exported, "skipped": report.skipped}).to_string() + "\njson"),
+                Err(err) => Err(json!({"code": 400, "message": err}).to_string() + "\njson")
+            };
+        }
+    };
+
+    let result = Path::new(&path).parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| File::create(&path))
+        .map_err(|e| e.to_string())
+        .and_then(|file| transfer::export(&req.db, &req.table, format, &columns, &mut BufWriter::new(file)));
+
+    match result {
+        Ok(report) => Ok(json!({"code": 200, "path": req.param("path"), "exported": report.exported, "skipped": report.skipped}).to_string() + "\njson"),
+        Err(err) => Err(json!({"code": 400, "message": err}).to_string() + "\njson")
+    }
+}
+
+/// Imports NDJSON or CSV rows from the body, or from the file in the `path` header, into the specified table.
+///
+/// Headers: `format` (`ndjson` by default or `csv`), `columns` and `key_column` for CSV,
+/// `mode` (`nx`, `xx` or `upsert` by default) for every row, and `path` relative to the
+/// transfer directory of the server.
+///
+/// # Arguments
+///
+/// * `req` - A reference to the `RequestHeaders` containing information about the request.
+/// * `body` - The data to import when no `path` header is given.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON report with the number of imported rows and the errors of failed lines.
+pub fn import(req: &receiver::RequestHeaders, body: &str) -> Result<String, String> {
+    let format: Format = transfer_format(req)?;
+    let columns: CsvColumns = CsvColumns::parse(req.param("key_column"), req.param("columns"));
+    let mode: PutMode = match PutMode::parse(req.param("mode").unwrap_or("upsert")) {
+        Some(mode) => mode,
+        None => return Err("{\"code\": 400, \"message\": \"mode must be nx, xx or upsert\"}\njson".to_string())
+    };
+
+    let result = match req.param("path") {
+        Some(path) => File::open(transfer_path(path)?)
+            .map_err(|e| e.to_string())
+            .and_then(|file| transfer::import(&req.db, &req.table, format, &columns, mode, BufReader::new(file))),
+        None => transfer::import(&req.db, &req.table, format, &columns, mode, Cursor::new(body.as_bytes()))
+    };
+
+    match result {
+        Ok(report) => Ok(json!({"code": 200, "imported": report.imported, "failed": report.failed, "errors": report.errors}).to_string() + "\njson"),
+        Err(err) => Err(json!({"code": 400, "message": err}).to_string() + "\njson")
+    }
+}
+
+/// Resolves the `path` header of an export or import inside the transfer directory.
+fn transfer_path(path: &str) -> Result<String, String> {
+    names::confine_path(&config::CONFIG.transfer_path, path)
+        .map_err(|err| json!({"code": 400, "message": err}).to_string() + "\njson")
+}
+
+/// Reads the `format` header of an export or import.
+fn transfer_format(req: &receiver::RequestHeaders) -> Result<Format, String> {
+    match Format::parse(req.param("format").unwrap_or("ndjson")) {
+        Some(format) => Ok(format),
+        None => Err("{\"code\": 400, \"message\": \"format must be ndjson or csv\"}\njson".to_string())
+    }
+}
@@ -0,0 +1,100 @@
+use serde_json::json;
+
+use crate::{http::receiver::RequestHeaders, tx_pool::transaction::{self, Transaction}};
+
+/// Opens a transaction on the database of the request.
+///
+/// # Arguments
+///
+/// * `req` - RequestHeaders containing the database name.
+/// * `session` - Client session, the transaction is rolled back when it disconnects.
+///
+/// # Returns
+///
+/// Returns a Result with the id of the transaction in the `tx` field.
+pub fn begin(req: &RequestHeaders, session: &str) -> Result<String, String> {
+    if req.db.is_empty() {
+        return Err("{\"code\": 400, \"message\": \"db header is required\"}\njson".to_string());
+    }
+
+    let id: String = transaction::begin(&req.db, session);
+    Ok(json!({"code": 200, "tx": id}).to_string() + "\njson")
+}
+
+/// Queues a write in the transaction given in the `tx` header.
+///
+/// # Arguments
+///
+/// * `path` - The type of the request.
+/// * `req` - The request headers.
+/// * `body` - The request body.
+/// * `session` - Client session sending the write.
+///
+/// # Returns
+///
+/// Returns a Result with the number of queued operations or an error message.
+pub fn queue(path: &str, req: &RequestHeaders, body: &str, session: &str) -> Result<String, String> {
+    let id: &str = req.param("tx").unwrap_or_default();
+
+    match transaction::queue(id, session, path, req, body) {
+        Ok(ops) => Ok(json!({"code": 200, "message": "Operation was queued", "ops": ops}).to_string() + "\njson"),
+        Err(err) => Err(json!({"code": 400, "message": err}).to_string() + "\njson")
+    }
+}
+
+/// Drops the transaction given in the `tx` header with its queued operations.
+///
+/// # Arguments
+///
+/// * `req` - RequestHeaders containing the `tx` header.
+/// * `session` - Client session which opened the transaction.
+///
+/// # Returns
+///
+/// Returns a Result indicating the status of the operation.
+pub fn rollback(req: &RequestHeaders, session: &str) -> Result<String, String> {
+    if transaction::rollback(req.param("tx").unwrap_or_default(), session) {
+        Ok("{\"code\": 200, \"message\": \"Transaction was rolled back\"}\njson".to_string())
+    } else {
+        Err("{\"code\": 404, \"message\": \"no such transaction\"}\njson".to_string())
+    }
+}
+
+/// Removes the transaction given in the `tx` header and builds the writes of its commit.
+///
+/// The transaction is dropped even if one of its operations fails.
+///
+/// # Arguments
+///
+/// * `req` - RequestHeaders containing the `tx` header.
+/// * `session` - Client session which opened the transaction.
+///
+/// # Returns
+///
+/// Returns a Result with the writes to log and apply, or an error response.
+pub fn prepare(req: &RequestHeaders, session: &str) -> Result<String, String> {
+    let tx: Transaction = match transaction::take(req.param("tx").unwrap_or_default(), session) {
+        Some(tx) if tx.db == req.db => tx,
+        Some(_) => return Err("{\"code\": 400, \"message\": \"the transaction belongs to another database\"}\njson".to_string()),
+        None => return Err("{\"code\": 404, \"message\": \"no such transaction\"}\njson".to_string())
+    };
+
+    transaction::prepare(&tx).map_err(|err| json!({"code": 409, "message": err}).to_string() + "\njson")
+}
+
+/// Writes the rows of a committed transaction.
+///
+/// # Arguments
+///
+/// * `req` - RequestHeaders containing the database name.
+/// * `body` - Writes built by [`prepare`].
+///
+/// # Returns
+///
+/// Returns a Result indicating the status of the operation.
+pub fn commit(req: &RequestHeaders, body: &str) -> Result<String, String> {
+    match transaction::apply(&req.db, body) {
+        Ok(rows) => Ok(json!({"code": 200, "message": "Transaction was committed", "rows": rows}).to_string() + "\njson"),
+        Err(err) => Err(json!({"code": 500, "message": err}).to_string() + "\njson")
+    }
+}
@@ -8,9 +8,18 @@ pub mod tests;
 pub mod http;
 pub mod types;
 pub mod tx_pool;
+pub mod cli;
+
+use std::{env, process};
 
 // The main function
 fn main() {
+    // Running an offline tool instead of the server
+    let args: Vec<String> = env::args().collect();
+    if let Some(code) = cli::run(&args) {
+        process::exit(code);
+    }
+
     // Reading configuration from file
     config::read_config().expect("[ ERROR ] Main: Can not read config");
 
@@ -20,9 +29,18 @@ fn main() {
         return;
     };
 
+    // Starting the background maintenance of the storage
+    db::start();
+
     // Starting the transaction pool
     tx_pool::start();
 
+    // Counting the tables with outdated statistics
+    db::stats::start();
+
+    // Starting the reaper of expired rows
+    db::ttl::start();
+
     // Starting the transactional pipeline server
     http::start();
 }
\ No newline at end of file
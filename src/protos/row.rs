@@ -1,5 +1,5 @@
-// This file is generated by rust-protobuf 3.2.0. Do not edit
-// .proto file is parsed by protoc --rust-out=...
+// This file is generated by rust-protobuf 3.3.0. Do not edit
+// .proto file is parsed by pure
 // @generated
 
 // https://github.com/rust-lang/rust-clippy/issues/702
@@ -25,14 +25,22 @@
 /// of protobuf runtime.
 const _PROTOBUF_VERSION_CHECK: () = ::protobuf::VERSION_3_3_0;
 
-#[derive(PartialEq,Clone,Default,Debug)]
 // @@protoc_insertion_point(message:Row)
+#[derive(PartialEq,Clone,Default,Debug)]
 pub struct Row {
     // message fields
     // @@protoc_insertion_point(field:Row.value)
     pub value: ::std::option::Option<::std::string::String>,
     // @@protoc_insertion_point(field:Row.type)
     pub type_: ::std::option::Option<::std::string::String>,
+    // @@protoc_insertion_point(field:Row.expires_at)
+    pub expires_at: ::std::option::Option<u64>,
+    // @@protoc_insertion_point(field:Row.data)
+    pub data: ::std::option::Option<::std::vec::Vec<u8>>,
+    // @@protoc_insertion_point(field:Row.items)
+    pub items: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:Row.fields)
+    pub fields: ::std::collections::HashMap<::std::string::String, ::std::string::String>,
     // special fields
     // @@protoc_insertion_point(special_field:Row.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
@@ -121,8 +129,63 @@ impl Row {
         self.type_.take().unwrap_or_else(|| ::std::string::String::new())
     }
 
+    // optional uint64 expires_at = 4;
+
+    pub fn expires_at(&self) -> u64 {
+        self.expires_at.unwrap_or(0)
+    }
+
+    pub fn clear_expires_at(&mut self) {
+        self.expires_at = ::std::option::Option::None;
+    }
+
+    pub fn has_expires_at(&self) -> bool {
+        self.expires_at.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_expires_at(&mut self, v: u64) {
+        self.expires_at = ::std::option::Option::Some(v);
+    }
+
+    // optional bytes data = 5;
+
+    pub fn data(&self) -> &[u8] {
+        match self.data.as_ref() {
+            Some(v) => v,
+            None => &[],
+        }
+    }
+
+    pub fn clear_data(&mut self) {
+        self.data = ::std::option::Option::None;
+    }
+
+    pub fn has_data(&self) -> bool {
+        self.data.is_some()
+    }
+
+    // Param is passed by value, moved
+    pub fn set_data(&mut self, v: ::std::vec::Vec<u8>) {
+        self.data = ::std::option::Option::Some(v);
+    }
+
+    // Mutable pointer to the field.
+    // If field is not initialized, it is initialized with default value first.
+    pub fn mut_data(&mut self) -> &mut ::std::vec::Vec<u8> {
+        if self.data.is_none() {
+            self.data = ::std::option::Option::Some(::std::vec::Vec::new());
+        }
+        self.data.as_mut().unwrap()
+    }
+
+    // Take field
+    pub fn take_data(&mut self) -> ::std::vec::Vec<u8> {
+        self.data.take().unwrap_or_else(|| ::std::vec::Vec::new())
+    }
+
     fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
-        let mut fields = ::std::vec::Vec::with_capacity(2);
+        let mut fields = ::std::vec::Vec::with_capacity(6);
         let mut oneofs = ::std::vec::Vec::with_capacity(0);
         fields.push(::protobuf::reflect::rt::v2::make_option_accessor::<_, _>(
             "value",
@@ -134,6 +197,26 @@ impl Row {
             |m: &Row| { &m.type_ },
             |m: &mut Row| { &mut m.type_ },
         ));
+        fields.push(::protobuf::reflect::rt::v2::make_option_accessor::<_, _>(
+            "expires_at",
+            |m: &Row| { &m.expires_at },
+            |m: &mut Row| { &mut m.expires_at },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_option_accessor::<_, _>(
+            "data",
+            |m: &Row| { &m.data },
+            |m: &mut Row| { &mut m.data },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_vec_simpler_accessor::<_, _>(
+            "items",
+            |m: &Row| { &m.items },
+            |m: &mut Row| { &mut m.items },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_map_simpler_accessor::<_, _, _>(
+            "fields",
+            |m: &Row| { &m.fields },
+            |m: &mut Row| { &mut m.fields },
+        ));
         ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<Row>(
             "Row",
             fields,
@@ -152,6 +235,7 @@ impl ::protobuf::Message for Row {
         if self.type_.is_none() {
             return false;
         }
+        // TODO: check map values are initialized
         true
     }
 
@@ -164,6 +248,30 @@ impl ::protobuf::Message for Row {
                 26 => {
                     self.type_ = ::std::option::Option::Some(is.read_string()?);
                 },
+                32 => {
+                    self.expires_at = ::std::option::Option::Some(is.read_uint64()?);
+                },
+                42 => {
+                    self.data = ::std::option::Option::Some(is.read_bytes()?);
+                },
+                50 => {
+                    self.items.push(is.read_string()?);
+                },
+                58 => {
+                    let len = is.read_raw_varint32()?;
+                    let old_limit = is.push_limit(len as u64)?;
+                    let mut key = ::std::default::Default::default();
+                    let mut value = ::std::default::Default::default();
+                    while let Some(tag) = is.read_raw_tag_or_eof()? {
+                        match tag {
+                            10 => key = is.read_string()?,
+                            18 => value = is.read_string()?,
+                            _ => ::protobuf::rt::skip_field_for_tag(tag, is)?,
+                        };
+                    }
+                    is.pop_limit(old_limit);
+                    self.fields.insert(key, value);
+                },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
                 },
@@ -182,6 +290,21 @@ impl ::protobuf::Message for Row {
         if let Some(v) = self.type_.as_ref() {
             my_size += ::protobuf::rt::string_size(3, &v);
         }
+        if let Some(v) = self.expires_at {
+            my_size += ::protobuf::rt::uint64_size(4, v);
+        }
+        if let Some(v) = self.data.as_ref() {
+            my_size += ::protobuf::rt::bytes_size(5, &v);
+        }
+        for value in &self.items {
+            my_size += ::protobuf::rt::string_size(6, &value);
+        };
+        for (k, v) in &self.fields {
+            let mut entry_size = 0;
+            entry_size += ::protobuf::rt::string_size(1, &k);
+            entry_size += ::protobuf::rt::string_size(2, &v);
+            my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(entry_size) + entry_size
+        };
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
@@ -194,6 +317,24 @@ impl ::protobuf::Message for Row {
         if let Some(v) = self.type_.as_ref() {
             os.write_string(3, v)?;
         }
+        if let Some(v) = self.expires_at {
+            os.write_uint64(4, v)?;
+        }
+        if let Some(v) = self.data.as_ref() {
+            os.write_bytes(5, v)?;
+        }
+        for v in &self.items {
+            os.write_string(6, &v)?;
+        };
+        for (k, v) in &self.fields {
+            let mut entry_size = 0;
+            entry_size += ::protobuf::rt::string_size(1, &k);
+            entry_size += ::protobuf::rt::string_size(2, &v);
+            os.write_raw_varint32(58)?; // Tag.
+            os.write_raw_varint32(entry_size as u32)?;
+            os.write_string(1, &k)?;
+            os.write_string(2, &v)?;
+        };
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -213,16 +354,16 @@ impl ::protobuf::Message for Row {
     fn clear(&mut self) {
         self.value = ::std::option::Option::None;
         self.type_ = ::std::option::Option::None;
+        self.expires_at = ::std::option::Option::None;
+        self.data = ::std::option::Option::None;
+        self.items.clear();
+        self.fields.clear();
         self.special_fields.clear();
     }
 
     fn default_instance() -> &'static Row {
-        static instance: Row = Row {
-            value: ::std::option::Option::None,
-            type_: ::std::option::Option::None,
-            special_fields: ::protobuf::SpecialFields::new(),
-        };
-        &instance
+        static instance: ::protobuf::rt::Lazy<Row> = ::protobuf::rt::Lazy::new();
+        instance.get(Row::new)
     }
 }
 
@@ -244,17 +385,13 @@ impl ::protobuf::reflect::ProtobufValue for Row {
 }
 
 static file_descriptor_proto_data: &'static [u8] = b"\
-    \n\trow.proto\"/\n\x03Row\x12\x14\n\x05value\x18\x02\x20\x02(\tR\x05valu\
-    e\x12\x12\n\x04type\x18\x03\x20\x02(\tR\x04typeJ\xb4\x01\n\x06\x12\x04\0\
-    \0\x05\x01\n\x08\n\x01\x0c\x12\x03\0\0\x12\n\n\n\x02\x04\0\x12\x04\x02\0\
-    \x05\x01\n\n\n\x03\x04\0\x01\x12\x03\x02\x08\x0b\n\x0b\n\x04\x04\0\x02\0\
-    \x12\x03\x03\x04\x1e\n\x0c\n\x05\x04\0\x02\0\x04\x12\x03\x03\x04\x0c\n\
-    \x0c\n\x05\x04\0\x02\0\x05\x12\x03\x03\r\x13\n\x0c\n\x05\x04\0\x02\0\x01\
-    \x12\x03\x03\x14\x19\n\x0c\n\x05\x04\0\x02\0\x03\x12\x03\x03\x1c\x1d\n\
-    \x0b\n\x04\x04\0\x02\x01\x12\x03\x04\x04\x1d\n\x0c\n\x05\x04\0\x02\x01\
-    \x04\x12\x03\x04\x04\x0c\n\x0c\n\x05\x04\0\x02\x01\x05\x12\x03\x04\r\x13\
-    \n\x0c\n\x05\x04\0\x02\x01\x01\x12\x03\x04\x14\x18\n\x0c\n\x05\x04\0\x02\
-    \x01\x03\x12\x03\x04\x1b\x1c\
+    \n\trow.proto\"\xdd\x01\n\x03Row\x12\x14\n\x05value\x18\x02\x20\x02(\tR\
+    \x05value\x12\x12\n\x04type\x18\x03\x20\x02(\tR\x04type\x12\x1d\n\nexpir\
+    es_at\x18\x04\x20\x01(\x04R\texpiresAt\x12\x12\n\x04data\x18\x05\x20\x01\
+    (\x0cR\x04data\x12\x14\n\x05items\x18\x06\x20\x03(\tR\x05items\x12(\n\
+    \x06fields\x18\x07\x20\x03(\x0b2\x10.Row.FieldsEntryR\x06fields\x1a9\n\
+    \x0bFieldsEntry\x12\x10\n\x03key\x18\x01\x20\x01(\tR\x03key\x12\x14\n\
+    \x05value\x18\x02\x20\x01(\tR\x05value:\x028\x01b\x06proto2\
 ";
 
 /// `FileDescriptorProto` object which was a source for this generated file
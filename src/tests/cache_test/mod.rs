@@ -1,10 +1,72 @@
 #[cfg(test)]
 mod test {
-    use crate::cache;
+    use crate::cache::{self, PutMode};
+    use crate::db::engine::engine;
+    use crate::protos::row::Row;
 
     #[test]
     
     fn add_to_cache_test() {
         // cache::add("", table, key, value)
     }
-}
\ No newline at end of file
+
+    fn row(value: &str) -> Row {
+        let mut row: Row = Row::new();
+        row.set_value(value.to_string());
+        row.set_type("string".to_string());
+        row
+    }
+
+    #[test]
+    fn put_modes_test() {
+        let db: &str = "put_modes_db";
+
+        assert!(!cache::put(db, "t", "k", &row("1"), PutMode::Update).unwrap());
+        assert!(cache::put(db, "t", "k", &row("1"), PutMode::Insert).unwrap());
+        assert!(!cache::put(db, "t", "k", &row("2"), PutMode::Insert).unwrap());
+        assert_eq!(cache::get(db, "t", "k").unwrap().value(), "1");
+
+        assert!(cache::put(db, "t", "k", &row("3"), PutMode::Update).unwrap());
+        assert!(cache::put(db, "t", "k", &row("4"), PutMode::Upsert).unwrap());
+
+        // The cached and the stored copy hold the same single row
+        assert_eq!(cache::get(db, "t", "k").unwrap().value(), "4");
+        assert_eq!(engine().read_row(db, "t", "k").unwrap().unwrap().value(), "4");
+        assert_eq!(engine().list_rows(db, "t").unwrap().len(), 1);
+
+        // Rows which are stored but not cached still exist
+        engine().write_row(db, "t", "stored", &row("5")).unwrap();
+        assert!(!cache::add_row(db, "t", "stored", &row("6")));
+        assert!(!cache::put(db, "t", "stored", &row("6"), PutMode::Insert).unwrap());
+        assert_eq!(cache::get(db, "t", "stored").unwrap().value(), "5");
+
+        assert_eq!(PutMode::parse("XX"), Some(PutMode::Update));
+        assert!(PutMode::parse("replace").is_none());
+
+        engine().delete_db(db).unwrap();
+    }
+
+    #[test]
+    fn separator_names_test() {
+        let _ = engine().delete_db("sep|rdb|db");
+        let _ = engine().delete_db("sep");
+        cache::evict_db("sep|rdb|db");
+        cache::evict_db("sep");
+
+        // Names holding the separator of the cache keys
+        assert!(cache::put("sep|rdb|db", "t", "k", &row("1"), PutMode::Upsert).unwrap());
+        assert!(cache::put("sep", "rdb|db|rdb|t", "k", &row("2"), PutMode::Upsert).unwrap());
+        assert_eq!(cache::cached_rows("sep|rdb|db", None), 1);
+        assert_eq!(cache::cached_rows("sep", Some("rdb|db|rdb|t")), 1);
+
+        assert!(cache::delete_table("sep|rdb|db", "t"));
+        assert_eq!(cache::cached_rows("sep|rdb|db", None), 0);
+        assert_eq!(cache::get("sep", "rdb|db|rdb|t", "k").unwrap().value(), "2");
+
+        cache::evict_db("sep");
+        assert_eq!(cache::cached_rows("sep", None), 0);
+
+        engine().delete_db("sep|rdb|db").unwrap();
+        engine().delete_db("sep").unwrap();
+    }
+}
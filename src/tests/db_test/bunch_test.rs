@@ -0,0 +1,139 @@
+#[cfg(test)]
+mod test {
+    use serde_json::{json, Value};
+
+    use crate::cache;
+    use crate::db::{engine::engine, schema, table::create_table};
+    use crate::http::{receiver::RequestHeaders, row_methods};
+    use crate::protos::row::Row;
+    use crate::tx_pool::{req_handler::handle_request, transaction};
+
+    const DB: &str = "bunch_test_db";
+
+    fn headers(table: &str, best_effort: bool) -> RequestHeaders {
+        let mut head: RequestHeaders = RequestHeaders {
+            db: DB.to_string(),
+            table: table.to_string(),
+            ..Default::default()
+        };
+        if best_effort {
+            head.params.insert("best_effort".to_string(), "true".to_string());
+        }
+
+        head
+    }
+
+    /// Drops the tables an earlier run left behind.
+    fn reset(tables: &[&str]) {
+        for table in tables {
+            cache::delete_table(DB, table);
+        }
+    }
+
+    fn parse(response: &str) -> Value {
+        serde_json::from_str(response.trim_end_matches("\njson")).unwrap()
+    }
+
+    fn statuses(response: &Value) -> Vec<&str> {
+        response["results"].as_array().unwrap().iter().map(|r| r["status"].as_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn atomic_bunch_test() {
+        reset(&["atomic"]);
+        let body: String = json!([
+            {"key": "1", "value": 1, "_type": "int"},
+            {"key": "2", "value": "not a number", "_type": "int"},
+            {"key": "", "value": 3, "_type": "int"}
+        ]).to_string();
+
+        // One invalid element keeps the whole bunch out
+        let response: Value = parse(&row_methods::bunch(&headers("atomic", false), &body).unwrap_err());
+        assert_eq!(response["code"], 400);
+        assert_eq!(response["written"], 0);
+        assert_eq!(statuses(&response), vec!["not_written", "invalid", "invalid"]);
+        assert!(cache::get(DB, "atomic", "1").is_err());
+
+        let body: String = json!([
+            {"key": "1", "value": 1, "_type": "int"},
+            {"key": "2", "value": {"a": 1}, "_type": "json"}
+        ]).to_string();
+        let response: Value = parse(&row_methods::bunch(&headers("atomic", false), &body).unwrap());
+        assert_eq!((response["code"].as_u64(), response["written"].as_u64()), (Some(200), Some(2)));
+        assert_eq!(statuses(&response), vec!["written", "written"]);
+
+        // The rows are cached like single writes
+        assert_eq!(cache::cached_rows(DB, Some("atomic")), 2);
+        assert_eq!(cache::get(DB, "atomic", "2").unwrap().value(), "{\"a\":1}");
+
+        assert!(row_methods::bunch(&headers("atomic", false), "not json").is_err());
+    }
+
+    #[test]
+    fn existing_key_test() {
+        reset(&["existing"]);
+        assert!(cache::add(DB, "existing", "1", "0", "int"));
+
+        // An existing or repeated key keeps the whole bunch out
+        let body: String = json!([
+            {"key": "1", "value": 1, "_type": "int"},
+            {"key": "2", "value": 2, "_type": "int"},
+            {"key": "2", "value": 3, "_type": "int"}
+        ]).to_string();
+        let response: Value = parse(&handle_request("add_bunch", &headers("existing", false), &body, "s").unwrap_err());
+        assert_eq!(response["code"], 409);
+        assert_eq!(statuses(&response), vec!["exists", "not_written", "exists"]);
+        assert_eq!(cache::get(DB, "existing", "1").unwrap().value(), "0");
+        assert!(cache::get(DB, "existing", "2").is_err());
+
+        let response: Value = parse(&handle_request("add_bunch", &headers("existing", true), &body, "s").unwrap());
+        assert_eq!((response["written"].as_u64(), response["invalid"].as_u64()), (Some(1), Some(2)));
+        assert_eq!(cache::get(DB, "existing", "1").unwrap().value(), "0");
+        assert_eq!(cache::get(DB, "existing", "2").unwrap().value(), "2");
+    }
+
+    #[test]
+    fn best_effort_bunch_test() {
+        reset(&["typed"]);
+        create_table(DB, "typed");
+        schema::set(DB, "typed", json!({"type": "object", "properties": {"n": {"type": "integer"}}})).unwrap();
+
+        let body: String = json!([
+            {"key": "1", "value": {"n": 1}, "_type": "json"},
+            {"key": "2", "value": {"n": "x"}, "_type": "json"},
+            {"key": "3", "value": {"n": 3}, "_type": "json"}
+        ]).to_string();
+
+        let response: Value = parse(&row_methods::bunch(&headers("typed", false), &body).unwrap_err());
+        assert_eq!(response["code"], 422);
+        assert_eq!(response["results"][1]["violations"][0]["pointer"], "/n");
+
+        let response: Value = parse(&row_methods::bunch(&headers("typed", true), &body).unwrap());
+        assert_eq!((response["written"].as_u64(), response["invalid"].as_u64()), (Some(2), Some(1)));
+        assert_eq!(statuses(&response), vec!["written", "invalid", "written"]);
+        assert!(cache::get(DB, "typed", "1").is_ok());
+        assert!(cache::get(DB, "typed", "2").is_err());
+    }
+
+    #[test]
+    fn write_rows_revert_test() {
+        reset(&["revert"]);
+        assert!(cache::add(DB, "revert", "old", "before", "string"));
+
+        let mut row: Row = Row::new();
+        row.set_value("after".to_string());
+        row.set_type("string".to_string());
+
+        // The second write fails, the first one is put back
+        let rows: Vec<(String, String, Option<Row>)> = vec![
+            ("revert".to_string(), "old".to_string(), Some(row.clone())),
+            ("revert".to_string(), "new".to_string(), Some(row.clone())),
+            (String::new(), "broken".to_string(), Some(row))
+        ];
+        assert!(transaction::write_rows(DB, &rows).is_err());
+
+        assert_eq!(cache::get(DB, "revert", "old").unwrap().value(), "before");
+        assert!(cache::get(DB, "revert", "new").is_err());
+        assert!(!engine().row_exists(DB, "revert", "new"));
+    }
+}
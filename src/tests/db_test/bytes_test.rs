@@ -0,0 +1,66 @@
+#[cfg(test)]
+mod test {
+    use std::{io::{Cursor, Write}, net::{TcpListener, TcpStream}, thread};
+
+    use crate::cache::{self, PutMode};
+    use crate::db::{engine::engine, transfer::{self, CsvColumns, Format}};
+    use crate::http::{self, receiver::{self, RequestHeaders}};
+    use crate::protos::row::Row;
+    use crate::types;
+
+    /// Bytes which break the text transport: the section bytes, zero and invalid UTF-8.
+    const BLOB: [u8; 8] = [0x00, 0x01, 0x02, 0x17, 0xff, 0xfe, b'\n', b' '];
+
+    #[test]
+    fn row_test() {
+        let db: &str = "bytes_test_db";
+        let text: String = types::encode_bytes(&BLOB);
+        assert!(types::is_valid_data(&text, "bytes").is_ok());
+        assert!(types::is_valid_data("not base64!", "bytes").is_err());
+
+        let mut row: Row = Row::new();
+        types::set_row_value(&mut row, &text, "bytes").unwrap();
+        assert!(cache::put(db, "blobs", "b", &row, PutMode::Upsert).unwrap());
+
+        let stored: Row = engine().read_row(db, "blobs", "b").unwrap().unwrap();
+        assert_eq!(stored.data(), BLOB);
+        assert_eq!(types::row_value(&cache::get(db, "blobs", "b").unwrap()), text);
+
+        let columns: CsvColumns = CsvColumns::parse(None, None);
+        let mut data: Vec<u8> = Vec::new();
+        transfer::export(db, "blobs", Format::Ndjson, &columns, &mut data).unwrap();
+        transfer::import(db, "copy", Format::Ndjson, &columns, PutMode::Upsert, Cursor::new(data)).unwrap();
+        assert_eq!(engine().read_row(db, "copy", "b").unwrap().unwrap().data(), BLOB);
+
+        engine().delete_db(db).unwrap();
+    }
+
+    #[test]
+    fn transport_test() {
+        let listener: TcpListener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut stream: TcpStream = TcpStream::connect(address).unwrap();
+            let mut message: Vec<u8> = format!("\x01req: add_row\ntype: bytes\nlength: {}\x17\x02", BLOB.len()).into_bytes();
+            message.extend_from_slice(&BLOB);
+            message.push(0x17);
+            stream.write_all(&message).unwrap();
+        });
+
+        let (stream, _) = listener.accept().unwrap();
+        let (header, body): (String, Vec<u8>) = receiver::deserialize(&stream, "client").unwrap();
+        client.join().unwrap();
+
+        assert_eq!(body, BLOB);
+        let (_, head): (String, RequestHeaders) = receiver::get_header(header);
+        assert_eq!(types::decode_bytes(&receiver::body_text(&head, &body)).unwrap(), BLOB);
+
+        // The closed connection ends the session instead of reading nothing forever
+        assert_eq!(receiver::deserialize(&stream, "client").unwrap_err(), "client");
+
+        // A response for a client which is gone is dropped
+        http::respond(&RequestHeaders { rud: "1".to_string(), ..Default::default() }, &(types::encode_bytes(&BLOB) + "\nbytes"), "gone_client");
+        assert!(!http::CLIENTS.lock().unwrap().writable.contains_key("gone_client"));
+    }
+}
@@ -0,0 +1,157 @@
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+    use std::net::{TcpListener, TcpStream};
+    use std::time::Duration;
+    use serde_json::Value;
+
+    use crate::cache;
+    use crate::db::{self, cdc};
+    use crate::http::{receiver::RequestHeaders, CLIENTS};
+    use crate::protos::row::Row;
+    use crate::tx_pool::{req_handler::handle_request, transaction};
+
+    const DB: &str = "cdc_test_db";
+
+    fn headers(rud: &str, table: &str, key: &str, params: &[(&str, &str)]) -> RequestHeaders {
+        RequestHeaders {
+            rud: rud.to_string(),
+            db: DB.to_string(),
+            table: table.to_string(),
+            key: key.to_string(),
+            _type: "string".to_string(),
+            params: params.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+        }
+    }
+
+    /// Connects a client to a session, returns the client side of the connection.
+    fn connect(session: &str) -> TcpStream {
+        let listener: TcpListener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client: TcpStream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+
+        CLIENTS.lock().unwrap().writable.insert(session.to_string(), server);
+        client.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+        client
+    }
+
+    /// Reads event frames until `count` of them arrived, returns their rud and event.
+    fn read_events(client: &mut TcpStream, count: usize) -> Vec<(String, Value)> {
+        let mut text: String = String::new();
+        let mut buf: [u8; 4096] = [0; 4096];
+
+        while text.matches("\njson").count() < count {
+            let n: usize = client.read(&mut buf).expect("an event frame");
+            text.push_str(std::str::from_utf8(&buf[..n]).unwrap());
+        }
+
+        text.split("rud: ")
+            .filter(|frame| !frame.is_empty())
+            .map(|frame| {
+                let (rud, data) = frame.split_once('\n').unwrap();
+                (rud.to_string(), serde_json::from_str(data.strip_suffix("\njson").unwrap()).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn subscribe_test() {
+        db::create_db(DB);
+        cache::delete_table(DB, "events");
+        cache::delete_table(DB, "other");
+        let session: &str = "cdc_session";
+        let mut client: TcpStream = connect(session);
+
+        let reply: String = handle_request("subscribe", &headers("watch", "events", "", &[("prefix", "user:")]), "", session).unwrap();
+        let reply: Value = serde_json::from_str(reply.strip_suffix("\njson").unwrap()).unwrap();
+        let id: &str = reply["subscription"].as_str().unwrap();
+
+        handle_request("add_row", &headers("1", "events", "user:1", &[]), "ann", session).unwrap();
+        handle_request("add_row", &headers("2", "events", "order:1", &[]), "skipped", session).unwrap();
+        handle_request("add_row", &headers("3", "other", "user:1", &[]), "skipped", session).unwrap();
+        handle_request("put_row", &headers("4", "events", "user:1", &[]), "bob", session).unwrap();
+        handle_request("delete_row", &headers("5", "events", "user:1", &[]), "", session).unwrap();
+
+        let events: Vec<(String, Value)> = read_events(&mut client, 3);
+        assert_eq!(events.len(), 3);
+        assert!(events.iter().all(|(rud, event)| rud == "watch" && event["subscription"] == id));
+
+        let ops: Vec<(&str, u64, &Value)> = events.iter()
+            .map(|(_, event)| (event["op"].as_str().unwrap(), event["seq"].as_u64().unwrap(), &event["value"]))
+            .collect();
+        assert_eq!(ops, vec![
+            ("add", 1, &Value::from("ann")),
+            ("update", 2, &Value::from("bob")),
+            ("delete", 3, &Value::Null)
+        ]);
+        assert_eq!(events[0].1["key"], "user:1");
+        assert_eq!(events[0].1["table"], "events");
+        assert_eq!(events[0].1["type"], "string");
+
+        // Only the session which subscribed can cancel
+        assert!(handle_request("unsubscribe", &headers("6", "", "", &[("subscription", id)]), "", "intruder").is_err());
+        assert!(handle_request("unsubscribe", &headers("6", "", "", &[("subscription", id)]), "", session).is_ok());
+
+        handle_request("put_row", &headers("7", "events", "user:2", &[]), "carl", session).unwrap();
+        let mut buf: [u8; 64] = [0; 64];
+        assert!(client.read(&mut buf).is_err());
+
+        CLIENTS.lock().unwrap().writable.remove(session);
+    }
+
+    #[test]
+    fn drop_session_test() {
+        db::create_db(DB);
+        let session: &str = "cdc_closed_session";
+        let _client: TcpStream = connect(session);
+
+        assert!(handle_request("subscribe", &headers("watch", "", "", &[]), "", session).is_ok());
+        assert!(handle_request("subscribe", &RequestHeaders { db: "cdc_missing_db".to_string(), ..Default::default() }, "", session).is_err());
+        let id: String = cdc::subscribe(session, "watch", DB, Some("closed"), "").unwrap();
+
+        // Events for a session which is gone are skipped
+        CLIENTS.lock().unwrap().writable.remove(session);
+        handle_request("put_row", &headers("1", "closed", "key", &[]), "value", session).unwrap();
+
+        cdc::drop_session(session);
+        assert!(!cdc::unsubscribe(&id, session));
+
+        // A subscription which arrives after the session closed is not kept
+        assert!(handle_request("subscribe", &headers("watch", "", "", &[]), "", session).is_err());
+        assert!(cdc::subscribe(session, "watch", DB, None, "").is_none());
+    }
+
+    #[test]
+    fn transaction_events_test() {
+        db::create_db(DB);
+        let session: &str = "cdc_tx_session";
+        let mut client: TcpStream = connect(session);
+        cdc::subscribe(session, "watch", DB, Some("tx_events"), "").unwrap();
+
+        let mut row: Row = Row::new();
+        row.set_value("value".to_string());
+        row.set_type("string".to_string());
+
+        // The commit fails on its last row, the written ones are put back unseen
+        let rows: Vec<(String, String, Option<Row>)> = vec![
+            ("tx_events".to_string(), "a".to_string(), Some(row.clone())),
+            (String::new(), "broken".to_string(), Some(row.clone()))
+        ];
+        assert!(transaction::write_rows(DB, &rows).is_err());
+
+        let rows: Vec<(String, String, Option<Row>)> = vec![
+            ("tx_events".to_string(), "b".to_string(), Some(row.clone())),
+            ("tx_events".to_string(), "c".to_string(), Some(row))
+        ];
+        assert_eq!(transaction::write_rows(DB, &rows), Ok(2));
+
+        let events: Vec<(String, Value)> = read_events(&mut client, 2);
+        let keys: Vec<(&str, u64)> = events.iter()
+            .map(|(_, event)| (event["key"].as_str().unwrap(), event["seq"].as_u64().unwrap()))
+            .collect();
+        assert_eq!(keys, vec![("b", 1), ("c", 2)]);
+
+        cdc::drop_session(session);
+        CLIENTS.lock().unwrap().writable.remove(session);
+    }
+}
@@ -0,0 +1,138 @@
+#[cfg(test)]
+mod test {
+    use std::thread::{self, JoinHandle};
+
+    use crate::cache;
+    use crate::http::receiver::RequestHeaders;
+    use crate::protos::row::Row;
+    use crate::tx_pool::req_handler::handle_request;
+    use crate::types;
+
+    const DB: &str = "collection_test_db";
+    const TABLE: &str = "collections";
+
+    fn headers(key: &str, _type: &str, params: &[(&str, &str)]) -> RequestHeaders {
+        RequestHeaders {
+            db: DB.to_string(),
+            table: TABLE.to_string(),
+            key: key.to_string(),
+            _type: _type.to_string(),
+            params: params.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Drops the rows an earlier run left behind.
+    fn reset(keys: &[&str]) {
+        for key in keys {
+            let _ = cache::delete(DB, TABLE, key);
+        }
+    }
+
+    #[test]
+    fn row_value_test() {
+        assert!(types::is_valid_data("[\"a\", \"b\"]", "list").is_ok());
+        assert!(types::is_valid_data("[\"a\", 1]", "set").is_err());
+        assert!(types::is_valid_data("{\"a\": \"1\"}", "hash").is_ok());
+        assert!(types::is_valid_data("{\"a\": 1}", "hash").is_err());
+
+        let mut row: Row = Row::new();
+        types::set_row_value(&mut row, "[\"b\", \"a\", \"b\"]", "set").unwrap();
+        assert_eq!(row.items, vec!["a", "b"]);
+        assert_eq!(row.value(), "");
+        assert_eq!(types::row_value(&row), "[\"a\",\"b\"]");
+
+        // Another type drops the items
+        types::set_row_value(&mut row, "text", "string").unwrap();
+        assert!(row.items.is_empty());
+        assert_eq!(types::row_value(&row), "text");
+    }
+
+    #[test]
+    fn add_and_get_test() {
+        reset(&["stored"]);
+
+        handle_request("add_row", &headers("stored", "hash", &[]), "{\"b\": \"2\", \"a\": \"1\"}", "s").unwrap();
+        assert_eq!(handle_request("get_row", &headers("stored", "", &[]), "", "s").unwrap(), "{\"a\":\"1\",\"b\":\"2\"}\nhash");
+        assert!(handle_request("add_row", &headers("bad", "list", &[]), "not a list", "s").is_err());
+    }
+
+    #[test]
+    fn list_test() {
+        reset(&["queue"]);
+
+        assert_eq!(handle_request("list_push", &headers("queue", "", &[]), "[\"c\", \"d\"]", "s").unwrap(), "2\nuint");
+        assert_eq!(handle_request("list_push", &headers("queue", "", &[("side", "left")]), "[\"b\", \"a\"]", "s").unwrap(), "4\nuint");
+        assert_eq!(handle_request("list_range", &headers("queue", "", &[]), "", "s").unwrap(), "[\"a\",\"b\",\"c\",\"d\"]\nlist");
+        assert_eq!(handle_request("list_range", &headers("queue", "", &[("start", "1"), ("stop", "-2")]), "", "s").unwrap(), "[\"b\",\"c\"]\nlist");
+
+        assert_eq!(handle_request("list_pop", &headers("queue", "", &[("side", "left")]), "", "s").unwrap(), "[\"a\"]\nlist");
+        assert_eq!(handle_request("list_pop", &headers("queue", "", &[("count", "5")]), "", "s").unwrap(), "[\"d\",\"c\",\"b\"]\nlist");
+        assert_eq!(handle_request("list_pop", &headers("queue", "", &[]), "", "s").unwrap(), "[]\nlist");
+
+        // Popping a missing key does not create it
+        assert_eq!(handle_request("list_pop", &headers("missing", "", &[]), "", "s").unwrap(), "[]\nlist");
+        assert!(cache::get(DB, TABLE, "missing").is_err());
+
+        assert!(handle_request("list_push", &headers("queue", "", &[("side", "up")]), "[\"a\"]", "s").is_err());
+        assert!(handle_request("list_push", &headers("queue", "", &[]), "{}", "s").is_err());
+    }
+
+    #[test]
+    fn set_test() {
+        reset(&["tags"]);
+
+        assert_eq!(handle_request("set_add", &headers("tags", "", &[]), "[\"red\", \"blue\", \"red\"]", "s").unwrap(), "2\nuint");
+        assert_eq!(handle_request("set_add", &headers("tags", "", &[]), "[\"blue\", \"green\"]", "s").unwrap(), "1\nuint");
+        assert_eq!(handle_request("set_members", &headers("tags", "", &[]), "", "s").unwrap(), "[\"blue\",\"green\",\"red\"]\nset");
+        assert_eq!(handle_request("set_contains", &headers("tags", "", &[]), "green", "s").unwrap(), "true\nbool");
+
+        assert_eq!(handle_request("set_remove", &headers("tags", "", &[]), "[\"green\", \"pink\"]", "s").unwrap(), "1\nuint");
+        assert_eq!(handle_request("set_contains", &headers("tags", "", &[]), "green", "s").unwrap(), "false\nbool");
+        assert_eq!(handle_request("set_members", &headers("none", "", &[]), "", "s").unwrap(), "[]\nset");
+    }
+
+    #[test]
+    fn hash_test() {
+        reset(&["user"]);
+
+        assert_eq!(handle_request("hash_set", &headers("user", "", &[]), "{\"name\": \"ann\", \"age\": \"30\"}", "s").unwrap(), "2\nuint");
+        assert_eq!(handle_request("hash_set", &headers("user", "", &[]), "{\"age\": \"31\", \"city\": \"oslo\"}", "s").unwrap(), "1\nuint");
+        assert_eq!(handle_request("hash_get", &headers("user", "", &[]), "age", "s").unwrap(), "31\nstring");
+        assert!(handle_request("hash_get", &headers("user", "", &[]), "email", "s").unwrap_err().contains("404"));
+
+        assert_eq!(handle_request("hash_delete", &headers("user", "", &[]), "[\"age\", \"email\"]", "s").unwrap(), "1\nuint");
+        assert_eq!(handle_request("get_row", &headers("user", "", &[]), "", "s").unwrap(), "{\"city\":\"oslo\",\"name\":\"ann\"}\nhash");
+    }
+
+    #[test]
+    fn wrong_type_test() {
+        reset(&["plain", "a_set"]);
+
+        assert!(cache::add(DB, TABLE, "plain", "text", "string"));
+
+        assert!(handle_request("list_push", &headers("plain", "", &[]), "[\"a\"]", "s").is_err());
+        assert!(handle_request("set_members", &headers("plain", "", &[]), "", "s").is_err());
+        handle_request("set_add", &headers("a_set", "", &[]), "[\"a\"]", "s").unwrap();
+        assert!(handle_request("hash_set", &headers("a_set", "", &[]), "{\"a\": \"b\"}", "s").is_err());
+        assert_eq!(cache::get(DB, TABLE, "plain").unwrap().value(), "text");
+    }
+
+    #[test]
+    fn concurrent_push_test() {
+        reset(&["jobs"]);
+
+        let workers: Vec<JoinHandle<()>> = (0..8)
+            .map(|_| thread::spawn(|| {
+                for _ in 0..25 {
+                    handle_request("list_push", &headers("jobs", "", &[]), "[\"job\"]", "s").unwrap();
+                }
+            }))
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        assert_eq!(cache::get(DB, TABLE, "jobs").unwrap().items.len(), 200);
+    }
+}
@@ -0,0 +1,87 @@
+#[cfg(test)]
+mod test {
+    use std::thread::{self, JoinHandle};
+
+    use crate::cache;
+    use crate::http::receiver::RequestHeaders;
+    use crate::tx_pool::req_handler::handle_request;
+    use crate::types;
+
+    const DB: &str = "counter_test_db";
+    const TABLE: &str = "counters";
+
+    fn headers(key: &str, _type: &str) -> RequestHeaders {
+        RequestHeaders {
+            db: DB.to_string(),
+            table: TABLE.to_string(),
+            key: key.to_string(),
+            _type: _type.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Drops the rows an earlier run left behind.
+    fn reset(keys: &[&str]) {
+        for key in keys {
+            let _ = cache::delete(DB, TABLE, key);
+        }
+    }
+
+    #[test]
+    fn add_number_test() {
+        assert_eq!(types::add_number("5", "int", "-7").unwrap(), "-2");
+        assert_eq!(types::add_number("5", "uint", "-5").unwrap(), "0");
+        assert!(types::add_number("5", "uint", "-6").is_err());
+        assert!(types::add_number(&u64::MAX.to_string(), "uint", "1").is_err());
+        assert!(types::add_number(&i64::MAX.to_string(), "int", "1").is_err());
+        assert!(types::add_number("1", "int", "1.5").is_err());
+        assert_eq!(types::add_number("1.5", "float", "0.25").unwrap(), "1.75");
+        assert!(types::add_number("1", "string", "1").is_err());
+    }
+
+    #[test]
+    fn incr_test() {
+        reset(&["hits", "stock", "ratio", "name"]);
+
+        // A missing key starts at zero
+        assert_eq!(handle_request("incr", &headers("hits", ""), "", "s").unwrap(), "1\nint");
+        assert_eq!(handle_request("incr", &headers("hits", ""), "10", "s").unwrap(), "11\nint");
+        assert_eq!(handle_request("decr", &headers("hits", ""), "", "s").unwrap(), "10\nint");
+        assert_eq!(handle_request("decr", &headers("hits", ""), "4", "s").unwrap(), "6\nint");
+        assert_eq!(handle_request("incr", &headers("hits", ""), "-2", "s").unwrap(), "4\nint");
+
+        // decr takes the delta without a sign
+        assert!(handle_request("decr", &headers("hits", ""), "-5", "s").unwrap_err().contains("400"));
+        assert!(handle_request("decr", &headers("hits", ""), "+5", "s").unwrap_err().contains("400"));
+        assert!(handle_request("incr", &headers("hits", ""), "--5", "s").is_err());
+        assert_eq!(cache::get(DB, TABLE, "hits").unwrap().value(), "4");
+
+        // The declared type of the row is kept
+        assert_eq!(handle_request("decr", &headers("stock", "uint"), "0", "s").unwrap(), "0\nuint");
+        assert!(handle_request("decr", &headers("stock", "uint"), "1", "s").is_err());
+        assert_eq!(cache::get(DB, TABLE, "stock").unwrap().value(), "0");
+        assert_eq!(handle_request("incr", &headers("ratio", "float"), "0.5", "s").unwrap(), "0.5\nfloat");
+
+        assert!(cache::add(DB, TABLE, "name", "text", "string"));
+        assert!(handle_request("incr", &headers("name", ""), "", "s").is_err());
+        assert!(handle_request("incr", &headers("new", "string"), "", "s").is_err());
+    }
+
+    #[test]
+    fn concurrent_incr_test() {
+        reset(&["shared"]);
+
+        let workers: Vec<JoinHandle<()>> = (0..8)
+            .map(|_| thread::spawn(|| {
+                for _ in 0..50 {
+                    handle_request("incr", &headers("shared", ""), "", "s").unwrap();
+                }
+            }))
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        assert_eq!(cache::get(DB, TABLE, "shared").unwrap().value(), "400");
+    }
+}
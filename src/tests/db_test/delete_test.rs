@@ -0,0 +1,87 @@
+#[cfg(test)]
+mod test {
+    use serde_json::{json, Value};
+
+    use crate::cache;
+    use crate::db::{engine::engine, ttl};
+    use crate::http::{receiver::RequestHeaders, row_methods};
+    use crate::protos::row::Row;
+
+    const DB: &str = "delete_test_db";
+
+    fn headers(table: &str, dry_run: bool) -> RequestHeaders {
+        let mut head: RequestHeaders = RequestHeaders {
+            db: DB.to_string(),
+            table: table.to_string(),
+            ..Default::default()
+        };
+        if dry_run {
+            head.params.insert("dry_run".to_string(), "true".to_string());
+        }
+
+        head
+    }
+
+    /// Drops the tables an earlier run left behind.
+    fn reset(tables: &[&str]) {
+        for table in tables {
+            cache::delete_table(DB, table);
+        }
+    }
+
+    fn parse(response: &str) -> Value {
+        serde_json::from_str(response.trim_end_matches("\njson")).unwrap()
+    }
+
+    #[test]
+    fn delete_many_test() {
+        reset(&["many"]);
+        for key in ["1", "2", "3"] {
+            assert!(cache::add(DB, "many", key, "value", "string"));
+        }
+
+        // An expired row which is not reaped yet does not count
+        let mut expired: Row = Row::new();
+        expired.set_value("value".to_string());
+        expired.set_type("string".to_string());
+        expired.set_expires_at(ttl::now() - 1);
+        engine().write_row(DB, "many", "expired", &expired).unwrap();
+
+        let body: String = json!(["1", "3", "missing", "expired"]).to_string();
+
+        let response: Value = parse(&row_methods::delete_many(&headers("many", true), &body).unwrap());
+        assert_eq!((response["matched"].as_u64(), response["deleted"].as_u64()), (Some(2), Some(0)));
+        assert_eq!(response["keys"], json!(["1", "3"]));
+        assert!(cache::get(DB, "many", "1").is_ok());
+
+        let response: Value = parse(&row_methods::delete_many(&headers("many", false), &body).unwrap());
+        assert_eq!(response["deleted"], 2);
+        assert!(cache::get(DB, "many", "1").is_err());
+        assert!(!engine().row_exists(DB, "many", "3"));
+        assert_eq!(cache::cached_rows(DB, Some("many")), 1);
+
+        assert!(row_methods::delete_many(&headers("many", false), "{}").is_err());
+    }
+
+    #[test]
+    fn delete_where_test() {
+        reset(&["tenants"]);
+        assert!(cache::add(DB, "tenants", "a1", "{\"tenant\":\"a\",\"n\":1}", "json"));
+        assert!(cache::add(DB, "tenants", "a2", "{\"tenant\":\"a\",\"n\":2}", "json"));
+        assert!(cache::add(DB, "tenants", "b1", "{\"tenant\":\"b\",\"n\":1}", "json"));
+        let filter: &str = "{\"tenant\":\"a\"}";
+
+        let response: Value = parse(&row_methods::delete_where(&headers("tenants", true), filter).unwrap());
+        assert_eq!(response["keys"], json!(["a1", "a2"]));
+        assert_eq!(response["deleted"], 0);
+        assert!(cache::get(DB, "tenants", "a1").is_ok());
+
+        let response: Value = parse(&row_methods::delete_where(&headers("tenants", false), filter).unwrap());
+        assert_eq!(response["deleted"], 2);
+        assert!(cache::get(DB, "tenants", "a2").is_err());
+        assert_eq!(engine().list_rows(DB, "tenants").unwrap(), vec!["b1".to_string()]);
+
+        assert!(row_methods::delete_where(&headers("tenants", false), "[]").is_err());
+        assert!(row_methods::delete_where(&headers("missing", false), filter).is_err());
+    }
+}
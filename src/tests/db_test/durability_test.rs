@@ -0,0 +1,29 @@
+#[cfg(test)]
+mod test {
+    use crate::config::Durability;
+    use crate::db::{durability, meta::{self, DbMeta}, row::{add_row, read_row}};
+    use crate::protos::row::Row;
+
+    #[test]
+    fn db_override_test() {
+        assert_eq!(durability::level("durability_none_db"), Durability::None);
+
+        let db_meta: DbMeta = DbMeta { durability: Some("fsync-every-write".to_string()), ..Default::default() };
+        meta::set_db_meta("durability_fsync_db", &db_meta).unwrap();
+        assert_eq!(durability::level("durability_fsync_db"), Durability::FsyncEveryWrite);
+    }
+
+    #[test]
+    fn group_commit_test() {
+        let db_meta: DbMeta = DbMeta { durability: Some("group-commit".to_string()), ..Default::default() };
+        meta::set_db_meta("durability_group_db", &db_meta).unwrap();
+
+        let mut row: Row = Row::new();
+        row.set_value("1".to_string());
+        row.set_type("int".to_string());
+
+        // Returns only after the flusher thread synced the batch
+        assert!(add_row("durability_group_db", "rows", "a", &mut row));
+        assert_eq!(read_row("durability_group_db", "rows", "a").unwrap().value(), "1");
+    }
+}
@@ -0,0 +1,91 @@
+#[cfg(test)]
+mod test {
+    use std::ops::Bound;
+
+    use crate::db::engine::{KeyRange, StorageEngine, fs::FsEngine, memory::MemoryEngine};
+    use crate::protos::row::Row;
+
+    fn row(value: &str) -> Row {
+        let mut row: Row = Row::new();
+        row.set_value(value.to_string());
+        row.set_type("string".to_string());
+        row
+    }
+
+    /// Checks the behaviour every engine must share.
+    fn check_engine(engine: &dyn StorageEngine, db: &str) {
+        assert!(!engine.db_exists(db));
+        assert!(engine.create_db(db));
+        assert!(!engine.create_db(db));
+        assert!(engine.list_dbs().unwrap().contains(&db.to_string()));
+
+        // Writing a row creates the table
+        engine.write_row(db, "users", "a", &row("1")).unwrap();
+        engine.write_row(db, "users", "b", &row("2")).unwrap();
+        engine.write_row(db, "users", "a", &row("3")).unwrap();
+        assert!(engine.table_exists(db, "users"));
+        assert_eq!(engine.list_tables(db).unwrap(), vec!["users".to_string()]);
+
+        assert_eq!(engine.read_row(db, "users", "a").unwrap().unwrap().value(), "3");
+        assert!(engine.read_row(db, "users", "c").unwrap().is_none());
+        assert!(engine.row_exists(db, "users", "b"));
+
+        let mut keys: Vec<String> = engine.list_rows(db, "users").unwrap();
+        keys.sort();
+        assert_eq!(keys, vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(engine.read_table(db, "users").unwrap().len(), 2);
+
+        assert!(engine.delete_row(db, "users", "a").unwrap());
+        assert!(!engine.delete_row(db, "users", "a").unwrap());
+
+        engine.write_db_meta(db, "{}").unwrap();
+        assert_eq!(engine.read_db_meta(db).unwrap(), "{}");
+
+        assert!(engine.delete_table(db, "users"));
+        assert!(!engine.table_exists(db, "users"));
+
+        engine.delete_db(db).unwrap();
+        assert!(!engine.db_exists(db));
+    }
+
+    /// Checks ordered range scans of an engine.
+    fn check_scan(engine: &dyn StorageEngine, db: &str) {
+        for key in ["order:2024-02-01", "order:2023-12-31", "order:2024-01-15", "order:2024-01-01", "user:1"] {
+            engine.write_row(db, "events", key, &row(key)).unwrap();
+        }
+
+        assert_eq!(engine.list_rows(db, "events").unwrap()[0], "order:2023-12-31");
+
+        let mut range: KeyRange = KeyRange {
+            start: Bound::Included("order:2024-01".to_string()),
+            end: Bound::Excluded("order:2024-02".to_string()),
+            ..KeyRange::all()
+        };
+        assert_eq!(engine.scan_rows(db, "events", &range).unwrap(), vec!["order:2024-01-01", "order:2024-01-15"]);
+
+        range.reverse = true;
+        range.limit = 1;
+        assert_eq!(engine.scan_rows(db, "events", &range).unwrap(), vec!["order:2024-01-15"]);
+
+        range.start = Bound::Included("z".to_string());
+        assert!(engine.scan_rows(db, "events", &range).unwrap().is_empty());
+
+        engine.delete_db(db).unwrap();
+    }
+
+    #[test]
+    fn scan_rows_test() {
+        check_scan(&MemoryEngine::new(), "memory_scan_db");
+        check_scan(&FsEngine::new(), "fs_scan_db");
+    }
+
+    #[test]
+    fn memory_engine_test() {
+        check_engine(&MemoryEngine::new(), "memory_engine_db");
+    }
+
+    #[test]
+    fn fs_engine_test() {
+        check_engine(&FsEngine::new(), "fs_engine_db");
+    }
+}
@@ -0,0 +1,55 @@
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::db::{engine::engine, json_filter};
+    use crate::http::{pagination::{self, Page}, receiver::RequestHeaders, row_methods::Bunch};
+    use crate::protos::row::Row;
+
+    #[test]
+    fn cursor_test() {
+        let cursor: String = pagination::encode_cursor("user:1/ü");
+        assert_eq!(pagination::decode_cursor(&cursor).unwrap(), "user:1/ü");
+        assert!(pagination::decode_cursor("abc").is_none());
+        assert!(pagination::decode_cursor("zz").is_none());
+    }
+
+    #[test]
+    fn filter_page_test() {
+        let db: &str = "filter_page_db";
+        for i in 0..1200 {
+            let mut row: Row = Row::new();
+            row.set_value(format!("{{\"id\": {}, \"even\": {}}}", i, i % 2 == 0));
+            row.set_type("json".to_string());
+            engine().write_row(db, "items", &format!("{:04}", i), &row).unwrap();
+        }
+
+        let mut req: RequestHeaders = RequestHeaders {
+            params: HashMap::from([("limit".to_string(), "400".to_string())]),
+            ..Default::default()
+        };
+
+        let mut seen: Vec<String> = Vec::new();
+        loop {
+            let page: Page = Page::from_headers(&req).unwrap().unwrap();
+            let matches: Vec<Bunch> = json_filter::filter_range(db, "items", "{\"even\": true}", &page.range()).unwrap();
+            let (data, next): (Vec<Bunch>, Option<String>) = page.split(matches, |b| b.key.as_str());
+
+            seen.extend(data.into_iter().map(|b| b.key));
+            match next {
+                Some(cursor) => req.params.insert("cursor".to_string(), cursor),
+                None => break
+            };
+        }
+
+        assert_eq!(seen.len(), 600);
+        assert_eq!(seen[0], "0000");
+        assert_eq!(seen[599], "1198");
+
+        // Rows without the filtered field do not match instead of failing the request
+        assert_eq!(json_filter::filter(db, "items", "{\"missing\": 1}").unwrap().len(), 0);
+        assert!(json_filter::filter(db, "items", "[1]").is_err());
+
+        engine().delete_db(db).unwrap();
+    }
+}
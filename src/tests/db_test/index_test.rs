@@ -0,0 +1,42 @@
+#[cfg(test)]
+mod test {
+    use serde_json::{Map, Value};
+
+    use crate::cache;
+    use crate::db::{engine::engine, index, json_filter};
+
+    fn filter(json: &str) -> Map<String, Value> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn index_test() {
+        let db: &str = "index_test_db";
+        cache::add(db, "users", "1", "{\"user\": {\"email\": \"a@x.io\"}}", "json");
+        cache::add(db, "users", "2", "{\"user\": {\"email\": \"b@x.io\"}}", "json");
+
+        assert!(index::create(db, "users", "user.email").unwrap());
+        assert!(!index::create(db, "users", "user.email").unwrap());
+        assert!(index::create(db, "users", "user..email").is_err());
+        assert_eq!(index::paths(db, "users"), vec!["user.email".to_string()]);
+
+        // Writes after the build are picked up
+        cache::add(db, "users", "3", "{\"user\": {\"email\": \"a@x.io\"}}", "json");
+        let by_email: Map<String, Value> = filter("{\"user\": {\"email\": \"a@x.io\"}}");
+        assert_eq!(index::lookup(db, "users", &by_email).unwrap(), vec!["1".to_string(), "3".to_string()]);
+        assert_eq!(json_filter::filter(db, "users", "{\"user\": {\"email\": \"a@x.io\"}}").unwrap().len(), 2);
+
+        cache::delete(db, "users", "1").unwrap();
+        assert_eq!(index::lookup(db, "users", &by_email).unwrap(), vec!["3".to_string()]);
+
+        // Not indexed paths fall back to the scan
+        assert!(index::lookup(db, "users", &filter("{\"user\": {\"name\": \"a\"}}")).is_none());
+
+        assert!(index::drop(db, "users", "user.email").unwrap());
+        assert!(!index::drop(db, "users", "user.email").unwrap());
+        assert!(index::lookup(db, "users", &by_email).is_none());
+        assert_eq!(json_filter::filter(db, "users", "{\"user\": {\"email\": \"a@x.io\"}}").unwrap().len(), 1);
+
+        engine().delete_db(db).unwrap();
+    }
+}
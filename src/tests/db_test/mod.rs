@@ -1,2 +1,25 @@
 pub mod row_test;
-pub mod table_test;
\ No newline at end of file
+pub mod table_test;
+pub mod segment_test;
+pub mod wal_test;
+pub mod durability_test;
+pub mod engine_test;
+pub mod filter_test;
+pub mod index_test;
+pub mod patch_test;
+pub mod ttl_test;
+pub mod snapshot_test;
+pub mod transfer_test;
+pub mod names_test;
+pub mod bytes_test;
+pub mod schema_test;
+pub mod stats_test;
+pub mod rename_test;
+pub mod verify_test;
+pub mod tx_test;
+pub mod bunch_test;
+pub mod multi_get_test;
+pub mod delete_test;
+pub mod counter_test;
+pub mod collection_test;
+pub mod cdc_test;
\ No newline at end of file
@@ -0,0 +1,51 @@
+#[cfg(test)]
+mod test {
+    use serde_json::{json, Value};
+
+    use crate::cache;
+    use crate::http::{receiver::RequestHeaders, row_methods};
+
+    const DB: &str = "multi_get_test_db";
+
+    /// Drops the tables an earlier run left behind.
+    fn reset(tables: &[&str]) {
+        for table in tables {
+            cache::delete_table(DB, table);
+        }
+    }
+
+    #[test]
+    fn multi_get_test() {
+        reset(&["users", "orders"]);
+        assert!(cache::add(DB, "users", "1", "alice", "string"));
+        assert!(cache::add(DB, "users", "2", "{\"name\":\"bob\"}", "json"));
+        assert!(cache::add(DB, "orders", "7", "3", "int"));
+
+        let head: RequestHeaders = RequestHeaders {
+            db: DB.to_string(),
+            table: "users".to_string(),
+            ..Default::default()
+        };
+        let body: String = json!([
+            "1",
+            ["orders", "7"],
+            {"table": "users", "key": "2"},
+            "missing",
+            ["", "1"],
+            "1"
+        ]).to_string();
+
+        let response: String = row_methods::multi_get(&head, &body).unwrap();
+        let rows: Value = serde_json::from_str(response.trim_end_matches("\njson")).unwrap();
+
+        assert_eq!(rows[0], json!({"table": "users", "key": "1", "found": true, "value": "alice", "_type": "string"}));
+        assert_eq!(rows[1], json!({"table": "orders", "key": "7", "found": true, "value": "3", "_type": "int"}));
+        assert_eq!(rows[2]["value"], "{\"name\":\"bob\"}");
+        assert_eq!(rows[3], json!({"table": "users", "key": "missing", "found": false}));
+        assert_eq!(rows[4]["found"], false);
+        assert!(rows[4]["error"].is_string());
+        assert_eq!(rows[5], rows[0]);
+
+        assert!(row_methods::multi_get(&head, "{\"key\": 1}").is_err());
+    }
+}
@@ -0,0 +1,99 @@
+#[cfg(test)]
+mod test {
+    use crate::db::{engine::engine, names, segment};
+    use crate::http::receiver::{get_header, RequestHeaders};
+    use crate::protos::row::Row;
+
+    fn row(value: &str) -> Row {
+        let mut row: Row = Row::new();
+        row.set_value(value.to_string());
+        row.set_type("string".to_string());
+        row
+    }
+
+    #[test]
+    fn encode_test() {
+        assert_eq!(names::encode("users_2024-v1.db"), "users_2024-v1.db");
+        assert_eq!(names::encode("../../x"), "%2E.%2F..%2Fx");
+        assert_eq!(names::encode("a/b"), "a%2Fb");
+        assert_eq!(names::encode("."), "%2E");
+
+        for name in ["../../x", "a/b", ".", "..", "50%", "ключ", "a b\\c", "x\0y"] {
+            let encoded: String = names::encode(name);
+            assert!(!encoded.contains('/') && !encoded.starts_with('.'));
+            assert_eq!(names::decode(&encoded).unwrap(), name);
+        }
+
+        assert_eq!(names::decode("%2"), None);
+        assert_eq!(names::decode("%+1"), None);
+    }
+
+    #[test]
+    fn check_test() {
+        assert!(names::check_name("table", "").is_err());
+        assert!(names::check_name("table", &"a".repeat(names::MAX_NAME_LEN)).is_ok());
+        assert!(names::check_name("table", &"a".repeat(names::MAX_NAME_LEN + 1)).is_err());
+        // Encoding makes the name three times longer
+        assert!(names::check_name("table", &"/".repeat(100)).is_err());
+
+        assert!(names::check_key("").is_err());
+        assert!(names::check_key(&"k".repeat(names::MAX_KEY_LEN + 1)).is_err());
+
+        assert_eq!(names::decode_hex_key("612f62").unwrap(), "a/b");
+        assert!(names::decode_hex_key("6").is_err());
+        assert!(names::decode_hex_key("zz").is_err());
+    }
+
+    #[test]
+    fn binary_key_test() {
+        let db: &str = "names_binary_db";
+        let bytes: Vec<u8> = vec![0xff, b'a', 0x00, 0xc3, 0xf4, 0x8f, 0xbf, 0xbf];
+
+        let key: String = names::decode_hex_key("ff6100c3f48fbfbf").unwrap();
+        assert_eq!(names::key_to_bytes(&key), bytes);
+        assert!(names::check_key(&key).is_ok());
+        assert_ne!(key, names::decode_hex_key("3f6100c3f48fbfbf").unwrap());
+
+        // A reserved char sent as text would not read back as itself
+        assert!(names::check_key("\u{10FF61}").is_err());
+
+        engine().write_row(db, "t", &key, &row("binary")).unwrap();
+        assert_eq!(engine().read_row(db, "t", &key).unwrap().unwrap().value(), "binary");
+
+        // The key survives a reload of the segments
+        segment::forget(db, "t");
+        assert_eq!(segment::keys(db, "t").unwrap(), vec![key]);
+
+        engine().delete_db(db).unwrap();
+    }
+
+    #[test]
+    fn storage_test() {
+        let db: &str = "names_test_db";
+        let storage = engine();
+
+        assert!(!storage.create_table(db, ""));
+        assert!(storage.write_row(db, "t", "", &row("x")).is_err());
+
+        storage.write_row(db, "t", "../../other_db/t/x", &row("escaped")).unwrap();
+        storage.write_row(db, "t", "ключ with spaces", &row("utf8")).unwrap();
+        storage.write_row(db, "a/b", "k", &row("nested")).unwrap();
+
+        assert!(!storage.db_exists("other_db"));
+        assert_eq!(storage.read_row(db, "t", "../../other_db/t/x").unwrap().unwrap().value(), "escaped");
+        assert_eq!(storage.read_row(db, "t", "ключ with spaces").unwrap().unwrap().value(), "utf8");
+        assert_eq!(storage.read_row(db, "a/b", "k").unwrap().unwrap().value(), "nested");
+        assert_eq!(storage.list_tables(db).unwrap(), vec!["a/b".to_string(), "t".to_string()]);
+
+        storage.delete_db(db).unwrap();
+    }
+
+    #[test]
+    fn header_test() {
+        let (req, head): (String, RequestHeaders) = get_header("req: get_row\r\ndb: d\nkey: a key with spaces\nkey_encoding: hex\n".to_string());
+        assert_eq!(req, "get_row");
+        assert_eq!(head.db, "d");
+        assert_eq!(head.key, "a key with spaces");
+        assert_eq!(head.param("key_encoding"), Some("hex"));
+    }
+}
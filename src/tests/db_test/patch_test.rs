@@ -0,0 +1,93 @@
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, thread};
+    use serde_json::{json, Value};
+
+    use crate::cache;
+    use crate::db::{engine::engine, json_patch, wal};
+    use crate::http::receiver::RequestHeaders;
+    use crate::protos::row::Row;
+    use crate::tx_pool::req_handler::handle_request;
+
+    #[test]
+    fn merge_patch_test() {
+        let doc: Value = json!({"title": "Goodbye!", "author": {"givenName": "John", "familyName": "Doe"}, "tags": ["example", "sample"]});
+        let patch: Value = json!({"title": "Hello!", "author": {"familyName": null}, "tags": ["example"], "phoneNumber": "+01-123-456-7890"});
+
+        assert_eq!(
+            json_patch::patch(&doc, &patch, None).unwrap(),
+            json!({"title": "Hello!", "author": {"givenName": "John"}, "tags": ["example"], "phoneNumber": "+01-123-456-7890"})
+        );
+    }
+
+    #[test]
+    fn json_patch_test() {
+        let doc: Value = json!({"foo": ["bar", "baz"], "a/b": 1});
+        let patch: Value = json!([
+            {"op": "add", "path": "/foo/1", "value": "qux"},
+            {"op": "remove", "path": "/foo/0"},
+            {"op": "replace", "path": "/a~1b", "value": 2},
+            {"op": "copy", "from": "/a~1b", "path": "/c"},
+            {"op": "move", "from": "/c", "path": "/foo/-"},
+            {"op": "test", "path": "/foo", "value": ["qux", "baz", 2]}
+        ]);
+        assert_eq!(json_patch::patch(&doc, &patch, None).unwrap(), json!({"foo": ["qux", "baz", 2], "a/b": 2}));
+
+        // A failed operation leaves the document untouched
+        assert!(json_patch::patch(&doc, &json!([{"op": "remove", "path": "/nope"}]), None).is_err());
+        assert!(json_patch::patch(&doc, &json!([{"op": "test", "path": "/a~1b", "value": 5}]), None).is_err());
+        assert!(json_patch::patch(&doc, &json!({}), Some("xml")).is_err());
+    }
+
+    #[test]
+    fn patch_under_key_lock_test() {
+        let db: &str = "patch_lock_db";
+        cache::add(db, "t", "counter", "{\"n\": 0}", "json");
+
+        let patch: Arc<Value> = Arc::new(json!([{"op": "add", "path": "/list/-", "value": 1}]));
+        cache::update(db, "t", "counter", |mut row| {
+            row.set_value("{\"n\": 0, \"list\": []}".to_string());
+            Ok(row)
+        }).unwrap();
+
+        let workers: Vec<thread::JoinHandle<()>> = (0..8).map(|_| {
+            let patch: Arc<Value> = patch.clone();
+            thread::spawn(move || {
+                for _ in 0..10 {
+                    cache::update(db, "t", "counter", |mut row| {
+                        let doc: Value = serde_json::from_str(row.value()).unwrap();
+                        row.set_value(json_patch::patch(&doc, &patch, None)?.to_string());
+                        Ok(row)
+                    }).unwrap();
+                }
+            })
+        }).collect();
+        workers.into_iter().for_each(|w| w.join().unwrap());
+
+        let row: Row = engine().read_row(db, "t", "counter").unwrap().unwrap();
+        let doc: Value = serde_json::from_str(row.value()).unwrap();
+        assert_eq!(doc["list"].as_array().unwrap().len(), 80);
+        assert!(cache::update(db, "t", "missing", Ok).is_err());
+
+        engine().delete_db(db).unwrap();
+    }
+
+    #[test]
+    fn patch_request_test() {
+        let db: &str = "patch_request_db";
+        cache::add(db, "t", "doc", "{\"n\": 0}", "json");
+        let head: RequestHeaders = RequestHeaders {
+            db: db.to_string(),
+            table: "t".to_string(),
+            key: "doc".to_string(),
+            ..Default::default()
+        };
+
+        // The patched row is logged instead of the patch, which may not apply twice
+        assert!(wal::is_row_change("patch_row") && !wal::is_mutation("patch_row"));
+        assert_eq!(handle_request("patch_row", &head, "[{\"op\": \"add\", \"path\": \"/list\", \"value\": [1]}]", "s").unwrap(), "{\"list\":[1],\"n\":0}\njson");
+        assert!(handle_request("patch_row", &RequestHeaders { key: "missing".to_string(), ..head.clone() }, "{}", "s").is_err());
+
+        engine().delete_db(db).unwrap();
+    }
+}
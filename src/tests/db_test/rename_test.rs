@@ -0,0 +1,67 @@
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use crate::cache::{self, cache_db};
+    use crate::db::{engine::engine, index, schema, stats::{self, TableStats}};
+
+    #[test]
+    fn rename_table_test() {
+        let db: &str = "rename_test_db";
+        assert!(cache::add(db, "items", "1", "{\"name\":\"a\"}", "json"));
+        assert!(cache::add(db, "items", "2", "{\"name\":\"b\"}", "json"));
+        assert!(cache::add(db, "taken", "x", "1", "int"));
+        index::create(db, "items", "name").unwrap();
+        schema::set(db, "items", json!({"type": "object"})).unwrap();
+        assert_eq!(stats::table_stats(db, "items").unwrap().rows, 2);
+
+        // The target name is already used
+        assert!(cache::rename_table(db, "items", "taken").is_err());
+        assert!(cache::rename_table(db, "missing", "other").is_err());
+
+        cache::rename_table(db, "items", "goods").unwrap();
+        assert!(!engine().table_exists(db, "items"));
+        assert!(cache::get(db, "items", "1").is_err());
+        assert_eq!(cache::get(db, "goods", "1").unwrap().value(), "{\"name\":\"a\"}");
+        assert_eq!(cache::cached_rows(db, Some("goods")), 2);
+        assert_eq!(cache::cached_rows(db, Some("items")), 0);
+
+        assert_eq!(index::paths(db, "goods"), vec!["name".to_string()]);
+        assert!(index::paths(db, "items").is_empty());
+        assert!(schema::get(db, "goods").is_some());
+        assert!(schema::get(db, "items").is_none());
+
+        let goods: TableStats = stats::table_stats(db, "goods").unwrap();
+        assert_eq!(goods.rows, 2);
+
+        // Writes keep working under the new name
+        assert!(cache::add(db, "goods", "3", "{\"name\":\"c\"}", "json"));
+        assert_eq!(engine().list_tables(db).unwrap(), vec!["goods".to_string(), "taken".to_string()]);
+
+        engine().delete_db(db).unwrap();
+        stats::forget_db(db);
+        index::forget_db(db);
+    }
+
+    #[test]
+    fn rename_db_test() {
+        let db: &str = "rename_test_from_db";
+        let to: &str = "rename_test_to_db";
+        let taken: &str = "rename_test_taken_db";
+        assert!(cache::add(db, "items", "1", "hello", "string"));
+        assert!(engine().create_db(taken));
+
+        assert!(cache_db::rename_db(db, taken).is_err());
+
+        cache_db::rename_db(db, to).unwrap();
+        assert!(!engine().db_exists(db));
+        assert!(engine().list_dbs().unwrap().contains(&to.to_string()));
+        assert_eq!(cache::get(to, "items", "1").unwrap().value(), "hello");
+        assert_eq!(cache::cached_rows(to, None), 1);
+        assert_eq!(cache::cached_rows(db, None), 0);
+
+        engine().delete_db(to).unwrap();
+        engine().delete_db(taken).unwrap();
+        stats::forget_db(to);
+    }
+}
@@ -0,0 +1,93 @@
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use serde_json::{json, Value};
+
+    use crate::cache::{self, PutMode};
+    use crate::db::{engine::engine, meta, schema::{self, Schema, Violation}, transfer::{self, CsvColumns, Format, ImportReport}};
+    use crate::protos::row::Row;
+
+    fn row(value: &str, _type: &str) -> Row {
+        let mut row: Row = Row::new();
+        row.set_value(value.to_string());
+        row.set_type(_type.to_string());
+        row
+    }
+
+    fn validate(schema: &Value, value: &Value) -> Vec<Violation> {
+        Schema::compile(schema.clone()).unwrap().validate(value)
+    }
+
+    fn pointers(errors: Vec<Violation>) -> Vec<String> {
+        errors.into_iter().map(|v| v.pointer).collect()
+    }
+
+    #[test]
+    fn validate_test() {
+        let orders: Value = json!({
+            "type": "object",
+            "required": ["id", "price"],
+            "properties": {
+                "id": {"type": "integer", "minimum": 1},
+                "price": {"$ref": "#/definitions/price"},
+                "tags": {"type": "array", "items": {"type": "string", "pattern": "^[a-z]+$"}, "uniqueItems": true},
+                "a/b": {"enum": ["x", "y"]}
+            },
+            "additionalProperties": false,
+            "definitions": {"price": {"type": "number", "exclusiveMinimum": 0}}
+        });
+
+        assert!(validate(&orders, &json!({"id": 1, "price": 9.5, "tags": ["a", "b"], "a/b": "x"})).is_empty());
+        assert_eq!(pointers(validate(&orders, &json!({"id": 1, "price": "abc"}))), vec!["/price"]);
+        assert_eq!(
+            pointers(validate(&orders, &json!({"id": 0, "tags": ["ok", "Bad", "ok"], "a/b": "z", "extra": 1}))),
+            vec!["/price", "/a~1b", "/extra", "/id", "/tags", "/tags/1"]
+        );
+
+        let one_of: Value = json!({"oneOf": [{"type": "integer"}, {"type": "number", "minimum": 2}]});
+        assert!(validate(&one_of, &json!(1)).is_empty());
+        assert_eq!(validate(&one_of, &json!(3)).len(), 1);
+        assert!(validate(&json!(false), &json!(null)).len() == 1);
+    }
+
+    #[test]
+    fn table_schema_test() {
+        let db: &str = "schema_test_db";
+        assert!(engine().create_table(db, "orders"));
+
+        assert!(schema::set(db, "orders", json!({"type": "object", "pattern": "("})).is_err());
+        assert!(schema::set(db, "orders", json!({"type": "money"})).is_err());
+        assert!(schema::set(db, "missing", json!({})).is_err());
+
+        schema::set(db, "orders", json!({"properties": {"price": {"type": "number"}}})).unwrap();
+        assert!(schema::get(db, "orders").is_some());
+
+        assert!(schema::check(db, "orders", &row("{\"price\": 5}", "json")).is_ok());
+        assert!(schema::check(db, "orders", &row("plain", "string")).is_err());
+        let err: String = schema::check(db, "orders", &row("{\"price\": \"abc\"}", "json")).unwrap_err();
+        assert!(err.starts_with("{") && err.ends_with("\njson") && err.contains("\"pointer\":\"/price\""));
+
+        let columns: CsvColumns = CsvColumns::parse(None, None);
+        let input: &str = "{\"key\": \"1\", \"value\": {\"price\": 1}, \"type\": \"json\"}\n{\"key\": \"2\", \"value\": {\"price\": \"abc\"}, \"type\": \"json\"}";
+        let report: ImportReport = transfer::import(db, "orders", Format::Ndjson, &columns, PutMode::Upsert, Cursor::new(input)).unwrap();
+        assert_eq!((report.imported, report.failed), (1, 1));
+        assert!(report.errors[0].message.contains("/price"));
+
+        schema::set(db, "orders", Value::Null).unwrap();
+        assert!(schema::get(db, "orders").is_none());
+        assert!(schema::check(db, "orders", &row("plain", "string")).is_ok());
+
+        // The expressions are compiled again when the metadata is read back
+        schema::set(db, "orders", json!({"patternProperties": {"^x_": {"type": "string", "pattern": "^[a-z]+$"}}})).unwrap();
+        meta::forget_db(db);
+        assert!(schema::check(db, "orders", &row("{\"x_name\": \"abc\"}", "json")).is_ok());
+        assert!(schema::check(db, "orders", &row("{\"x_name\": \"ABC\"}", "json")).is_err());
+
+        schema::set(db, "orders", json!({})).unwrap();
+        assert!(cache::delete_table(db, "orders"));
+        assert!(schema::get(db, "orders").is_none());
+
+        engine().delete_db(db).unwrap();
+    }
+}
@@ -0,0 +1,176 @@
+#[cfg(test)]
+mod test {
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+
+    use crate::config;
+    use crate::db::{checksum, segment, table::create_table};
+
+    /// Flips the first byte of `needle` in a segment file.
+    fn damage(path: &str, needle: &[u8]) {
+        let mut bytes: Vec<u8> = fs::read(path).unwrap();
+        let at: usize = bytes.windows(needle.len()).position(|w| w == needle).unwrap();
+        bytes[at] ^= 0xFF;
+        fs::write(path, bytes).unwrap();
+    }
+
+    /// Creates a table, dropping what an earlier run left in it.
+    fn fresh_table(table: &str) {
+        let _ = segment::delete_table("segment_test_db", table);
+        create_table("segment_test_db", table);
+    }
+
+    #[test]
+    fn put_get_delete_test() {
+        fresh_table("rows");
+
+        segment::put("segment_test_db", "rows", "a", b"first").unwrap();
+        segment::put("segment_test_db", "rows", "a", b"second").unwrap();
+        assert_eq!(segment::get("segment_test_db", "rows", "a").unwrap(), Some(b"second".to_vec()));
+
+        assert!(segment::delete("segment_test_db", "rows", "a").unwrap());
+        assert!(!segment::delete("segment_test_db", "rows", "a").unwrap());
+        assert_eq!(segment::get("segment_test_db", "rows", "a").unwrap(), None);
+    }
+
+    #[test]
+    fn rebuild_and_compact_test() {
+        fresh_table("rebuild");
+
+        for i in 0..10 {
+            segment::put("segment_test_db", "rebuild", &format!("k{}", i), format!("v{}", i).as_bytes()).unwrap();
+        }
+        segment::put("segment_test_db", "rebuild", "k0", b"new").unwrap();
+        segment::delete("segment_test_db", "rebuild", "k1").unwrap();
+
+        // Index must be rebuilt from the segment files
+        segment::forget("segment_test_db", "rebuild");
+        assert_eq!(segment::keys("segment_test_db", "rebuild").unwrap().len(), 9);
+        assert_eq!(segment::get("segment_test_db", "rebuild", "k0").unwrap(), Some(b"new".to_vec()));
+
+        segment::compact("segment_test_db", "rebuild").unwrap();
+        segment::forget("segment_test_db", "rebuild");
+        assert_eq!(segment::keys("segment_test_db", "rebuild").unwrap().len(), 9);
+        assert_eq!(segment::get("segment_test_db", "rebuild", "k1").unwrap(), None);
+        assert_eq!(segment::get("segment_test_db", "rebuild", "k9").unwrap(), Some(b"v9".to_vec()));
+    }
+
+    #[test]
+    fn torn_record_test() {
+        fresh_table("torn");
+        segment::put("segment_test_db", "torn", "ok", b"value").unwrap();
+        segment::forget("segment_test_db", "torn");
+
+        // Half of a record, as if the process died in the middle of a write
+        let path: String = format!("{}/segment_test_db/torn/{:010}.seg", config::CONFIG.db_path, 1);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[1, 2, 0, 0, 0]).unwrap();
+
+        assert_eq!(segment::get("segment_test_db", "torn", "ok").unwrap(), Some(b"value".to_vec()));
+        segment::put("segment_test_db", "torn", "next", b"value").unwrap();
+        segment::forget("segment_test_db", "torn");
+        assert_eq!(segment::keys("segment_test_db", "torn").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn damaged_length_test() {
+        fresh_table("lengths");
+        segment::put("segment_test_db", "lengths", "a", b"first").unwrap();
+        segment::put("segment_test_db", "lengths", "b", b"second").unwrap();
+        segment::put("segment_test_db", "lengths", "c", b"third").unwrap();
+        segment::forget("segment_test_db", "lengths");
+
+        // The value length of the middle record claims 4 GB
+        let path: String = format!("{}/segment_test_db/lengths/{:010}.seg", config::CONFIG.db_path, 1);
+        let mut bytes: Vec<u8> = fs::read(&path).unwrap();
+        let at: usize = bytes.windows(6).position(|w| w == b"second").unwrap() - 1 - 4 - 4;
+        bytes[at..at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        fs::write(&path, &bytes).unwrap();
+
+        // The replay stops at the damaged record and keeps the rest of the segment in place
+        assert_eq!(segment::get("segment_test_db", "lengths", "a").unwrap(), Some(b"first".to_vec()));
+        assert_eq!(segment::get("segment_test_db", "lengths", "b").unwrap(), None);
+        assert_eq!(segment::get("segment_test_db", "lengths", "c").unwrap(), None);
+        assert_eq!(segment::skipped("segment_test_db", "lengths").unwrap().len(), 1);
+        assert_eq!(fs::read(&path).unwrap().len(), bytes.len());
+
+        // New records are not appended after the skipped bytes
+        segment::put("segment_test_db", "lengths", "d", b"fourth").unwrap();
+        segment::forget("segment_test_db", "lengths");
+        assert_eq!(segment::keys("segment_test_db", "lengths").unwrap(), vec!["a".to_string(), "d".to_string()]);
+    }
+
+    #[test]
+    fn phantom_record_test() {
+        fresh_table("phantom");
+
+        // A value holding a whole encoded record of another key
+        let mut ghost: Vec<u8> = vec![1];
+        ghost.extend_from_slice(&5u32.to_le_bytes());
+        ghost.extend_from_slice(&5u32.to_le_bytes());
+        ghost.extend_from_slice(&checksum::crc32(b"ghostvalue").to_le_bytes());
+        ghost.extend_from_slice(b"ghostvalue");
+        segment::put("segment_test_db", "phantom", "a", b"first").unwrap();
+        segment::put("segment_test_db", "phantom", "outer", &ghost).unwrap();
+        segment::forget("segment_test_db", "phantom");
+
+        // The value length of the outer record claims 4 GB
+        let path: String = format!("{}/segment_test_db/phantom/{:010}.seg", config::CONFIG.db_path, 1);
+        let mut bytes: Vec<u8> = fs::read(&path).unwrap();
+        let at: usize = bytes.windows(5).position(|w| w == b"outer").unwrap() - 4 - 4;
+        bytes[at..at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        fs::write(&path, &bytes).unwrap();
+
+        assert_eq!(segment::keys("segment_test_db", "phantom").unwrap(), vec!["a".to_string()]);
+        assert_eq!(segment::get("segment_test_db", "phantom", "ghost").unwrap(), None);
+    }
+
+    #[test]
+    fn checksum_mismatch_test() {
+        fresh_table("damaged");
+        segment::put("segment_test_db", "damaged", "a", b"hello").unwrap();
+        segment::put("segment_test_db", "damaged", "b", b"world").unwrap();
+        segment::forget("segment_test_db", "damaged");
+
+        let path: String = format!("{}/segment_test_db/damaged/{:010}.seg", config::CONFIG.db_path, 1);
+        damage(&path, b"hello");
+
+        let err: String = segment::get("segment_test_db", "damaged", "a").unwrap_err();
+        assert!(checksum::is_corrupted(&err), "{}", err);
+        assert_eq!(segment::get("segment_test_db", "damaged", "b").unwrap(), Some(b"world".to_vec()));
+
+        // The damaged record is not carried into a compacted segment
+        assert!(segment::compact("segment_test_db", "damaged").is_err());
+
+        // The partial segment is removed, so nothing stale replays after the newer writes
+        let partial: String = format!("{}/segment_test_db/damaged/{:010}.seg", config::CONFIG.db_path, 2);
+        assert!(fs::metadata(&partial).is_err());
+        segment::delete("segment_test_db", "damaged", "b").unwrap();
+        segment::forget("segment_test_db", "damaged");
+        assert_eq!(segment::get("segment_test_db", "damaged", "b").unwrap(), None);
+    }
+
+    #[test]
+    fn legacy_segment_test() {
+        fresh_table("legacy");
+
+        // A segment of version 1, records without checksums
+        let mut bytes: Vec<u8> = b"RDSG\x01".to_vec();
+        bytes.push(1);
+        bytes.extend_from_slice(&3u32.to_le_bytes());
+        bytes.extend_from_slice(&5u32.to_le_bytes());
+        bytes.extend_from_slice(b"oldvalue");
+        let path: String = format!("{}/segment_test_db/legacy/{:010}.seg", config::CONFIG.db_path, 1);
+        fs::write(&path, &bytes).unwrap();
+
+        assert_eq!(segment::get("segment_test_db", "legacy", "old").unwrap(), Some(b"value".to_vec()));
+
+        // New records go to a segment of the current version
+        segment::put("segment_test_db", "legacy", "new", b"value").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), bytes);
+
+        segment::forget("segment_test_db", "legacy");
+        assert_eq!(segment::keys("segment_test_db", "legacy").unwrap(), vec!["new".to_string(), "old".to_string()]);
+        assert_eq!(segment::get("segment_test_db", "legacy", "new").unwrap(), Some(b"value".to_vec()));
+    }
+}
@@ -0,0 +1,94 @@
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use serde_json::{json, Value};
+
+    use crate::{cache, config};
+    use crate::db::{checksum, engine::{engine, CapturedRows}, meta, segment, snapshot::{self, Manifest}};
+
+    #[test]
+    fn crc32_test() {
+        assert_eq!(checksum::crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(checksum::crc32(b""), 0);
+    }
+
+    #[test]
+    fn snapshot_restore_test() {
+        let db: &str = "snap_source_db";
+        let path: &str = "snapshots_test/source.snap";
+        let file: String = format!("{}/{}", config::CONFIG.snapshot_path, path);
+
+        cache::add(db, "users", "1", "{\"name\": \"a\"}", "json");
+        cache::add(db, "users", "2", "{\"name\": \"b\"}", "json");
+        cache::add(db, "counters", "hits", "10", "int");
+        engine().create_table(db, "empty");
+        meta::set_db_meta(db, &meta::DbMeta { durability: Some("none".to_string()), ..Default::default() }).unwrap();
+
+        let manifest: Manifest = snapshot::create(&[db.to_string()], path).unwrap();
+        assert_eq!(manifest.databases[0].tables.len(), 3);
+        assert_eq!(manifest.databases[0].tables.iter().map(|t| t.rows).sum::<u64>(), 3);
+        assert!(snapshot::verify(path).is_ok());
+
+        // Restore into a new name
+        assert_eq!(snapshot::restore(path, Some(db), Some("snap_copy_db"), false).unwrap(), vec!["snap_copy_db".to_string()]);
+        assert_eq!(engine().read_row("snap_copy_db", "users", "2").unwrap().unwrap().value(), "{\"name\": \"b\"}");
+        assert!(engine().table_exists("snap_copy_db", "empty"));
+        assert_eq!(meta::get_db_meta("snap_copy_db").durability.unwrap(), "none");
+
+        // Existing databases are only replaced on request
+        assert!(snapshot::restore(path, None, Some("snap_copy_db"), false).is_err());
+        cache::add("snap_copy_db", "users", "3", "{}", "json");
+        snapshot::restore(path, None, Some("snap_copy_db"), true).unwrap();
+        assert!(!engine().row_exists("snap_copy_db", "users", "3"));
+        assert!(!engine().db_exists("snap_copy_db~restore"));
+
+        // A restore which fails on a row leaves the live database as it was
+        let rows: String = "{\"db\":\"snap_source_db\",\"table\":\"users\",\"key\":\"1\",\"value\":\"{}\",\"type\":\"json\"}\n".to_string()
+            + "{\"db\":\"snap_source_db\",\"table\":\"users\",\"key\":\"9\",\"value\":\"not a list\",\"type\":\"list\"}\n";
+        let manifest: Value = json!({"format": snapshot::FORMAT, "version": snapshot::VERSION, "created_at": 0, "databases": [
+            {"name": db, "tables": [{"name": "users", "rows": 2, "checksum": format!("{:08x}", checksum::crc32(rows.as_bytes()))}]}
+        ]});
+        fs::write(format!("{}/snapshots_test/odd.snap", config::CONFIG.snapshot_path), manifest.to_string() + "\n" + &rows).unwrap();
+        assert!(snapshot::verify("snapshots_test/odd.snap").is_ok());
+
+        cache::add("snap_copy_db", "users", "3", "{}", "json");
+        assert!(snapshot::restore("snapshots_test/odd.snap", None, Some("snap_copy_db"), true).is_err());
+        assert!(engine().row_exists("snap_copy_db", "users", "3"));
+        assert!(!engine().db_exists("snap_copy_db~restore"));
+
+        // A changed row is caught by the checksum
+        let content: String = fs::read_to_string(&file).unwrap();
+        fs::write(&file, content.replace("\\\"b\\\"", "\\\"c\\\"")).unwrap();
+        assert!(snapshot::verify(path).is_err());
+        assert!(snapshot::restore(path, None, Some("snap_broken_db"), false).is_err());
+        assert!(!engine().db_exists("snap_broken_db"));
+
+        // Archives stay inside the snapshot directory
+        assert!(snapshot::create(&[db.to_string()], "../outside.snap").is_err());
+        assert!(snapshot::create(&[db.to_string()], "/tmp/outside.snap").is_err());
+        assert!(snapshot::verify("snapshots_test/../../db/x.snap").is_err());
+
+        engine().delete_db(db).unwrap();
+        engine().delete_db("snap_copy_db").unwrap();
+        fs::remove_dir_all(format!("{}/snapshots_test", config::CONFIG.snapshot_path)).unwrap();
+    }
+
+    #[test]
+    fn capture_test() {
+        let db: &str = "snap_capture_db";
+        cache::add(db, "rows", "a", "1", "int");
+        cache::add(db, "rows", "b", "2", "int");
+
+        let rows: CapturedRows = engine().capture_table(db, "rows").unwrap();
+
+        // Writes and a compaction after the capture do not change what it reads
+        engine().delete_row(db, "rows", "a").unwrap();
+        cache::add(db, "rows", "c", "3", "int");
+        segment::compact(db, "rows").unwrap();
+
+        let captured: Vec<(String, String)> = rows.map(|row| row.map(|(key, row)| (key, row.value().to_string())).unwrap()).collect();
+        assert_eq!(captured, vec![("a".to_string(), "1".to_string()), ("b".to_string(), "2".to_string())]);
+
+        engine().delete_db(db).unwrap();
+    }
+}
@@ -0,0 +1,71 @@
+#[cfg(test)]
+mod test {
+    use crate::cache::{self, PutMode};
+    use crate::db::{engine::engine, stats::{self, Counts, DbStats, TableStats}};
+    use crate::protos::row::Row;
+
+    fn row(value: &str, _type: &str) -> Row {
+        let mut row: Row = Row::new();
+        row.set_value(value.to_string());
+        row.set_type(_type.to_string());
+        row
+    }
+
+    #[test]
+    fn table_stats_test() {
+        let db: &str = "stats_test_db";
+        assert!(cache::add(db, "items", "1", "hello", "string"));
+        assert!(cache::add(db, "items", "2", "42", "int"));
+
+        // Counted by the writes
+        let first: TableStats = stats::table_stats(db, "items").unwrap();
+        assert_eq!((first.rows, first.bytes), (2, 7));
+        assert!(first.last_write_at.is_some());
+
+        // Kept up to date by the writes afterwards
+        assert!(cache::add(db, "items", "3", "{\"a\":1}", "json"));
+        assert!(cache::put(db, "items", "1", &row("hi", "string"), PutMode::Update).unwrap());
+        cache::delete(db, "items", "2").unwrap();
+        assert!(cache::add(db, "other", "x", "1", "int"));
+
+        let table: TableStats = stats::table_stats(db, "items").unwrap();
+        assert_eq!((table.rows, table.bytes), (2, 9));
+        assert_eq!(table.types.get("string"), Some(&Counts { rows: 1, bytes: 2 }));
+        assert_eq!(table.types.get("json"), Some(&Counts { rows: 1, bytes: 7 }));
+        assert!(!table.types.contains_key("int"));
+        assert_eq!(table.cached_rows, 2);
+
+        let all: DbStats = stats::db_stats(db).unwrap();
+        assert_eq!((all.rows, all.bytes, all.tables.len()), (3, 10, 2));
+        assert_eq!(all.types.get("int"), Some(&Counts { rows: 1, bytes: 1 }));
+        assert_eq!(all.cached_rows, 3);
+
+        assert!(stats::table_stats(db, "missing").is_err());
+        assert!(stats::db_stats("stats_missing_db").is_err());
+
+        engine().delete_db(db).unwrap();
+        stats::forget_db(db);
+    }
+
+    #[test]
+    fn saved_stats_test() {
+        let db: &str = "stats_saved_db";
+        assert!(cache::add(db, "items", "1", "hello", "string"));
+        assert!(cache::add(db, "items", "2", "42", "int"));
+
+        // The first change marks the saved file as outdated
+        assert!(engine().read_table_file(db, "items", "stats.json").unwrap().contains("\"clean\":false"));
+        stats::save_all();
+        assert!(engine().read_table_file(db, "items", "stats.json").unwrap().contains("\"clean\":true"));
+
+        // Read back from the side file instead of counting the table
+        let before: TableStats = stats::table_stats(db, "items").unwrap();
+        stats::forget_table(db, "items");
+        let after: TableStats = stats::table_stats(db, "items").unwrap();
+        assert_eq!((after.rows, after.bytes, after.last_write_at), (2, 7, before.last_write_at));
+        assert_eq!(after.types, before.types);
+
+        engine().delete_db(db).unwrap();
+        stats::forget_db(db);
+    }
+}
@@ -0,0 +1,138 @@
+#[cfg(test)]
+mod test {
+    use std::{fs, io::{Cursor, Read}, net::{TcpListener, TcpStream}, time::Duration};
+    use serde_json::Value;
+
+    use crate::cache::{self, PutMode};
+    use crate::config;
+    use crate::db::{engine::engine, transfer::{self, CsvColumns, Format, ImportReport}};
+    use crate::http::{receiver::RequestHeaders, CLIENTS};
+    use crate::tx_pool::req_handler::handle_request;
+
+    fn export(db: &str, table: &str, format: Format, columns: &CsvColumns) -> String {
+        let mut out: Vec<u8> = Vec::new();
+        transfer::export(db, table, format, columns, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn headers(db: &str, table: &str, params: &[(&str, &str)]) -> RequestHeaders {
+        RequestHeaders {
+            rud: "7".to_string(),
+            db: db.to_string(),
+            table: table.to_string(),
+            params: params.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn ndjson_round_trip_test() {
+        let db: &str = "transfer_ndjson_db";
+        cache::add(db, "users", "1", "{\"name\":\"a\",\"tags\":[1,2]}", "json");
+        cache::add(db, "users", "2", "hello", "string");
+        cache::add(db, "users", "3", "42", "int");
+
+        let columns: CsvColumns = CsvColumns::parse(None, None);
+        let data: String = export(db, "users", Format::Ndjson, &columns);
+        assert_eq!(data.lines().count(), 3);
+        assert!(data.starts_with("{\"key\":\"1\",\"value\":{\"name\":\"a\",\"tags\":[1,2]},\"type\":\"json\"}"));
+
+        // A broken line, one failing the type check and one which is not UTF-8 do not stop the import
+        let mut input: Vec<u8> = (data + "not json\n{\"key\": \"4\", \"value\": \"x\", \"type\": \"int\"}\n").into_bytes();
+        input.extend_from_slice(b"\xFF\xFE\n{\"key\": \"5\", \"value\": \"after\", \"type\": \"string\"}\n");
+        let report: ImportReport = transfer::import(db, "copy", Format::Ndjson, &columns, PutMode::Upsert, Cursor::new(input)).unwrap();
+        assert_eq!(report.imported, 4);
+        assert_eq!(report.failed, 3);
+        assert_eq!(report.errors.iter().map(|e| e.line).collect::<Vec<usize>>(), vec![4, 5, 6]);
+        assert_eq!(engine().read_row(db, "copy", "5").unwrap().unwrap().value(), "after");
+
+        assert_eq!(engine().read_row(db, "copy", "2").unwrap().unwrap().value(), "hello");
+        assert_eq!(engine().read_row(db, "copy", "1").unwrap().unwrap().value(), "{\"name\":\"a\",\"tags\":[1,2]}");
+
+        // Insert only mode reports existing keys
+        let again: ImportReport = transfer::import(db, "copy", Format::Ndjson, &columns, PutMode::Insert, Cursor::new("{\"key\": \"3\", \"value\": \"1\", \"type\": \"int\"}")).unwrap();
+        assert_eq!(again.failed, 1);
+
+        engine().delete_db(db).unwrap();
+    }
+
+    #[test]
+    fn csv_round_trip_test() {
+        let db: &str = "transfer_csv_db";
+        let input: &[u8] = b"id,full name,age,zip\n1,\"Doe, John\",42,007\n2,\"multi\nline\",,123\n3,short\n4,\xFF,1,2\n";
+        let columns: CsvColumns = CsvColumns::parse(Some("id"), Some("full name:name"));
+
+        let report: ImportReport = transfer::import(db, "people", Format::Csv, &columns, PutMode::Upsert, Cursor::new(input)).unwrap();
+        assert_eq!(report.imported, 2);
+        assert_eq!(report.errors.iter().map(|e| e.line).collect::<Vec<usize>>(), vec![5, 6]);
+
+        assert_eq!(engine().read_row(db, "people", "1").unwrap().unwrap().value(), "{\"age\":42,\"name\":\"Doe, John\",\"zip\":\"007\"}");
+        assert_eq!(engine().read_row(db, "people", "2").unwrap().unwrap().value(), "{\"name\":\"multi\\nline\",\"zip\":123}");
+
+        let export_columns: CsvColumns = CsvColumns::parse(Some("id"), Some("full name:name,age"));
+        let data: String = export(db, "people", Format::Csv, &export_columns);
+        assert_eq!(data, "id,full name,age\n1,\"Doe, John\",42\n2,\"multi\nline\",\n");
+
+        engine().delete_db(db).unwrap();
+    }
+
+    #[test]
+    fn stream_export_test() {
+        let db: &str = "transfer_stream_db";
+        let session: &str = "transfer_session";
+        for i in 0..600 {
+            cache::add(db, "rows", &format!("{:03}", i), &i.to_string(), "int");
+        }
+
+        let listener: TcpListener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client: TcpStream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        CLIENTS.lock().unwrap().writable.insert(session.to_string(), server);
+        client.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+
+        // The rows go out in frames while they are read, the report closes the response
+        let report: String = handle_request("export_table", &headers(db, "rows", &[]), "", session).unwrap();
+        let report: Value = serde_json::from_str(report.strip_suffix("\njson").unwrap()).unwrap();
+        assert_eq!(report["exported"], 600);
+
+        let mut text: String = String::new();
+        while text.matches("\nndjson").count() < 2 {
+            let mut buf: [u8; 65536] = [0; 65536];
+            let n: usize = client.read(&mut buf).expect("an export frame");
+            text.push_str(std::str::from_utf8(&buf[..n]).unwrap());
+        }
+
+        let frames: Vec<&str> = text.split("rud: 7\n").filter(|frame| !frame.is_empty()).collect();
+        let lines: Vec<usize> = frames.iter().map(|frame| frame.strip_suffix("\nndjson").unwrap().lines().count()).collect();
+        assert_eq!(lines, vec![512, 88]);
+
+        CLIENTS.lock().unwrap().writable.remove(session);
+        engine().delete_db(db).unwrap();
+    }
+
+    #[test]
+    fn transfer_path_test() {
+        let db: &str = "transfer_path_db";
+        let session: &str = "transfer_path_session";
+        cache::add(db, "rows", "a", "1", "int");
+
+        // Files stay inside the transfer directory
+        assert!(handle_request("export_table", &headers(db, "rows", &[("path", "../outside.ndjson")]), "", session).is_err());
+        assert!(handle_request("export_table", &headers(db, "rows", &[("path", "/tmp/outside.ndjson")]), "", session).is_err());
+        assert!(handle_request("import_table", &headers(db, "copy", &[("path", "../db/wal.log")]), "", session).is_err());
+
+        handle_request("export_table", &headers(db, "rows", &[("path", "transfer_test/rows.ndjson")]), "", session).unwrap();
+        assert!(fs::metadata(format!("{}/transfer_test/rows.ndjson", config::CONFIG.transfer_path)).is_ok());
+
+        // The imported rows are logged one by one, not the input
+        handle_request("import_table", &headers(db, "copy", &[("path", "transfer_test/rows.ndjson")]), "", session).unwrap();
+        assert_eq!(engine().read_row(db, "copy", "a").unwrap().unwrap().value(), "1");
+
+        let log: String = fs::read_to_string(format!("{}/wal.log", config::CONFIG.db_path)).unwrap();
+        assert!(log.lines().any(|line| line.contains("\"req\":\"put_row\"") && line.contains("\"table\":\"copy\"") && line.contains(db)));
+        assert!(!log.contains("\"req\":\"import_table\""));
+
+        fs::remove_dir_all(format!("{}/transfer_test", config::CONFIG.transfer_path)).unwrap();
+        engine().delete_db(db).unwrap();
+    }
+}
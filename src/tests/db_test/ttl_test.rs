@@ -0,0 +1,93 @@
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::cache;
+    use crate::db::{engine::{engine, KeyRange}, ttl};
+    use crate::http::receiver::RequestHeaders;
+    use crate::protos::row::Row;
+
+    fn row(value: &str, expires_at: Option<u64>) -> Row {
+        let mut row: Row = Row::new();
+        row.set_value(value.to_string());
+        row.set_type("string".to_string());
+        if let Some(at) = expires_at {
+            row.set_expires_at(at);
+        }
+        row
+    }
+
+    fn headers(params: &[(&str, &str)]) -> RequestHeaders {
+        RequestHeaders {
+            params: params.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<HashMap<String, String>>(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn headers_test() {
+        assert_eq!(ttl::from_headers(&headers(&[])).unwrap(), None);
+        assert_eq!(ttl::from_headers(&headers(&[("expires_at", "42")])).unwrap(), Some(42));
+        assert!(ttl::from_headers(&headers(&[("ttl", "60")])).unwrap().unwrap() >= ttl::now() + 60);
+        assert!(ttl::from_headers(&headers(&[("ttl", "0")])).is_err());
+        assert!(ttl::from_headers(&headers(&[("ttl", &u64::MAX.to_string())])).is_err());
+        assert!(ttl::from_headers(&headers(&[("ttl", "1"), ("expires_at", "1")])).is_err());
+    }
+
+    #[test]
+    fn expiry_test() {
+        let db: &str = "ttl_test_db";
+        assert!(cache::add_row(db, "sessions", "old", &row("a", Some(ttl::now() - 1))));
+        assert!(cache::add_row(db, "sessions", "new", &row("b", Some(ttl::now() + 100))));
+        assert!(cache::add_row(db, "sessions", "keep", &row("c", None)));
+
+        // Expired rows are hidden before the reaper runs
+        assert!(cache::get(db, "sessions", "old").is_err());
+        assert_eq!(ttl::list_rows(db, "sessions").unwrap(), vec!["keep".to_string(), "new".to_string()]);
+        assert!(ttl::remaining(&cache::get(db, "sessions", "new").unwrap()).unwrap() > 90);
+        assert!(ttl::remaining(&cache::get(db, "sessions", "keep").unwrap()).is_none());
+
+        // An expired key can be added again
+        assert!(cache::add_row(db, "sessions", "old", &row("a", Some(ttl::now() - 1))));
+
+        assert!(ttl::reap() >= 1);
+        assert!(!engine().row_exists(db, "sessions", "old"));
+        assert!(engine().row_exists(db, "sessions", "new"));
+
+        engine().delete_db(db).unwrap();
+    }
+
+    #[test]
+    fn persisted_deadlines_test() {
+        let db: &str = "ttl_persist_db";
+        assert!(cache::add_row(db, "sessions", "a b", &row("a", Some(ttl::now() + 100))));
+        assert!(cache::add_row(db, "sessions", "plain", &row("b", None)));
+        assert!(engine().read_table_file(db, "sessions", "deadlines.log").unwrap().contains("a%20b"));
+
+        // Deadlines are read back from the side file, not from the rows
+        engine().write_table_file(db, "sessions", "deadlines.log", &format!("{} plain\n1 gone\n0 gone\n", ttl::now() - 1)).unwrap();
+        assert_eq!(ttl::load_table(db, "sessions"), 1);
+        assert!(ttl::is_key_expired(db, "sessions", "plain"));
+        assert!(!ttl::is_key_expired(db, "sessions", "gone"));
+
+        engine().delete_db(db).unwrap();
+    }
+
+    #[test]
+    fn scan_limit_test() {
+        let db: &str = "ttl_scan_db";
+        for key in ["a", "b", "c", "d", "e"] {
+            let expires_at: Option<u64> = if key < "d" { Some(ttl::now() - 1) } else { None };
+            assert!(cache::add_row(db, "t", key, &row(key, expires_at)));
+        }
+
+        let mut range: KeyRange = KeyRange::all();
+        range.limit = 2;
+        assert_eq!(ttl::scan_rows(db, "t", &range).unwrap(), vec!["d".to_string(), "e".to_string()]);
+        range.reverse = true;
+        range.limit = 1;
+        assert_eq!(ttl::scan_rows(db, "t", &range).unwrap(), vec!["e".to_string()]);
+
+        engine().delete_db(db).unwrap();
+    }
+}
@@ -0,0 +1,144 @@
+#[cfg(test)]
+mod test {
+    use protobuf::Message;
+    use serde_json::{json, Value};
+
+    use crate::{cache, config, types};
+    use crate::db::{engine::engine, schema, table::create_table, wal::{self, Wal}};
+    use crate::http::receiver::RequestHeaders;
+    use crate::protos::row::Row;
+    use crate::tx_pool::{req_handler::handle_request, transaction::{self, Write}};
+
+    const DB: &str = "tx_test_db";
+
+    fn headers(table: &str, key: &str, tx: &str) -> RequestHeaders {
+        let mut head: RequestHeaders = RequestHeaders {
+            rud: "0".to_string(),
+            db: DB.to_string(),
+            table: table.to_string(),
+            key: key.to_string(),
+            _type: "string".to_string(),
+            ..Default::default()
+        };
+        if !tx.is_empty() {
+            head.params.insert("tx".to_string(), tx.to_string());
+        }
+
+        head
+    }
+
+    /// Drops the tables an earlier run left behind.
+    fn reset(tables: &[&str]) {
+        for table in tables {
+            cache::delete_table(DB, table);
+        }
+    }
+
+    fn begin(session: &str) -> String {
+        let response: String = handle_request("begin_tx", &headers("", "", ""), "", session).unwrap();
+        let response: Value = serde_json::from_str(response.trim_end_matches("\njson")).unwrap();
+        response["tx"].as_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn commit_test() {
+        reset(&["accounts", "history"]);
+        assert!(cache::add(DB, "accounts", "a", "100", "string"));
+        assert!(cache::add(DB, "accounts", "gone", "1", "string"));
+
+        let tx: String = begin("session-1");
+        handle_request("update_row", &headers("accounts", "a", &tx), "90", "session-1").unwrap();
+        handle_request("add_row", &headers("history", "1", &tx), "a:-10", "session-1").unwrap();
+        handle_request("delete_row", &headers("accounts", "gone", &tx), "", "session-1").unwrap();
+
+        // Queued writes are not seen before the commit
+        assert_eq!(cache::get(DB, "accounts", "a").unwrap().value(), "100");
+        assert!(cache::get(DB, "history", "1").is_err());
+
+        // The transaction belongs to its session
+        assert!(handle_request("commit_tx", &headers("", "", &tx), "", "session-2").is_err());
+
+        handle_request("commit_tx", &headers("", "", &tx), "", "session-1").unwrap();
+        assert_eq!(cache::get(DB, "accounts", "a").unwrap().value(), "90");
+        assert_eq!(cache::get(DB, "history", "1").unwrap().value(), "a:-10");
+        assert!(cache::get(DB, "accounts", "gone").is_err());
+
+        // A committed transaction is closed
+        assert!(handle_request("commit_tx", &headers("", "", &tx), "", "session-1").is_err());
+        assert!(handle_request("add_row", &headers("accounts", "b", &tx), "1", "session-1").is_err());
+    }
+
+    #[test]
+    fn failed_commit_test() {
+        reset(&["stock", "orders"]);
+        assert!(cache::add(DB, "stock", "apple", "5", "string"));
+        create_table(DB, "orders");
+        schema::set(DB, "orders", json!({"type": "object", "required": ["item"]})).unwrap();
+
+        // One failing operation drops all of them
+        let tx: String = begin("session-3");
+        handle_request("update_row", &headers("stock", "apple", &tx), "4", "session-3").unwrap();
+        handle_request("update_row", &headers("stock", "pear", &tx), "1", "session-3").unwrap();
+        let err: String = handle_request("commit_tx", &headers("", "", &tx), "", "session-3").unwrap_err();
+        assert!(err.contains("409") && err.contains("operation 2"), "{}", err);
+        assert_eq!(cache::get(DB, "stock", "apple").unwrap().value(), "5");
+
+        // Later operations see the queued rows of earlier ones
+        let tx: String = begin("session-3");
+        handle_request("add_row", &headers("stock", "pear", &tx), "1", "session-3").unwrap();
+        handle_request("update_row", &headers("stock", "pear", &tx), "2", "session-3").unwrap();
+        let mut order: RequestHeaders = headers("orders", "1", &tx);
+        order._type = "json".to_string();
+        handle_request("add_row", &order, "{\"qty\":1}", "session-3").unwrap();
+        let err: String = handle_request("commit_tx", &headers("", "", &tx), "", "session-3").unwrap_err();
+        assert!(err.contains("operation 3") && err.contains("schema"), "{}", err);
+        assert!(cache::get(DB, "stock", "pear").is_err());
+
+        // Only row writes of the database of the transaction can be queued
+        let tx: String = begin("session-3");
+        assert!(handle_request("delete_table", &headers("stock", "", &tx), "", "session-3").is_err());
+        let mut other: RequestHeaders = headers("stock", "x", &tx);
+        other.db = "tx_test_other_db".to_string();
+        assert!(handle_request("add_row", &other, "1", "session-3").is_err());
+    }
+
+    #[test]
+    fn rollback_test() {
+        let tx: String = begin("session-4");
+        handle_request("add_row", &headers("rollback", "1", &tx), "1", "session-4").unwrap();
+        handle_request("rollback_tx", &headers("", "", &tx), "", "session-4").unwrap();
+        assert!(handle_request("commit_tx", &headers("", "", &tx), "", "session-4").is_err());
+
+        // A closed session rolls back its open transactions
+        let tx: String = begin("session-5");
+        handle_request("add_row", &headers("rollback", "2", &tx), "2", "session-5").unwrap();
+        transaction::drop_session("session-5");
+        assert!(handle_request("commit_tx", &headers("", "", &tx), "", "session-5").is_err());
+
+        assert!(cache::get(DB, "rollback", "1").is_err());
+        assert!(cache::get(DB, "rollback", "2").is_err());
+        assert!(!engine().table_exists(DB, "rollback"));
+    }
+
+    #[test]
+    fn recover_commit_test() {
+        reset(&["recovered"]);
+        assert!(cache::add(DB, "recovered", "old", "1", "string"));
+
+        let mut row: Row = Row::new();
+        row.set_value("new".to_string());
+        row.set_type("string".to_string());
+        let writes: Vec<Write> = vec![
+            Write { table: "recovered".to_string(), key: "new".to_string(), row: Some(types::encode_bytes(&row.write_to_bytes().unwrap())) },
+            Write { table: "recovered".to_string(), key: "old".to_string(), row: None }
+        ];
+
+        // The process is killed after logging the commit
+        let path: String = format!("{}/tx_test_db.log", config::CONFIG.db_path);
+        Wal::open(&path).unwrap().begin("commit_tx", &headers("", "", ""), &serde_json::to_string(&writes).unwrap()).unwrap();
+
+        assert_eq!(wal::recover_from(&path).unwrap(), 1);
+        assert_eq!(cache::get(DB, "recovered", "new").unwrap().value(), "new");
+        assert!(cache::get(DB, "recovered", "old").is_err());
+    }
+}
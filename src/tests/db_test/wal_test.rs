@@ -0,0 +1,65 @@
+#[cfg(test)]
+mod test {
+    use crate::config;
+    use crate::db::{create_db, row::read_row, wal::{self, Wal}};
+    use crate::http::receiver::RequestHeaders;
+    use crate::tx_pool::req_handler;
+
+    fn headers(key: &str) -> RequestHeaders {
+        RequestHeaders {
+            rud: "0".to_string(),
+            db: "wal_test_db".to_string(),
+            table: "rows".to_string(),
+            key: key.to_string(),
+            _type: "string".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn recover_test() {
+        create_db("wal_test_db");
+        let path: String = format!("{}/wal_test_db.log", config::CONFIG.db_path);
+
+        {
+            let mut log: Wal = Wal::open(&path).unwrap();
+
+            // Committed entry must not be replayed
+            let id: u64 = log.begin("add_row", &headers("committed"), "value").unwrap();
+            log.commit(id).unwrap();
+
+            // The process is killed after logging but before applying
+            log.begin("add_row", &headers("lost"), "value").unwrap();
+        }
+
+        assert_eq!(wal::recover_from(&path).unwrap(), 1);
+        assert_eq!(read_row("wal_test_db", "rows", "lost").unwrap().value(), "value");
+        assert!(read_row("wal_test_db", "rows", "committed").is_err());
+
+        // Log is truncated after the recovery
+        assert_eq!(wal::recover_from(&path).unwrap(), 0);
+    }
+
+    #[test]
+    fn interleaved_recover_test() {
+        create_db("wal_test_db");
+        let path: String = format!("{}/wal_test_db_interleaved.log", config::CONFIG.db_path);
+
+        {
+            let mut log: Wal = Wal::open(&path).unwrap();
+
+            // The first put is applied, the second one is applied and committed before the first commit is written
+            log.begin("put_row", &headers("interleaved"), "1").unwrap();
+            req_handler::apply("put_row", &headers("interleaved"), "1").unwrap();
+
+            let id: u64 = log.begin("put_row", &headers("interleaved"), "2").unwrap();
+            req_handler::apply("put_row", &headers("interleaved"), "2").unwrap();
+            log.commit(id).unwrap();
+
+            // The process is killed before the first commit
+        }
+
+        assert_eq!(wal::recover_from(&path).unwrap(), 0);
+        assert_eq!(read_row("wal_test_db", "rows", "interleaved").unwrap().value(), "2");
+    }
+}
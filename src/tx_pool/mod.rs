@@ -1,6 +1,7 @@
 pub mod tx_table;
 pub mod req_handler;
 pub mod worker;
+pub mod transaction;
 
 use std::{thread::{self, sleep}, sync::{MutexGuard, Mutex}, time};
 use lazy_static::lazy_static;
@@ -51,7 +52,7 @@ fn worker() {
         
         let (tx, key): (TX, String) = worker::get_latest_tx();
         worker::delete_latest_tx(&key);
-        let a: Result<String, String> = req_handler::handle_request(&tx.req, &tx.head, &tx.body);
+        let a: Result<String, String> = req_handler::handle_request(&tx.req, &tx.head, &tx.body, &tx.to);
         
         let response: String;
         match a {
@@ -59,7 +60,7 @@ fn worker() {
             Err(err) => {response = err}
         }
 
-        http::send(&tx.head.rud, &response, &tx.to);
+        http::respond(&tx.head, &response, &tx.to);
 
         if worker::is_pool_empty() {
             wsleep();
@@ -75,5 +76,7 @@ pub fn start() {
         thread::spawn(worker);
         sleep(time::Duration::from_millis((CONFIG.workers_count * 2).into()));
     }
-    println!("[ LOG ] All workers are started")
+    println!("[ LOG ] All workers are started");
+
+    transaction::start();
 }
\ No newline at end of file
@@ -1,12 +1,20 @@
-use crate::http::{row_methods, receiver::RequestHeaders, table_methods, db_methods};
+use std::sync::{RwLockReadGuard, RwLockWriteGuard};
+use serde_json::Value;
+
+use crate::{http::{row_methods::{self, BunchResult}, receiver::RequestHeaders, table_methods, db_methods, tx_methods, collection_methods, cdc_methods}, db::{wal, names, snapshot, engine::engine}, tx_pool::transaction::{self, RowWrite}};
 
 /// Handles incoming requests based on the provided path.
 ///
+/// Mutations are logged to the write-ahead log and return only after they meet
+/// the durability level of the database, so the reply is never sent earlier.
+/// Writes with a `tx` header are queued in their transaction instead.
+///
 /// # Arguments
 ///
 /// * `path` - The type of the request.
 /// * `head` - The request headers.
 /// * `body` - The request body.
+/// * `session` - The client session which sent the request.
 ///
 /// # Returns
 ///
@@ -15,56 +23,315 @@ use crate::http::{row_methods, receiver::RequestHeaders, table_methods, db_metho
 /// # Examples
 ///
 /// ```rust
-/// let result = handle_request("get_row", &request_headers, "request_body");
+/// let result = handle_request("get_row", &request_headers, "request_body", &session);
 /// match result {
 ///     Ok(response) => println!("Response: {}", response),
 ///     Err(error) => println!("Error: {}", error),
 /// }
 /// ```
-pub fn handle_request(path: &str, head: &RequestHeaders, body: &str) -> Result<String, String> {
+pub fn handle_request(path: &str, head: &RequestHeaders, body: &str, session: &str) -> Result<String, String> {
     println!("[ INFO ]: get new request - `{}`", path);
 
+    let head: RequestHeaders = match check_headers(head) {
+        Ok(head) => head,
+        Err(err) => return Err(("{\"code\": 400, \"message\": \"".to_owned() + &err + "\"}\njson").to_string())
+    };
+    let head: &RequestHeaders = &head;
+
+    // Transactions and subscriptions belong to the session which opened them, exports stream to the session which asked
+    match path {
+        "begin_tx" => return tx_methods::begin(head, session),
+        "rollback_tx" => return tx_methods::rollback(head, session),
+        "commit_tx" => return commit_tx(head, session),
+        "subscribe" => return cdc_methods::subscribe(head, session),
+        "unsubscribe" => return cdc_methods::unsubscribe(head, session),
+        "export_table" => {
+            let _reading: RwLockReadGuard<'_, ()> = transaction::read_gate();
+            return table_methods::export(head, session);
+        },
+        _ => {}
+    }
+
+    let row_change: bool = wal::is_row_change(path) || wal::is_row_writer(path);
+    if head.param("tx").is_some() && (row_change || wal::is_mutation(path)) {
+        return tx_methods::queue(path, head, body, session);
+    }
+
+    if path == "add_bunch" {
+        return add_bunch(head, body);
+    }
+
+    // Patches, counters, collections and imports log the rows they store, replaying the change itself would apply it twice
+    if row_change {
+        let _shared: RwLockReadGuard<'_, ()> = snapshot::write_gate();
+        return apply(path, head, body);
+    }
+
+    if !wal::is_mutation(path) {
+        let _reading: RwLockReadGuard<'_, ()> = transaction::read_gate();
+        return apply(path, head, body);
+    }
+
+    // Snapshots wait until the write is applied, renames and restores wait until no other write runs
+    let exclusive: bool = wal::is_exclusive(path);
+    let _exclusive: Option<RwLockWriteGuard<'_, ()>> = exclusive.then(snapshot::exclusive_gate);
+    let _shared: Option<RwLockReadGuard<'_, ()>> = (!exclusive).then(snapshot::write_gate);
+
+    logged(path, head, body)
+}
+
+/// Commits the transaction given in the `tx` header.
+///
+/// The queued operations are checked and their final rows are logged as one
+/// `commit_tx` entry of the write-ahead log, then written while no other
+/// write and no reader runs.
+fn commit_tx(head: &RequestHeaders, session: &str) -> Result<String, String> {
+    let _readers: RwLockWriteGuard<'_, ()> = transaction::commit_gate();
+    let _writers: RwLockWriteGuard<'_, ()> = snapshot::exclusive_gate();
+
+    let writes: String = tx_methods::prepare(head, session)?;
+
+    logged("commit_tx", head, &writes)
+}
+
+/// Writes a bunch of new rows.
+///
+/// The rows are checked and logged as one `commit_tx` entry of the
+/// write-ahead log, then written while no other write and no reader runs,
+/// like the rows of a committed transaction.
+fn add_bunch(head: &RequestHeaders, body: &str) -> Result<String, String> {
+    let _readers: RwLockWriteGuard<'_, ()> = transaction::commit_gate();
+    let _writers: RwLockWriteGuard<'_, ()> = snapshot::exclusive_gate();
+
+    let (rows, results): (Vec<RowWrite>, Vec<BunchResult>) = row_methods::check_bunch(head, body)?;
+    let writes: String = transaction::encode_writes(&rows)
+        .map_err(|err| row_methods::bunch_response(500, &format!("nothing was written - {}", err), 0, Vec::new()))?;
+
+    match logged("commit_tx", head, &writes) {
+        Ok(_) => Ok(row_methods::bunch_response(200, "bunch was add", rows.len(), results)),
+        Err(err) => {
+            let reason: Value = serde_json::from_str(err.trim_end_matches("\njson")).unwrap_or_default();
+            Err(row_methods::bunch_response(500, &format!("nothing was written - {}", reason["message"].as_str().unwrap_or_default()), 0, results))
+        }
+    }
+}
+
+/// Logs a mutation to the write-ahead log, applies it and marks it as applied.
+///
+/// The caller has to hold the write gate.
+fn logged(path: &str, head: &RequestHeaders, body: &str) -> Result<String, String> {
+    if !engine().is_persistent() {
+        return apply(path, head, body);
+    }
+
+    // Mutations are logged first, so they can be replayed after a crash
+    let id: u64 = match wal::begin(path, head, body) {
+        Ok(id) => id,
+        Err(err) => {
+            println!("{}", err);
+            return Err("{\"code\": 500, \"message\": \"Can not write to WAL\"}\njson".to_string());
+        }
+    };
+
+    let result: Result<String, String> = apply(path, head, body);
+    wal::commit(id);
+
+    result
+}
+
+/// Decodes a hex encoded key (`key_encoding: hex`) and checks the names of the request.
+///
+/// # Returns
+///
+/// Returns the headers with the decoded key, or an error message if a name can not be stored.
+fn check_headers(head: &RequestHeaders) -> Result<RequestHeaders, String> {
+    let mut head: RequestHeaders = head.clone();
+
+    match head.params.remove("key_encoding").as_deref() {
+        Some("hex") => head.key = names::decode_hex_key(&head.key)?,
+        Some("utf8") | None => {},
+        Some(_) => return Err("unknown key encoding, use hex or utf8".to_string())
+    }
+
+    if !head.db.is_empty() {
+        names::check_name("database", &head.db)?;
+    }
+    if !head.table.is_empty() {
+        names::check_name("table", &head.table)?;
+    }
+    if !head.key.is_empty() {
+        names::check_key(&head.key)?;
+    }
+
+    Ok(head)
+}
+
+/// Executes a request without logging it to the write-ahead log.
+///
+/// # Arguments
+///
+/// * `path` - The type of the request.
+/// * `head` - The request headers.
+/// * `body` - The request body.
+///
+/// # Returns
+///
+/// * `Result<String, String>` - A `Result` containing the response or an error message.
+pub fn apply(path: &str, head: &RequestHeaders, body: &str) -> Result<String, String> {
     match path {
         // Handle row operations
         "get_row" => {
-            return row_methods::get(head);
+            row_methods::get(head)
+        }
+
+        "multi_get" => {
+            row_methods::multi_get(head, body)
         }
 
         "filter_row" => {
-            return row_methods::filter(head, &body);
+            row_methods::filter(head, body)
         }
 
         "add_row" => {
-            return row_methods::add(head, &body);
+            row_methods::add(head, body)
         }
 
-        // "edit_row" => {
-        //     return row_methods::edit(req);
-        // }
+        "put_row" => {
+            row_methods::put(head, body)
+        }
+
+        "update_row" => {
+            row_methods::update(head, body)
+        }
+
+        "patch_row" => {
+            row_methods::patch(head, body)
+        }
+
+        "ttl" => {
+            row_methods::ttl(head)
+        }
+
+        "incr" => {
+            row_methods::incr(head, body, false)
+        }
+
+        "decr" => {
+            row_methods::incr(head, body, true)
+        }
+
+        // Handle list, set and hash operations
+        "list_push" => {
+            collection_methods::list_push(head, body)
+        }
+
+        "list_pop" => {
+            collection_methods::list_pop(head)
+        }
+
+        "list_range" => {
+            collection_methods::list_range(head)
+        }
+
+        "set_add" => {
+            collection_methods::set_add(head, body)
+        }
+
+        "set_remove" => {
+            collection_methods::set_remove(head, body)
+        }
+
+        "set_members" => {
+            collection_methods::set_members(head)
+        }
+
+        "set_contains" => {
+            collection_methods::set_contains(head, body)
+        }
+
+        "hash_set" => {
+            collection_methods::hash_set(head, body)
+        }
+
+        "hash_get" => {
+            collection_methods::hash_get(head, body)
+        }
+
+        "hash_delete" => {
+            collection_methods::hash_delete(head, body)
+        }
 
         "delete_row" => {
-            return row_methods::delete(head);
+            row_methods::delete(head)
+        }
+
+        "delete_many" => {
+            row_methods::delete_many(head, body)
+        }
+
+        "delete_where" => {
+            row_methods::delete_where(head, body)
         }
 
         "add_bunch" => {
-            return row_methods::bunch(head, &body);
+            row_methods::bunch(head, body)
         }
 
         // Handle table operations
         "get_table" => {
-            return table_methods::get(head);
+            table_methods::get(head)
         }
 
         "get_table_data" => {
-            return table_methods::get_with_keys(head);
+            table_methods::get_with_keys(head)
+        }
+
+        "scan_range" => {
+            table_methods::scan_range(head)
         }
 
         "add_table" => {
-            return table_methods::create(head);
+            table_methods::create(head)
         }
 
         "delete_table" => {
-            return table_methods::delete(head);
+            table_methods::delete(head)
+        }
+
+        "list_tables" => {
+            table_methods::list(head)
+        }
+
+        "rename_table" => {
+            table_methods::rename(head)
+        }
+
+        "create_index" => {
+            table_methods::create_index(head)
+        }
+
+        "drop_index" => {
+            table_methods::drop_index(head)
+        }
+
+        "table_stats" => {
+            table_methods::stats(head)
+        }
+
+        "verify_table" => {
+            table_methods::verify(head)
+        }
+
+        "set_table_schema" => {
+            table_methods::set_schema(head, body)
+        }
+
+        "get_table_schema" => {
+            table_methods::get_schema(head)
+        }
+
+        "import_table" => {
+            table_methods::import(head, body)
         }
 
         // (&Method::POST, "/db") => {
@@ -73,8 +340,49 @@ pub fn handle_request(path: &str, head: &RequestHeaders, body: &str) -> Result<S
 
         // Handle db operations
 
+        "list_dbs" => {
+            db_methods::list(head)
+        }
+
+        "create_db" => {
+            db_methods::create(head)
+        }
+
         "delete_db" => {
-            return db_methods::delete(head);
+            db_methods::delete(head)
+        }
+
+        "rename_db" => {
+            db_methods::rename(head)
+        }
+
+        "set_durability" => {
+            db_methods::set_durability(head)
+        }
+
+        "db_stats" => {
+            db_methods::stats(head)
+        }
+
+        "verify_db" => {
+            db_methods::verify(head)
+        }
+
+        // Writes of a committed transaction, built by `commit_tx`
+        "commit_tx" => {
+            tx_methods::commit(head, body)
+        }
+
+        "snapshot_db" => {
+            db_methods::snapshot(head)
+        }
+
+        "restore_db" => {
+            db_methods::restore(head)
+        }
+
+        "verify_snapshot" => {
+            db_methods::verify_snapshot(head)
         }
 
         /// Handle all other paths
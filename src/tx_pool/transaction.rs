@@ -0,0 +1,413 @@
+//! Multi-operation transactions.
+//!
+//! `begin_tx` opens a transaction on one database for the client session.
+//! Row writes sent with its id in the `tx` header are only queued, so no
+//! other client sees them. `commit_tx` checks the queued operations in order
+//! against the current rows and turns them into the final state of every
+//! touched row; if one operation fails nothing is written. The final rows are
+//! logged as one write-ahead log entry and applied while other writes and
+//! readers wait. `rollback_tx`, the disconnect of the session, or no request
+//! for `tx_idle_timeout` seconds drops the queued operations.
+//!
+//! Reads inside a transaction see the committed rows, not the queued writes.
+
+use std::{collections::{BTreeMap, HashMap}, sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard}, thread, time::{Duration, Instant}};
+use lazy_static::lazy_static;
+use protobuf::Message;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{cache::{self, PutMode}, config, db::{cdc, checksum, schema}, http::{receiver::RequestHeaders, row_methods}, protos::row::Row, types};
+
+/// Requests which can be queued in a transaction.
+pub static TX_OPS: [&str; 5] = [
+    "add_row",
+    "put_row",
+    "update_row",
+    "patch_row",
+    "delete_row"
+];
+
+/// Max number of queued operations of one transaction.
+const MAX_OPS: usize = 10_000;
+
+/// Table, key and new row of a write, None deletes the row.
+pub type RowWrite = (String, String, Option<Row>);
+
+/// A queued request.
+#[derive(Debug, Clone)]
+struct Op {
+    /// Request type.
+    req: String,
+
+    /// Request headers.
+    head: RequestHeaders,
+
+    /// Request body.
+    body: String
+}
+
+/// An open transaction.
+#[derive(Debug)]
+pub struct Transaction {
+    /// Database of all operations.
+    pub db: String,
+
+    /// Client session which opened the transaction.
+    session: String,
+
+    /// Queued operations in the order they were received.
+    ops: Vec<Op>,
+
+    /// Time of the last request of the transaction.
+    last_used: Instant
+}
+
+/// Final state of one row written by a commit.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Write {
+    /// Table name.
+    pub table: String,
+
+    /// Row key.
+    pub key: String,
+
+    /// Protobuf encoded row in base64, None if the row is deleted.
+    pub row: Option<String>
+}
+
+lazy_static! {
+    /// Open transactions by id.
+    static ref TRANSACTIONS: Mutex<HashMap<String, Transaction>> = Mutex::new(HashMap::new());
+
+    /// Readers take the gate shared and a commit exclusively, so no reader sees a half applied commit.
+    static ref COMMIT_GATE: RwLock<()> = RwLock::new(());
+}
+
+/// Opens a transaction.
+///
+/// # Arguments
+///
+/// * `db` - Database of the transaction.
+/// * `session` - Client session, the transaction is rolled back when it disconnects.
+///
+/// # Returns
+///
+/// Returns the id of the transaction.
+pub fn begin(db: &str, session: &str) -> String {
+    let id: String = Uuid::new_v4().to_string();
+
+    TRANSACTIONS.lock().unwrap().insert(id.clone(), Transaction {
+        db: db.to_string(),
+        session: session.to_string(),
+        ops: Vec::new(),
+        last_used: Instant::now()
+    });
+
+    id
+}
+
+/// Queues a write in an open transaction.
+///
+/// # Arguments
+///
+/// * `id` - Transaction id.
+/// * `session` - Client session sending the write.
+/// * `req` - Request type, one of [`TX_OPS`].
+/// * `head` - Request headers.
+/// * `body` - Request body.
+///
+/// # Returns
+///
+/// Returns the number of queued operations, or an error message if the write can not be queued.
+pub fn queue(id: &str, session: &str, req: &str, head: &RequestHeaders, body: &str) -> Result<usize, String> {
+    let mut transactions: MutexGuard<'_, HashMap<String, Transaction>> = TRANSACTIONS.lock().unwrap();
+    let tx: &mut Transaction = match transactions.get_mut(id) {
+        Some(tx) if tx.session == session => tx,
+        _ => return Err("no such transaction".to_string())
+    };
+
+    if !TX_OPS.contains(&req) {
+        return Err(format!("{} can not be part of a transaction", req));
+    }
+    if head.db != tx.db {
+        return Err(format!("the transaction belongs to the database {}", tx.db));
+    }
+    if tx.ops.len() >= MAX_OPS {
+        return Err(format!("a transaction holds at most {} operations", MAX_OPS));
+    }
+
+    tx.ops.push(Op { req: req.to_string(), head: head.clone(), body: body.to_string() });
+    tx.last_used = Instant::now();
+
+    Ok(tx.ops.len())
+}
+
+/// Removes an open transaction for committing it.
+///
+/// # Returns
+///
+/// Returns None if the session has no transaction with the id.
+pub fn take(id: &str, session: &str) -> Option<Transaction> {
+    let mut transactions: MutexGuard<'_, HashMap<String, Transaction>> = TRANSACTIONS.lock().unwrap();
+
+    match transactions.get(id) {
+        Some(tx) if tx.session == session => transactions.remove(id),
+        _ => None
+    }
+}
+
+/// Drops an open transaction with its queued operations.
+///
+/// # Returns
+///
+/// Returns false if the session has no transaction with the id.
+pub fn rollback(id: &str, session: &str) -> bool {
+    take(id, session).is_some()
+}
+
+/// Rolls back all open transactions of a disconnected session.
+pub fn drop_session(session: &str) {
+    let mut transactions: MutexGuard<'_, HashMap<String, Transaction>> = TRANSACTIONS.lock().unwrap();
+    let before: usize = transactions.len();
+
+    transactions.retain(|_, tx| tx.session != session);
+    if transactions.len() < before {
+        println!("[ INFO ] Transaction: rolled back {} transactions of a closed session", before - transactions.len());
+    }
+}
+
+/// Starts the thread which rolls back the transactions idle for longer than `tx_idle_timeout`.
+pub fn start() {
+    thread::spawn(|| {
+        let timeout: Duration = Duration::from_secs(config::CONFIG.tx_idle_timeout);
+
+        loop {
+            thread::sleep(Duration::from_secs(1));
+            drop_idle(timeout);
+        }
+    });
+}
+
+/// Rolls back the open transactions which got no request for `timeout`.
+///
+/// # Returns
+///
+/// Returns the number of rolled back transactions.
+pub fn drop_idle(timeout: Duration) -> usize {
+    let mut transactions: MutexGuard<'_, HashMap<String, Transaction>> = TRANSACTIONS.lock().unwrap();
+    let before: usize = transactions.len();
+
+    transactions.retain(|_, tx| tx.last_used.elapsed() < timeout);
+    let dropped: usize = before - transactions.len();
+    if dropped > 0 {
+        println!("[ INFO ] Transaction: rolled back {} idle transactions", dropped);
+    }
+
+    dropped
+}
+
+/// Takes the commit gate shared, readers keep it until the read is done.
+pub fn read_gate() -> RwLockReadGuard<'static, ()> {
+    COMMIT_GATE.read().unwrap()
+}
+
+/// Takes the commit gate exclusively, a commit keeps it until all its rows are written.
+///
+/// Must be taken before the write gate.
+pub fn commit_gate() -> RwLockWriteGuard<'static, ()> {
+    COMMIT_GATE.write().unwrap()
+}
+
+/// Checks the queued operations in order and builds the final state of every touched row.
+///
+/// The caller has to keep other writers out until the rows are written.
+///
+/// # Returns
+///
+/// Returns the writes as a JSON array, or an error message naming the first operation which fails.
+pub fn prepare(tx: &Transaction) -> Result<String, String> {
+    let mut rows: BTreeMap<(String, String), Option<Row>> = BTreeMap::new();
+
+    for (i, op) in tx.ops.iter().enumerate() {
+        let name: (String, String) = (op.head.table.clone(), op.head.key.clone());
+        let current: Option<Row> = match rows.get(&name) {
+            Some(row) => row.clone(),
+            None => read(&tx.db, &name.0, &name.1)?
+        };
+
+        let row: Option<Row> = stage(op, current)
+            .map_err(|err| format!("operation {} ({} {}/{}) failed - {}", i + 1, op.req, name.0, name.1, err))?;
+        rows.insert(name, row);
+    }
+
+    let rows: Vec<RowWrite> = rows.into_iter().map(|((table, key), row)| (table, key, row)).collect();
+
+    encode_writes(&rows)
+}
+
+/// Encodes the final state of a set of rows as the body of a `commit_tx` entry.
+///
+/// # Arguments
+///
+/// * `rows` - Table, key and new row, None deletes the row.
+///
+/// # Returns
+///
+/// Returns the writes as a JSON array, read back by [`apply`].
+pub fn encode_writes(rows: &[RowWrite]) -> Result<String, String> {
+    let mut writes: Vec<Write> = Vec::with_capacity(rows.len());
+    for (table, key, row) in rows {
+        let row: Option<String> = match row {
+            Some(row) => Some(types::encode_bytes(&row.write_to_bytes().map_err(|e| e.to_string())?)),
+            None => None
+        };
+        writes.push(Write { table: table.clone(), key: key.clone(), row });
+    }
+
+    serde_json::to_string(&writes).map_err(|e| e.to_string())
+}
+
+/// Writes the rows of a prepared commit.
+///
+/// Writing the same rows again gives the same result, so a commit logged
+/// before a crash is replayed as is.
+///
+/// # Arguments
+///
+/// * `db` - Database of the transaction.
+/// * `body` - Writes built by [`prepare`].
+///
+/// # Returns
+///
+/// Returns the number of written rows, or an error message if the rows were not written.
+pub fn apply(db: &str, body: &str) -> Result<usize, String> {
+    let writes: Vec<Write> = serde_json::from_str(body).map_err(|e| e.to_string())?;
+
+    let mut rows: Vec<RowWrite> = Vec::with_capacity(writes.len());
+    for write in writes {
+        let row: Option<Row> = decode(&write)?;
+        rows.push((write.table, write.key, row));
+    }
+
+    write_rows(db, &rows)
+}
+
+/// Writes or deletes a set of rows, all of them or none.
+///
+/// The rows go through the cache, so the cached copies, indexes and statistics
+/// follow. A failed write puts back the rows written before it. Subscribers
+/// hear of the rows only after all of them are written.
+///
+/// # Arguments
+///
+/// * `db` - Database name.
+/// * `rows` - Table, key and new row, None deletes the row.
+///
+/// # Returns
+///
+/// Returns the number of written rows, or an error message if the rows were not written.
+pub fn write_rows(db: &str, rows: &[RowWrite]) -> Result<usize, String> {
+    let mut done: Vec<(&str, &str, Option<Row>)> = Vec::with_capacity(rows.len());
+    let events: cdc::Deferred = cdc::defer();
+
+    for (table, key, row) in rows.iter() {
+        let result: Result<Option<Row>, String> = read(db, table, key).and_then(|before| {
+            set(db, table, key, row.as_ref(), before.is_some())?;
+            Ok(before)
+        });
+
+        match result {
+            Ok(before) => done.push((table, key, before)),
+            Err(err) => {
+                println!("[ ERROR ] Transaction: Can't write key - {}. Reason: {}", key, err);
+
+                // Put back the rows which are already written, newest first
+                for (table, key, before) in done.iter().rev() {
+                    if let Err(err) = set(db, table, key, before.as_ref(), true) {
+                        println!("[ ERROR ] Transaction: Can't restore key - {}. Reason: {}", key, err);
+                    }
+                }
+
+                return Err(err);
+            }
+        }
+    }
+
+    events.publish();
+    Ok(rows.len())
+}
+
+/// Builds the new state of a row from a queued operation and the current row.
+fn stage(op: &Op, current: Option<Row>) -> Result<Option<Row>, String> {
+    let exists: bool = current.is_some();
+
+    let mode: PutMode = match op.req.as_str() {
+        "add_row" => PutMode::Insert,
+        "put_row" => match PutMode::parse(op.head.param("mode").unwrap_or("upsert")) {
+            Some(mode) => mode,
+            None => return Err("mode must be nx, xx or upsert".to_string())
+        },
+        "update_row" => PutMode::Update,
+        "patch_row" => {
+            let row: Row = current.ok_or("key does not exist")?;
+            let patch: Value = serde_json::from_str(&op.body).map_err(|e| e.to_string())?;
+            let row: Row = row_methods::patch_row(&op.head, row, &patch)?;
+            check_schema(op, &row)?;
+
+            return Ok(Some(row));
+        },
+        "delete_row" if exists => return Ok(None),
+        "delete_row" => return Err("key does not exist".to_string()),
+        _ => return Err("not supported in a transaction".to_string())
+    };
+
+    match mode {
+        PutMode::Insert if exists => return Err("key already exists".to_string()),
+        PutMode::Update if !exists => return Err("key does not exist".to_string()),
+        _ => {}
+    }
+
+    types::is_valid_data(&op.body, &op.head._type)?;
+    let row: Row = row_methods::new_row(&op.head, &op.body)?;
+    check_schema(op, &row)?;
+
+    Ok(Some(row))
+}
+
+/// Checks a staged row against the schema of its table.
+fn check_schema(op: &Op, row: &Row) -> Result<(), String> {
+    let errors: Vec<schema::Violation> = schema::violations(&op.head.db, &op.head.table, row);
+    if errors.is_empty() {
+        return Ok(());
+    }
+
+    Err(format!("value does not match the table schema - {}", schema::describe(&errors)))
+}
+
+/// Reads the current row, None if there is no such row.
+fn read(db: &str, table: &str, key: &str) -> Result<Option<Row>, String> {
+    match cache::get(db, table, key) {
+        Ok(row) => Ok(Some(row)),
+        Err(err) if checksum::is_corrupted(&err) => Err(err),
+        Err(_) => Ok(None)
+    }
+}
+
+/// Decodes the row of a write.
+fn decode(write: &Write) -> Result<Option<Row>, String> {
+    match write.row.as_deref() {
+        Some(data) => Row::parse_from_bytes(&types::decode_bytes(data)?).map(Some).map_err(|e| e.to_string()),
+        None => Ok(None)
+    }
+}
+
+/// Writes a row, or deletes it if `row` is None and the row `exists`.
+fn set(db: &str, table: &str, key: &str, row: Option<&Row>, exists: bool) -> Result<(), String> {
+    match row {
+        Some(row) => cache::put(db, table, key, row, PutMode::Upsert).map(|_| ()),
+        None if exists => cache::delete(db, table, key).map(|_| ()),
+        None => Ok(())
+    }
+}
@@ -0,0 +1,211 @@
+//! The `list`, `set` and `hash` value types.
+//!
+//! The items of a `list` and a `set` live in the repeated `items` field of the
+//! row and the fields of a `hash` in its `fields` map, so they are stored and
+//! cached as they are and no text is parsed to change them. A `set` keeps its
+//! items sorted and unique. The text form of a `list` or a `set` is a JSON
+//! array of strings and the one of a `hash` a JSON object of strings.
+
+use std::collections::{BTreeMap, HashMap};
+use serde_json::Value;
+
+use crate::protos::row::Row;
+
+/// Checks if a type is one of the collection types.
+pub fn is_collection(data_type: &str) -> bool {
+    matches!(data_type, "list" | "set" | "hash")
+}
+
+/// Parses a JSON array of strings.
+pub fn parse_items(data: &str) -> Result<Vec<String>, String> {
+    match serde_json::from_str::<Value>(data) {
+        Ok(Value::Array(items)) => items.into_iter()
+            .map(|item| match item {
+                Value::String(item) => Ok(item),
+                _ => Err("items must be strings".to_string())
+            })
+            .collect(),
+        Ok(_) => Err("value must be a JSON array of strings".to_string()),
+        Err(err) => Err(err.to_string())
+    }
+}
+
+/// Parses a JSON object of strings.
+pub fn parse_fields(data: &str) -> Result<HashMap<String, String>, String> {
+    match serde_json::from_str::<Value>(data) {
+        Ok(Value::Object(fields)) => fields.into_iter()
+            .map(|(name, value)| match value {
+                Value::String(value) => Ok((name, value)),
+                _ => Err("field values must be strings".to_string())
+            })
+            .collect(),
+        Ok(_) => Err("value must be a JSON object of strings".to_string()),
+        Err(err) => Err(err.to_string())
+    }
+}
+
+/// Checks the text form of a collection value.
+pub fn validate(data: &str, data_type: &str) -> Result<(), String> {
+    match data_type {
+        "list" | "set" => parse_items(data).map(|_| ()),
+        _ => parse_fields(data).map(|_| ())
+    }
+}
+
+/// Stores the text form of a collection value in a row.
+pub fn set_value(row: &mut Row, data: &str, data_type: &str) -> Result<(), String> {
+    match data_type {
+        "list" => row.items = parse_items(data)?,
+        "set" => {
+            row.items = Vec::new();
+            set_add(row, parse_items(data)?);
+        },
+        _ => row.fields = parse_fields(data)?
+    }
+
+    Ok(())
+}
+
+/// Returns the text form of a collection row.
+pub fn value(row: &Row) -> String {
+    match row.type_() {
+        // Sorted fields give the same text for the same hash
+        "hash" => serde_json::to_string(&row.fields.iter().collect::<BTreeMap<&String, &String>>()).unwrap(),
+        _ => serde_json::to_string(&row.items).unwrap()
+    }
+}
+
+/// Returns the size of the items and fields of a row in bytes.
+pub fn size(row: &Row) -> usize {
+    row.items.iter().map(|item| item.len()).sum::<usize>()
+        + row.fields.iter().map(|(name, value)| name.len() + value.len()).sum::<usize>()
+}
+
+/// Creates an empty collection row.
+pub fn new_row(data_type: &str) -> Row {
+    let mut row: Row = Row::new();
+    row.set_value(String::new());
+    row.set_type(data_type.to_string());
+
+    row
+}
+
+/// Checks that a row has the expected collection type.
+pub fn check_type(row: &Row, data_type: &str) -> Result<(), String> {
+    if row.type_() != data_type {
+        return Err(format!("row is a {}, not a {}", row.type_(), data_type));
+    }
+
+    Ok(())
+}
+
+/// Adds items to the front (`left`) or the back of a list.
+///
+/// Items pushed to the front end up in reverse order, like pushing them one by one.
+///
+/// # Returns
+///
+/// Returns the new length of the list.
+pub fn list_push(row: &mut Row, items: Vec<String>, left: bool) -> usize {
+    if left {
+        row.items.splice(0..0, items.into_iter().rev());
+    } else {
+        row.items.extend(items);
+    }
+
+    row.items.len()
+}
+
+/// Removes up to `count` items from the front (`left`) or the back of a list.
+///
+/// # Returns
+///
+/// Returns the removed items in the order they were removed.
+pub fn list_pop(row: &mut Row, count: usize, left: bool) -> Vec<String> {
+    let count: usize = count.min(row.items.len());
+
+    if left {
+        row.items.drain(..count).collect()
+    } else {
+        let at: usize = row.items.len() - count;
+        row.items.drain(at..).rev().collect()
+    }
+}
+
+/// Returns the items of a list from `start` to `stop` (both included).
+///
+/// Negative positions count from the end, -1 being the last item.
+pub fn list_range(row: &Row, start: i64, stop: i64) -> Vec<String> {
+    let len: i64 = row.items.len() as i64;
+    let start: i64 = if start < 0 { (len + start).max(0) } else { start };
+    let stop: i64 = if stop < 0 { len + stop } else { stop.min(len - 1) };
+
+    if start > stop || start >= len {
+        return Vec::new();
+    }
+
+    row.items[start as usize..=stop as usize].to_vec()
+}
+
+/// Adds items to a set, keeping it sorted.
+///
+/// # Returns
+///
+/// Returns the number of items which were not in the set yet.
+pub fn set_add(row: &mut Row, items: Vec<String>) -> usize {
+    let mut added: usize = 0;
+
+    for item in items {
+        if let Err(at) = row.items.binary_search(&item) {
+            row.items.insert(at, item);
+            added += 1;
+        }
+    }
+
+    added
+}
+
+/// Removes items from a set.
+///
+/// # Returns
+///
+/// Returns the number of removed items.
+pub fn set_remove(row: &mut Row, items: &[String]) -> usize {
+    let mut removed: usize = 0;
+
+    for item in items {
+        if let Ok(at) = row.items.binary_search(item) {
+            row.items.remove(at);
+            removed += 1;
+        }
+    }
+
+    removed
+}
+
+/// Checks if an item is in a set.
+pub fn set_contains(row: &Row, item: &str) -> bool {
+    row.items.binary_search_by(|probe| probe.as_str().cmp(item)).is_ok()
+}
+
+/// Sets fields of a hash.
+///
+/// # Returns
+///
+/// Returns the number of fields which were not in the hash yet.
+pub fn hash_set(row: &mut Row, fields: HashMap<String, String>) -> usize {
+    fields.into_iter()
+        .filter(|(name, value)| row.fields.insert(name.clone(), value.clone()).is_none())
+        .count()
+}
+
+/// Deletes fields of a hash.
+///
+/// # Returns
+///
+/// Returns the number of deleted fields.
+pub fn hash_delete(row: &mut Row, names: &[String]) -> usize {
+    names.iter()
+        .filter(|name| row.fields.remove(*name).is_some())
+        .count()
+}
@@ -1,5 +1,11 @@
+pub mod collection;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::protos::row::Row;
+
 /// Array of valid data types.
-pub static TYPES: [&str; 8] = [
+pub static TYPES: [&str; 12] = [
     "string",
     "int",
     "uint",
@@ -7,7 +13,11 @@ pub static TYPES: [&str; 8] = [
     "bool",
     "date",
     "timestamp",
-    "json"
+    "json",
+    "bytes",
+    "list",
+    "set",
+    "hash"
 ];
 
 /// Checks if the provided data is valid for a given data type.
@@ -53,6 +63,121 @@ pub fn is_valid_data(data: &str, data_type: &str) -> Result<(), String> {
             Ok(_) => Ok(()),
             Err(err) => Err(err.to_string())
         },
+        "bytes" => decode_bytes(data).map(|_| ()),
+        "list" | "set" | "hash" => collection::validate(data, data_type),
         _ => Err("provided invalid type".to_string()),
     }
-}
\ No newline at end of file
+}
+
+/// Decodes the base64 text form of a `bytes` value.
+///
+/// # Arguments
+///
+/// * `data` - Base64 encoded value.
+///
+/// # Returns
+///
+/// * The raw bytes, otherwise `Err` with a descriptive error message.
+pub fn decode_bytes(data: &str) -> Result<Vec<u8>, String> {
+    STANDARD.decode(data.trim()).map_err(|err| format!("not a base64 value - {}", err))
+}
+
+/// Encodes raw bytes into the base64 text form of a `bytes` value.
+pub fn encode_bytes(data: &[u8]) -> String {
+    STANDARD.encode(data)
+}
+
+/// Stores a value in a row, a `bytes` value is decoded from base64 into the `data` field
+/// and a collection value into the `items` or `fields` of the row.
+///
+/// # Arguments
+///
+/// * `row` - The row to be filled.
+/// * `value` - The value in its text form.
+/// * `data_type` - The type of the value.
+///
+/// # Returns
+///
+/// * `Ok(())` if the value is stored, otherwise `Err` with a descriptive error message.
+pub fn set_row_value(row: &mut Row, value: &str, data_type: &str) -> Result<(), String> {
+    row.items.clear();
+    row.fields.clear();
+
+    if data_type == "bytes" {
+        row.set_data(decode_bytes(value)?);
+        row.set_value(String::new());
+    } else if collection::is_collection(data_type) {
+        row.clear_data();
+        collection::set_value(row, value, data_type)?;
+        row.set_value(String::new());
+    } else {
+        row.clear_data();
+        row.set_value(value.to_string());
+    }
+    row.set_type(data_type.to_string());
+
+    Ok(())
+}
+
+/// Returns the value of a row in its text form, a `bytes` value is encoded into base64
+/// and a collection value into JSON.
+pub fn row_value(row: &Row) -> String {
+    if row.type_() == "bytes" {
+        return encode_bytes(row.data());
+    }
+
+    if collection::is_collection(row.type_()) {
+        return collection::value(row);
+    }
+
+    row.value().to_string()
+}
+
+/// Returns the size of the value of a row in bytes.
+pub fn row_size(row: &Row) -> usize {
+    row.value().len() + row.data().len() + collection::size(row)
+}
+
+/// Checks if values of a type can be incremented.
+pub fn is_numeric(data_type: &str) -> bool {
+    matches!(data_type, "int" | "uint" | "float")
+}
+
+/// Adds a delta to a numeric value, keeping the value within its type.
+///
+/// # Arguments
+///
+/// * `value` - Current value.
+/// * `data_type` - Type of the value: `int`, `uint` or `float`.
+/// * `delta` - Amount to add, may be negative; an integer for `int` and `uint`.
+///
+/// # Returns
+///
+/// * `Ok(String)` with the new value, otherwise `Err` if the value is not numeric or the result is out of range.
+pub fn add_number(value: &str, data_type: &str, delta: &str) -> Result<String, String> {
+    match data_type {
+        "int" | "uint" => {
+            let delta: i64 = delta.parse::<i64>().map_err(|_| format!("delta of an {} row must be an integer", data_type))?;
+
+            let result: Option<String> = if data_type == "int" {
+                let value: i64 = value.parse::<i64>().map_err(|e| e.to_string())?;
+                value.checked_add(delta).map(|v| v.to_string())
+            } else {
+                let value: u64 = value.parse::<u64>().map_err(|e| e.to_string())?;
+                value.checked_add_signed(delta).map(|v| v.to_string())
+            };
+
+            result.ok_or(format!("result is out of the {} range", data_type))
+        },
+        "float" => {
+            let delta: f64 = delta.parse::<f64>().map_err(|_| "delta must be a number".to_string())?;
+            let value: f64 = value.parse::<f64>().map_err(|e| e.to_string())?;
+
+            match value + delta {
+                result if result.is_finite() => Ok(result.to_string()),
+                _ => Err("result is out of the float range".to_string())
+            }
+        },
+        _ => Err(format!("{} rows can not be incremented", data_type))
+    }
+}